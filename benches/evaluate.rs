@@ -0,0 +1,46 @@
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use expression_parser::expression::Expression;
+use std::collections::HashMap;
+
+const FORMULAS: [(&str, &str); 4] = [
+    ("polynomial", "3 * x ^ 3 - 2 * x ^ 2 * y + 5 * y - 7"),
+    ("rational", "(x * y + 1) / (x ^ 2 + y ^ 2 + 1) - z / 4"),
+    (
+        "functions",
+        "sqrt(x ^ 2 + y ^ 2) * sin(z) + max(x, y, z) - exp(-x / 10)",
+    ),
+    (
+        "let",
+        "let r = x * x + y * y; let s = sqrt(r); s * z - r / (s + 1)",
+    ),
+];
+
+fn bench_evaluate(c: &mut Criterion) {
+    let vars = HashMap::from([
+        ("x".to_string(), 1.5),
+        ("y".to_string(), -2.25),
+        ("z".to_string(), 0.75),
+    ]);
+    let slots = [1.5, -2.25, 0.75];
+
+    for (name, source) in FORMULAS {
+        let expr = Expression::parse(source).unwrap();
+        let compiled = expr.compile(&["x", "y", "z"]).unwrap();
+        let program = expr.to_bytecode(&["x", "y", "z"]).unwrap();
+
+        let mut group = c.benchmark_group(name);
+        group.bench_function("tree", |b| {
+            b.iter(|| black_box(&expr).evaluate(black_box(&vars)))
+        });
+        group.bench_function("compiled", |b| {
+            b.iter(|| compiled.evaluate(black_box(&slots)))
+        });
+        group.bench_function("bytecode", |b| {
+            b.iter(|| program.evaluate(black_box(&slots)))
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_evaluate);
+criterion_main!(benches);
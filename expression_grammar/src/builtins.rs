@@ -0,0 +1,106 @@
+use std::fmt;
+
+/// Number of arguments a function accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exact(n) => count == n,
+            Arity::AtLeast(n) => count >= n,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (prefix, n) = match *self {
+            Arity::Exact(n) => ("", n),
+            Arity::AtLeast(n) => ("at least ", n),
+        };
+        let plural = if n == 1 { "" } else { "s" };
+        write!(f, "{}{} argument{}", prefix, n, plural)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    pub arity: Arity,
+    pub function: fn(&[f64]) -> f64,
+}
+
+/// Looks up a function from the standard math library
+pub fn builtin(name: &str) -> Option<Builtin> {
+    let (arity, function): (Arity, fn(&[f64]) -> f64) = match name {
+        "sin" => (Arity::Exact(1), |a| a[0].sin()),
+        "cos" => (Arity::Exact(1), |a| a[0].cos()),
+        "tan" => (Arity::Exact(1), |a| a[0].tan()),
+        "asin" => (Arity::Exact(1), |a| a[0].asin()),
+        "acos" => (Arity::Exact(1), |a| a[0].acos()),
+        "atan" => (Arity::Exact(1), |a| a[0].atan()),
+        "atan2" => (Arity::Exact(2), |a| a[0].atan2(a[1])),
+        "sinh" => (Arity::Exact(1), |a| a[0].sinh()),
+        "cosh" => (Arity::Exact(1), |a| a[0].cosh()),
+        "tanh" => (Arity::Exact(1), |a| a[0].tanh()),
+        "sqrt" => (Arity::Exact(1), |a| a[0].sqrt()),
+        "cbrt" => (Arity::Exact(1), |a| a[0].cbrt()),
+        "exp" => (Arity::Exact(1), |a| a[0].exp()),
+        "ln" => (Arity::Exact(1), |a| a[0].ln()),
+        "log" => (Arity::Exact(2), |a| a[1].log(a[0])),
+        "log2" => (Arity::Exact(1), |a| a[0].log2()),
+        "log10" => (Arity::Exact(1), |a| a[0].log10()),
+        "abs" => (Arity::Exact(1), |a| a[0].abs()),
+        "sign" => (Arity::Exact(1), |a| {
+            if a[0] == 0.0 { 0.0 } else { a[0].signum() }
+        }),
+        "floor" => (Arity::Exact(1), |a| a[0].floor()),
+        "ceil" => (Arity::Exact(1), |a| a[0].ceil()),
+        "round" => (Arity::Exact(1), |a| a[0].round()),
+        "trunc" => (Arity::Exact(1), |a| a[0].trunc()),
+        "hypot" => (Arity::Exact(2), |a| a[0].hypot(a[1])),
+        "min" => (Arity::AtLeast(1), |a| {
+            a.iter().copied().fold(f64::INFINITY, f64::min)
+        }),
+        "max" => (Arity::AtLeast(1), |a| {
+            a.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+        }),
+        _ => return None,
+    };
+
+    Some(Builtin { arity, function })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arity_accepts() {
+        assert!(Arity::Exact(2).accepts(2));
+        assert!(!Arity::Exact(2).accepts(1));
+        assert!(Arity::AtLeast(1).accepts(5));
+        assert!(!Arity::AtLeast(1).accepts(0));
+    }
+
+    #[test]
+    fn test_arity_display() {
+        assert_eq!(Arity::Exact(1).to_string(), "1 argument");
+        assert_eq!(Arity::Exact(2).to_string(), "2 arguments");
+        assert_eq!(Arity::AtLeast(1).to_string(), "at least 1 argument");
+    }
+
+    #[test]
+    fn test_builtin_lookup() {
+        let sqrt = builtin("sqrt").unwrap();
+        assert_eq!(sqrt.arity, Arity::Exact(1));
+        assert_eq!((sqrt.function)(&[9.0]), 3.0);
+
+        assert_eq!((builtin("log").unwrap().function)(&[2.0, 8.0]), 3.0);
+        assert_eq!((builtin("max").unwrap().function)(&[1.0, 7.0, 3.0]), 7.0);
+        assert!(builtin("nope").is_none());
+    }
+}
@@ -0,0 +1,301 @@
+use crate::error::ParseError;
+use crate::parsing::Span;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: &str) -> Self {
+        Label {
+            span,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// A report pointing into the source, with a primary label underlined `^~~~`
+/// and any number of secondary labels underlined `----`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub primary: Label,
+    pub secondary: Vec<Label>,
+}
+
+impl Diagnostic {
+    pub fn new(message: &str, primary: Label) -> Self {
+        Diagnostic {
+            message: message.to_string(),
+            primary,
+            secondary: Vec::new(),
+        }
+    }
+
+    pub fn with_secondary(mut self, label: Label) -> Self {
+        self.secondary.push(label);
+        self
+    }
+
+    /// Renders the report as plain text
+    pub fn render(&self, source: &str) -> String {
+        self.render_with(source, false)
+    }
+
+    /// Renders the report with ANSI colors for terminals
+    pub fn render_colored(&self, source: &str) -> String {
+        self.render_with(source, true)
+    }
+
+    fn render_with(&self, source: &str, colored: bool) -> String {
+        let paint = |color: &str, text: &str| {
+            if colored {
+                format!("{}{}{}", color, text, RESET)
+            } else {
+                text.to_string()
+            }
+        };
+
+        let lines = split_lines(source);
+        let (line, column) = locate(&lines, self.primary.span.start);
+
+        let mut labels: Vec<(&Label, bool)> = vec![(&self.primary, true)];
+        labels.extend(self.secondary.iter().map(|label| (label, false)));
+
+        let mut line_numbers: Vec<usize> = labels
+            .iter()
+            .map(|(label, _)| locate(&lines, label.span.start).0)
+            .collect();
+        line_numbers.sort_unstable();
+        line_numbers.dedup();
+
+        let width = (line_numbers.last().copied().unwrap_or(0) + 1)
+            .to_string()
+            .len();
+        let gutter = paint(BLUE, &format!("{} |", " ".repeat(width)));
+
+        let mut out = format!(
+            "{}{}\n",
+            paint(RED, "error"),
+            paint(BOLD, &format!(": {}", self.message))
+        );
+        out += &format!(
+            "{}{} {}:{}\n",
+            " ".repeat(width),
+            paint(BLUE, "-->"),
+            line + 1,
+            column + 1
+        );
+        out += &format!("{}\n", gutter);
+
+        for number in line_numbers {
+            let (start, text) = lines[number];
+            let number_gutter = paint(BLUE, &format!("{:>width$} |", number + 1));
+            out += &format!("{} {}\n", number_gutter, text);
+
+            for (label, primary) in &labels {
+                if locate(&lines, label.span.start).0 != number {
+                    continue;
+                }
+
+                let line_end = start + text.len();
+                let label_start = label.span.start.min(line_end);
+                let label_end = label.span.end.clamp(label_start, line_end);
+                let column = text[..label_start - start].chars().count();
+                let length = text[label_start - start..label_end - start]
+                    .chars()
+                    .count()
+                    .max(1);
+
+                let (marker, color) = if *primary {
+                    (format!("^{}", "~".repeat(length - 1)), RED)
+                } else {
+                    ("-".repeat(length), BLUE)
+                };
+                out += &format!(
+                    "{} {}{}\n",
+                    gutter,
+                    " ".repeat(column),
+                    paint(color, &format!("{} {}", marker, label.message))
+                );
+            }
+        }
+
+        out
+    }
+}
+
+/// Lines of `source` paired with the byte offset each one starts at
+fn split_lines(source: &str) -> Vec<(usize, &str)> {
+    let mut start = 0;
+    source
+        .split('\n')
+        .map(|line| {
+            let entry = (start, line);
+            start += line.len() + 1;
+            entry
+        })
+        .collect()
+}
+
+/// Zero based line and character column of a byte offset
+fn locate(lines: &[(usize, &str)], offset: usize) -> (usize, usize) {
+    let line = lines
+        .iter()
+        .rposition(|&(start, _)| start <= offset)
+        .unwrap_or(0);
+    let (start, text) = lines[line];
+    let offset = offset.min(start + text.len());
+
+    (line, text[..offset - start].chars().count())
+}
+
+impl ParseError {
+    pub fn diagnostic(&self) -> Diagnostic {
+        let message = self.message();
+        let span = self.span();
+        match self {
+            ParseError::UnexpectedCharacter { .. } => {
+                Diagnostic::new(&message, Label::new(span, "not valid in an expression"))
+            }
+            ParseError::InvalidNumber { .. } => {
+                Diagnostic::new(&message, Label::new(span, "not a valid number"))
+            }
+            ParseError::InvalidEscape { .. } => Diagnostic::new(
+                &message,
+                Label::new(span, "expected '\\\"', '\\\\', '\\n' or '\\t'"),
+            ),
+            ParseError::UnterminatedString { .. } => Diagnostic::new(
+                &message,
+                Label::new(span, "expected '\"' to close the string"),
+            ),
+            ParseError::UnexpectedToken { .. } | ParseError::UnexpectedEnd { .. } => {
+                Diagnostic::new(
+                    &message,
+                    Label::new(span, "expected a number, variable or '('"),
+                )
+            }
+            ParseError::UnclosedParenthesis { open, .. } => {
+                Diagnostic::new(&message, Label::new(span, "expected ')' to close '('"))
+                    .with_secondary(Label::new(*open, "'(' opened here"))
+            }
+            ParseError::ExpectedArgumentDelimiter { open, .. } => {
+                Diagnostic::new(&message, Label::new(span, "expected ',' or ')'"))
+                    .with_secondary(Label::new(*open, "argument list opened here"))
+            }
+            ParseError::ExpectedListDelimiter { open, .. } => {
+                Diagnostic::new(&message, Label::new(span, "expected ',' or ']'"))
+                    .with_secondary(Label::new(*open, "list opened here"))
+            }
+            ParseError::ExpectedColon { question, .. } => {
+                Diagnostic::new(&message, Label::new(span, "expected ':'"))
+                    .with_secondary(Label::new(*question, "conditional started here"))
+            }
+            ParseError::ConditionalArity { .. } => Diagnostic::new(
+                &message,
+                Label::new(span, "expected a condition and two branches"),
+            ),
+            ParseError::ExpectedEquals { .. } => {
+                Diagnostic::new(&message, Label::new(span, "expected '=' after the name"))
+            }
+            ParseError::ExpectedSeparator { .. } => Diagnostic::new(
+                &message,
+                Label::new(span, "expected ';' or a new line before this"),
+            ),
+            ParseError::UnclosedInterpolation { .. } => {
+                Diagnostic::new(&message, Label::new(span, "expected '}' to close '#{'"))
+            }
+            ParseError::TrailingInput { .. } => {
+                Diagnostic::new(&message, Label::new(span, "expected end of input"))
+            }
+        }
+    }
+
+    /// Renders the error as a caret-style report against the parsed `source`
+    pub fn render(&self, source: &str) -> String {
+        self.diagnostic().render(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_render_unclosed_parenthesis() {
+        let source = "2 * (x + 1";
+        let error = parse(source).unwrap_err();
+
+        assert_eq!(
+            error.render(source),
+            "\
+error: Expected closing parenthesis
+ --> 1:11
+  |
+1 | 2 * (x + 1
+  |           ^ expected ')' to close '('
+  |     - '(' opened here
+"
+        );
+    }
+
+    #[test]
+    fn test_render_multi_character_span() {
+        let source = "1 + 2.3.4";
+        let error = parse(source).unwrap_err();
+
+        assert_eq!(
+            error.render(source),
+            "\
+error: Invalid number '2.3.4'
+ --> 1:5
+  |
+1 | 1 + 2.3.4
+  |     ^~~~~ not a valid number
+"
+        );
+    }
+
+    #[test]
+    fn test_render_multiple_lines() {
+        let source = "max(1,\n    2 3)";
+        let diagnostic = Diagnostic::new(
+            "Expected ',' or ')' in argument list",
+            Label::new(Span::new(13, 14), "expected ',' or ')'"),
+        )
+        .with_secondary(Label::new(Span::new(3, 4), "argument list opened here"));
+
+        assert_eq!(
+            diagnostic.render(source),
+            "\
+error: Expected ',' or ')' in argument list
+ --> 2:7
+  |
+1 | max(1,
+  |    - argument list opened here
+2 |     2 3)
+  |       ^ expected ',' or ')'
+"
+        );
+    }
+
+    #[test]
+    fn test_render_colored() {
+        let source = "x @ y";
+        let rendered = parse(source)
+            .unwrap_err()
+            .diagnostic()
+            .render_colored(source);
+
+        assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m"));
+        assert!(rendered.contains("\x1b[1;31m^ not valid in an expression\x1b[0m"));
+    }
+}
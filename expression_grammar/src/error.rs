@@ -0,0 +1,170 @@
+use crate::parsing::{Span, TokenKind};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnexpectedCharacter {
+        character: char,
+        span: Span,
+    },
+    InvalidNumber {
+        text: String,
+        span: Span,
+    },
+    /// A `\` in a string literal followed by anything but `"`, `\`, `n` or `t`
+    InvalidEscape {
+        character: char,
+        span: Span,
+    },
+    /// `"` without its closing `"`
+    UnterminatedString {
+        span: Span,
+    },
+    UnexpectedToken {
+        found: TokenKind,
+        span: Span,
+    },
+    UnexpectedEnd {
+        span: Span,
+    },
+    /// `open` is the location of the unmatched `(`
+    UnclosedParenthesis {
+        span: Span,
+        open: Span,
+    },
+    /// `open` is the location of the `(` starting the argument list
+    ExpectedArgumentDelimiter {
+        span: Span,
+        open: Span,
+    },
+    /// `open` is the location of the `[` starting the list
+    ExpectedListDelimiter {
+        span: Span,
+        open: Span,
+    },
+    /// `question` is the location of the `?` missing its `:`
+    ExpectedColon {
+        span: Span,
+        question: Span,
+    },
+    /// `if(...)` called with other than three arguments
+    ConditionalArity {
+        found: usize,
+        span: Span,
+    },
+    /// `let name` not followed by `=`
+    ExpectedEquals {
+        span: Span,
+    },
+    /// The value of a `let` binding or assignment followed by neither `;` nor a line break
+    ExpectedSeparator {
+        span: Span,
+    },
+    /// `#{` without its closing `}`
+    UnclosedInterpolation {
+        span: Span,
+    },
+    /// A complete expression was parsed but tokens remain after it
+    TrailingInput {
+        found: TokenKind,
+        span: Span,
+    },
+}
+
+impl ParseError {
+    /// Location in the input where the error was detected
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedCharacter { span, .. }
+            | ParseError::InvalidNumber { span, .. }
+            | ParseError::InvalidEscape { span, .. }
+            | ParseError::UnterminatedString { span }
+            | ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnexpectedEnd { span }
+            | ParseError::UnclosedParenthesis { span, .. }
+            | ParseError::ExpectedArgumentDelimiter { span, .. }
+            | ParseError::ExpectedListDelimiter { span, .. }
+            | ParseError::ExpectedColon { span, .. }
+            | ParseError::ConditionalArity { span, .. }
+            | ParseError::ExpectedEquals { span }
+            | ParseError::ExpectedSeparator { span }
+            | ParseError::UnclosedInterpolation { span }
+            | ParseError::TrailingInput { span, .. } => *span,
+        }
+    }
+}
+
+impl ParseError {
+    /// Description of the error without its location
+    pub fn message(&self) -> String {
+        match self {
+            ParseError::UnexpectedCharacter { character, .. } => {
+                format!("Unexpected character '{}'", character)
+            }
+            ParseError::InvalidNumber { text, .. } => format!("Invalid number '{}'", text),
+            ParseError::InvalidEscape { character, .. } => {
+                format!("Invalid escape '\\{}' in string", character)
+            }
+            ParseError::UnterminatedString { .. } => "Unterminated string".to_string(),
+            ParseError::UnexpectedToken { found, .. } => format!("Unexpected token '{}'", found),
+            ParseError::UnexpectedEnd { .. } => "Unexpected end of input".to_string(),
+            ParseError::UnclosedParenthesis { .. } => "Expected closing parenthesis".to_string(),
+            ParseError::ExpectedArgumentDelimiter { .. } => {
+                "Expected ',' or ')' in argument list".to_string()
+            }
+            ParseError::ExpectedListDelimiter { .. } => "Expected ',' or ']' in list".to_string(),
+            ParseError::ExpectedColon { .. } => "Expected ':' in conditional".to_string(),
+            ParseError::ConditionalArity { found, .. } => {
+                format!("Function 'if' expects 3 arguments, got {}", found)
+            }
+            ParseError::ExpectedEquals { .. } => "Expected '=' in statement".to_string(),
+            ParseError::ExpectedSeparator { .. } => {
+                "Expected ';' or a line break after statement".to_string()
+            }
+            ParseError::UnclosedInterpolation { .. } => "Unclosed interpolation".to_string(),
+            ParseError::TrailingInput { found, .. } => {
+                format!("Unexpected token '{}' after expression", found)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message(), self.span())
+    }
+}
+
+impl Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_error_display() {
+        let error = ParseError::UnexpectedCharacter {
+            character: '@',
+            span: Span::new(2, 3),
+        };
+        assert_eq!(error.to_string(), "Unexpected character '@' at 2..3");
+        assert_eq!(error.span(), Span::new(2, 3));
+
+        let error = ParseError::TrailingInput {
+            found: TokenKind::RParen,
+            span: Span::new(5, 6),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Unexpected token ')' after expression at 5..6"
+        );
+
+        // Raised for assignments as well as `let` bindings
+        let error = crate::parse("x = 1 y = 2").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Expected ';' or a line break after statement at 6..7"
+        );
+    }
+}
@@ -0,0 +1,42 @@
+//! Tokenizer, parser and syntax tree for the expression language.
+//!
+//! Both `expression_parser` and the `expr!` macro parse through this crate, so the
+//! runtime and compile time parsers always accept exactly the same input. The
+//! [`builtins`] are part of the language too, so the macros can check and fold calls.
+
+pub mod builtins;
+pub mod diagnostic;
+pub mod error;
+pub mod parsing;
+pub mod syntax;
+
+use error::ParseError;
+use parsing::{Parser, ParserConfig, tokenize, tokenize_interpolated, tokenize_recovering};
+use syntax::Syntax;
+
+pub fn parse(input: &str) -> Result<Syntax, ParseError> {
+    Parser::new(tokenize(input)?).parse()
+}
+
+pub fn parse_with_config(input: &str, config: ParserConfig) -> Result<Syntax, ParseError> {
+    Parser::with_config(tokenize(input)?, config).parse()
+}
+
+/// Parses `input` allowing `#{...}` splices of Rust code, which the runtime parser rejects
+pub fn parse_interpolated(input: &str) -> Result<Syntax, ParseError> {
+    Parser::new(tokenize_interpolated(input)?).parse()
+}
+
+/// Parses `input` reporting every syntax error, both from the tokenizer and the parser.
+///
+/// Always produces a tree; the parts that could not be parsed are [`SyntaxKind::Error`] nodes.
+///
+/// [`SyntaxKind::Error`]: syntax::SyntaxKind::Error
+pub fn parse_recovering(input: &str) -> (Syntax, Vec<ParseError>) {
+    let (tokens, mut errors) = tokenize_recovering(input);
+    let (syntax, parse_errors) = Parser::new(tokens).parse_recovering();
+    errors.extend(parse_errors);
+    errors.sort_by_key(|error| error.span().start);
+
+    (syntax, errors)
+}
@@ -0,0 +1,1447 @@
+use crate::error::ParseError;
+use crate::syntax::{Syntax, SyntaxKind};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Byte range `start..end` into the parsed input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Number(f64),
+    Variable(String),
+    /// A `"..."` literal with its escapes already resolved
+    String(String),
+    Plus,         // +
+    Minus,        // -
+    Star,         // *
+    Slash,        // /
+    Caret,        // ^
+    LParen,       // (
+    RParen,       // )
+    Comma,        // ,
+    LBracket,     // [
+    RBracket,     // ]
+    Less,         // <
+    LessEqual,    // <=
+    Greater,      // >
+    GreaterEqual, // >=
+    EqualEqual,   // ==
+    BangEqual,    // !=
+    AndAnd,       // &&
+    OrOr,         // ||
+    Bang,         // !
+    Question,     // ?
+    Colon,        // :
+    Equal,        // =
+    Semicolon,    // ;
+    /// A line break, which the parser only uses to end `let` bindings
+    Newline,
+    /// Unrecognised input, only produced by [`tokenize_recovering`]
+    Invalid(String),
+    /// The Rust expression inside `#{...}`, only produced by [`tokenize_interpolated`]
+    Interpolation(String),
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Number(n) => write!(f, "{}", n),
+            TokenKind::Variable(name) => write!(f, "{}", name),
+            TokenKind::String(text) => write_string(f, text),
+            TokenKind::Plus => write!(f, "+"),
+            TokenKind::Minus => write!(f, "-"),
+            TokenKind::Star => write!(f, "*"),
+            TokenKind::Slash => write!(f, "/"),
+            TokenKind::Caret => write!(f, "^"),
+            TokenKind::LParen => write!(f, "("),
+            TokenKind::RParen => write!(f, ")"),
+            TokenKind::Comma => write!(f, ","),
+            TokenKind::LBracket => write!(f, "["),
+            TokenKind::RBracket => write!(f, "]"),
+            TokenKind::Less => write!(f, "<"),
+            TokenKind::LessEqual => write!(f, "<="),
+            TokenKind::Greater => write!(f, ">"),
+            TokenKind::GreaterEqual => write!(f, ">="),
+            TokenKind::EqualEqual => write!(f, "=="),
+            TokenKind::BangEqual => write!(f, "!="),
+            TokenKind::AndAnd => write!(f, "&&"),
+            TokenKind::OrOr => write!(f, "||"),
+            TokenKind::Bang => write!(f, "!"),
+            TokenKind::Question => write!(f, "?"),
+            TokenKind::Colon => write!(f, ":"),
+            TokenKind::Equal => write!(f, "="),
+            TokenKind::Semicolon => write!(f, ";"),
+            TokenKind::Newline => writeln!(f),
+            TokenKind::Invalid(text) => write!(f, "{}", text),
+            TokenKind::Interpolation(code) => write!(f, "#{{{}}}", code),
+        }
+    }
+}
+
+/// Writes `text` as a string literal the tokenizer reads back unchanged
+pub fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    /// Short-circuiting, only evaluates the right operand if the left is true
+    And,
+    /// Short-circuiting, only evaluates the right operand if the left is false
+    Or,
+}
+
+impl BinaryOperator {
+    fn from_token(token: &TokenKind) -> Option<Self> {
+        match token {
+            TokenKind::Plus => Some(BinaryOperator::Add),
+            TokenKind::Minus => Some(BinaryOperator::Subtract),
+            TokenKind::Star => Some(BinaryOperator::Multiply),
+            TokenKind::Slash => Some(BinaryOperator::Divide),
+            TokenKind::Caret => Some(BinaryOperator::Power),
+            TokenKind::Less => Some(BinaryOperator::Less),
+            TokenKind::LessEqual => Some(BinaryOperator::LessEqual),
+            TokenKind::Greater => Some(BinaryOperator::Greater),
+            TokenKind::GreaterEqual => Some(BinaryOperator::GreaterEqual),
+            TokenKind::EqualEqual => Some(BinaryOperator::Equal),
+            TokenKind::BangEqual => Some(BinaryOperator::NotEqual),
+            TokenKind::AndAnd => Some(BinaryOperator::And),
+            TokenKind::OrOr => Some(BinaryOperator::Or),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Power => "^",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+        }
+    }
+
+    fn apply(self, lhs: Syntax, rhs: Syntax) -> Syntax {
+        let span = Span::new(lhs.span.start, rhs.span.end);
+        let kind = SyntaxKind::Binary {
+            operator: self,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        Syntax::new(kind, span)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperatorInfo {
+    pub precedence: u8,
+    pub associativity: Associativity,
+}
+
+/// Precedence and associativity of every operator the parser understands.
+///
+/// Higher precedence binds tighter. The default follows the usual mathematical
+/// conventions: `+ -` < `* /` < prefix `-` < `^`, with `^` right-associative. Below the
+/// arithmetic operators, as in C, come `< <= > >=`, then `== !=`, then `&&` and last `||`.
+/// The conditional `c ? a : b` binds loosest of all and is not configurable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParserConfig {
+    operators: HashMap<BinaryOperator, OperatorInfo>,
+    prefix_precedence: u8,
+}
+
+impl ParserConfig {
+    pub fn operator(&self, operator: BinaryOperator) -> OperatorInfo {
+        self.operators[&operator]
+    }
+
+    pub fn prefix_precedence(&self) -> u8 {
+        self.prefix_precedence
+    }
+
+    pub fn with_operator(
+        mut self,
+        operator: BinaryOperator,
+        precedence: u8,
+        associativity: Associativity,
+    ) -> Self {
+        self.operators.insert(
+            operator,
+            OperatorInfo {
+                precedence,
+                associativity,
+            },
+        );
+        self
+    }
+
+    /// Sets how tightly prefix `-`, `+` and `!` bind; above `^` makes `-2 ^ 2` equal `4`
+    pub fn with_prefix_precedence(mut self, precedence: u8) -> Self {
+        self.prefix_precedence = precedence;
+        self
+    }
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        ParserConfig {
+            operators: HashMap::new(),
+            prefix_precedence: 30,
+        }
+        .with_operator(BinaryOperator::Add, 10, Associativity::Left)
+        .with_operator(BinaryOperator::Subtract, 10, Associativity::Left)
+        .with_operator(BinaryOperator::Multiply, 20, Associativity::Left)
+        .with_operator(BinaryOperator::Divide, 20, Associativity::Left)
+        .with_operator(BinaryOperator::Power, 40, Associativity::Right)
+        .with_operator(BinaryOperator::Less, 6, Associativity::Left)
+        .with_operator(BinaryOperator::LessEqual, 6, Associativity::Left)
+        .with_operator(BinaryOperator::Greater, 6, Associativity::Left)
+        .with_operator(BinaryOperator::GreaterEqual, 6, Associativity::Left)
+        .with_operator(BinaryOperator::Equal, 5, Associativity::Left)
+        .with_operator(BinaryOperator::NotEqual, 5, Associativity::Left)
+        .with_operator(BinaryOperator::And, 4, Associativity::Left)
+        .with_operator(BinaryOperator::Or, 3, Associativity::Left)
+    }
+}
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    /// Whether each token is the first on its line, in place of the [`TokenKind::Newline`]
+    /// tokens which are removed from `tokens`
+    line_starts: Vec<bool>,
+    current: usize,
+    config: ParserConfig,
+    /// Set by [`Parser::parse_recovering`] to collect errors instead of stopping at the first
+    errors: Option<Vec<ParseError>>,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Parser::with_config(tokens, ParserConfig::default())
+    }
+
+    pub fn with_config(tokens: Vec<Token>, config: ParserConfig) -> Self {
+        let mut line_starts = Vec::with_capacity(tokens.len());
+        let mut line_start = false;
+        let tokens = tokens
+            .into_iter()
+            .filter(|token| {
+                if token.kind == TokenKind::Newline {
+                    line_start = true;
+                    return false;
+                }
+                line_starts.push(std::mem::take(&mut line_start));
+                true
+            })
+            .collect();
+
+        Parser {
+            tokens,
+            line_starts,
+            current: 0,
+            config,
+            errors: None,
+        }
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.current).map(|token| &token.kind)
+    }
+
+    fn peek_next(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.current + 1).map(|token| &token.kind)
+    }
+
+    fn advance(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.current);
+        if token.is_some() {
+            self.current += 1;
+        }
+        token
+    }
+
+    fn recovering(&self) -> bool {
+        self.errors.is_some()
+    }
+
+    /// Span of the next token, or an empty span just past the last one at end of input
+    fn current_span(&self) -> Span {
+        match self.tokens.get(self.current) {
+            Some(token) => token.span,
+            None => {
+                let end = self.tokens.last().map_or(0, |token| token.span.end);
+                Span::new(end, end)
+            }
+        }
+    }
+
+    /// End of the most recently consumed token
+    fn previous_end(&self) -> usize {
+        match self.current.checked_sub(1) {
+            Some(previous) => self.tokens[previous].span.end,
+            None => 0,
+        }
+    }
+
+    /// Fails with `error`, or records it and carries on when recovering
+    fn report(&mut self, error: ParseError) -> Result<(), ParseError> {
+        match &mut self.errors {
+            Some(errors) => {
+                errors.push(error);
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+
+    /// Skips ahead to the first of `stop` that is not nested in brackets, without consuming it
+    fn synchronize(&mut self, stop: &[TokenKind]) {
+        let mut depth = 0;
+        while let Some(kind) = self.peek() {
+            if depth == 0 && stop.contains(kind) {
+                return;
+            }
+            match kind {
+                TokenKind::LParen | TokenKind::LBracket => depth += 1,
+                TokenKind::RParen | TokenKind::RBracket if depth > 0 => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Parses the whole token stream as a script, see [`Parser::parse_script`]
+    pub fn parse(&mut self) -> Result<Syntax, ParseError> {
+        let mut expr = self.parse_script()?;
+
+        while let Some(token) = self.tokens.get(self.current) {
+            // When recovering, invalid tokens were already reported by the tokenizer and a
+            // token the expression stopped at may already have been reported as unexpected
+            let reported = matches!(token.kind, TokenKind::Invalid(_))
+                || self
+                    .errors
+                    .as_ref()
+                    .and_then(|errors| errors.last())
+                    .is_some_and(|error| error.span() == token.span);
+            if !(self.recovering() && reported) {
+                self.report(ParseError::TrailingInput {
+                    found: token.kind.clone(),
+                    span: token.span,
+                })?;
+            }
+            self.advance();
+            expr = self.parse_binary_from(expr, 0)?;
+        }
+
+        Ok(expr)
+    }
+
+    /// Parses the whole token stream, reporting every syntax error instead of stopping at the first.
+    ///
+    /// Unparseable parts of the input become [`SyntaxKind::Error`] nodes in the returned tree.
+    pub fn parse_recovering(&mut self) -> (Syntax, Vec<ParseError>) {
+        self.errors = Some(Vec::new());
+        let expr = self
+            .parse()
+            .expect("a recovering parser reports errors instead of returning them");
+
+        (expr, self.errors.take().unwrap_or_default())
+    }
+
+    /// Parses the longest leading expression and hands back the tokens that follow it
+    pub fn parse_prefix(mut self) -> Result<(Syntax, Vec<Token>), ParseError> {
+        let expr = self.parse_expression()?;
+        let rest = self.tokens.split_off(self.current);
+
+        Ok((expr, rest))
+    }
+
+    /// An expression that may start with statements setting variables, `x = x + 1; x * 2`.
+    ///
+    /// Assignments are only statements of the script as a whole, not of the expressions
+    /// within it, so they read and write the same variables wherever they appear.
+    pub fn parse_script(&mut self) -> Result<Syntax, ParseError> {
+        match self.parse_statement(true)? {
+            Some(statement) => Ok(statement),
+            None => self.parse_conditional(),
+        }
+    }
+
+    pub fn parse_expression(&mut self) -> Result<Syntax, ParseError> {
+        match self.parse_statement(false)? {
+            Some(binding) => Ok(binding),
+            None => self.parse_conditional(),
+        }
+    }
+
+    fn parse_conditional(&mut self) -> Result<Syntax, ParseError> {
+        let condition = self.parse_binary(0)?;
+        if self.peek() != Some(&TokenKind::Question) {
+            return Ok(condition);
+        }
+        let question = self.current_span();
+        self.advance();
+
+        let then = self.parse_expression()?;
+        if self.peek() == Some(&TokenKind::Colon) {
+            self.advance();
+        } else {
+            self.report(ParseError::ExpectedColon {
+                span: self.current_span(),
+                question,
+            })?;
+        }
+        // Right-associative, so `a ? b : c ? d : e` is `a ? b : (c ? d : e)`
+        let otherwise = self.parse_expression()?;
+
+        Ok(conditional(condition, then, otherwise))
+    }
+
+    /// `let name = value; body`, binding `name` to `value` for the rest of the expression,
+    /// or in a script the assignment `name = value; body`.
+    ///
+    /// `let` is only a keyword when a name follows it, so it remains usable as a variable.
+    /// A line break ends the statement in place of `;` where `value` cannot continue onto
+    /// the next line, as it can with a leading binary operator. The body of the last
+    /// assignment in a script can be left out.
+    fn parse_statement(&mut self, script: bool) -> Result<Option<Syntax>, ParseError> {
+        let start = self.current_span().start;
+        let (name, assignment) = match (self.peek(), self.peek_next()) {
+            (Some(TokenKind::Variable(keyword)), Some(TokenKind::Variable(name)))
+                if keyword == "let" =>
+            {
+                let name = name.clone();
+                self.advance();
+                (name, None)
+            }
+            (Some(TokenKind::Variable(name)), Some(TokenKind::Equal)) if script => {
+                (name.clone(), Some(self.current_span()))
+            }
+            _ => return Ok(None),
+        };
+        self.advance();
+
+        if self.peek() == Some(&TokenKind::Equal) {
+            self.advance();
+        } else {
+            self.report(ParseError::ExpectedEquals {
+                span: self.current_span(),
+            })?;
+        }
+        let value = self.parse_expression()?;
+
+        match self.peek() {
+            Some(TokenKind::Semicolon) => {
+                self.advance();
+            }
+            // The missing body is reported as an unexpected end
+            None => {}
+            Some(_) if self.line_starts[self.current] => {}
+            Some(_) => self.report(ParseError::ExpectedSeparator {
+                span: self.current_span(),
+            })?,
+        }
+
+        let body = match assignment {
+            // Nothing follows the last assignment of a script, which results in the variable
+            Some(target) if self.peek().is_none() => {
+                Syntax::new(SyntaxKind::Variable(name.clone()), target)
+            }
+            _ if script => self.parse_script()?,
+            _ => self.parse_expression()?,
+        };
+
+        let span = Span::new(start, body.span.end.max(value.span.end));
+        let (value, body) = (Box::new(value), Box::new(body));
+        let kind = match assignment {
+            Some(_) => SyntaxKind::Assign { name, value, body },
+            None => SyntaxKind::Let { name, value, body },
+        };
+        Ok(Some(Syntax::new(kind, span)))
+    }
+
+    /// Precedence climbing over the binary operators in the parser's [`ParserConfig`]
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Syntax, ParseError> {
+        let lhs = self.parse_unary()?;
+        self.parse_binary_from(lhs, min_precedence)
+    }
+
+    fn parse_binary_from(
+        &mut self,
+        mut expr: Syntax,
+        min_precedence: u8,
+    ) -> Result<Syntax, ParseError> {
+        while let Some(operator) = self.peek().and_then(BinaryOperator::from_token) {
+            let info = self.config.operator(operator);
+            if info.precedence < min_precedence {
+                break;
+            }
+            self.advance();
+
+            let next_precedence = match info.associativity {
+                Associativity::Left => info.precedence.checked_add(1),
+                Associativity::Right => Some(info.precedence),
+            };
+            let rhs = match next_precedence {
+                Some(precedence) => self.parse_binary(precedence)?,
+                // Nothing binds tighter than a left-associative operator at `u8::MAX`
+                None => self.parse_unary()?,
+            };
+            expr = operator.apply(expr, rhs);
+        }
+
+        Ok(expr)
+    }
+
+    /// By default prefix `-`, `+` and `!` bind looser than `^`, so `-2 ^ 2` is `-(2 ^ 2)`
+    fn parse_unary(&mut self) -> Result<Syntax, ParseError> {
+        let start = self.current_span().start;
+        match self.peek() {
+            Some(TokenKind::Bang) => {
+                self.advance();
+                let operand = self.parse_binary(self.config.prefix_precedence)?;
+                let span = Span::new(start, operand.span.end);
+                Ok(Syntax::new(SyntaxKind::Not(Box::new(operand)), span))
+            }
+            Some(TokenKind::Minus) => {
+                self.advance();
+                let operand = self.parse_binary(self.config.prefix_precedence)?;
+                let span = Span::new(start, operand.span.end);
+                Ok(Syntax::new(SyntaxKind::Negate(Box::new(operand)), span))
+            }
+            Some(TokenKind::Plus) => {
+                self.advance();
+                let operand = self.parse_binary(self.config.prefix_precedence)?;
+                Ok(Syntax::new(
+                    operand.kind,
+                    Span::new(start, operand.span.end),
+                ))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Syntax, ParseError> {
+        let span = self.current_span();
+        let kind = match self.peek() {
+            Some(kind) => kind.clone(),
+            None => {
+                self.report(ParseError::UnexpectedEnd { span })?;
+                return Ok(Syntax::new(SyntaxKind::Error, span));
+            }
+        };
+
+        match kind {
+            TokenKind::Number(n) => {
+                self.advance();
+                Ok(Syntax::new(SyntaxKind::Number(n), span))
+            }
+            TokenKind::String(text) => {
+                self.advance();
+                Ok(Syntax::new(SyntaxKind::String(text), span))
+            }
+            TokenKind::Variable(name) => {
+                self.advance();
+                match name.as_str() {
+                    "true" => return Ok(Syntax::new(SyntaxKind::Bool(true), span)),
+                    "false" => return Ok(Syntax::new(SyntaxKind::Bool(false), span)),
+                    "null" => return Ok(Syntax::new(SyntaxKind::Null, span)),
+                    _ => {}
+                }
+                if let Some(TokenKind::LParen) = self.peek() {
+                    let open = self.current_span();
+                    self.advance();
+                    let args = self.parse_delimited(open, TokenKind::RParen, |span, open| {
+                        ParseError::ExpectedArgumentDelimiter { span, open }
+                    })?;
+                    let span = Span::new(span.start, self.previous_end());
+                    if name == "if" {
+                        return self.parse_if(args, span);
+                    }
+                    return Ok(Syntax::new(SyntaxKind::Call { name, args }, span));
+                }
+
+                Ok(Syntax::new(SyntaxKind::Variable(name), span))
+            }
+            TokenKind::Interpolation(code) => {
+                self.advance();
+                Ok(Syntax::new(SyntaxKind::Interpolation(code), span))
+            }
+            TokenKind::LBracket => {
+                self.advance();
+                let items = self.parse_delimited(span, TokenKind::RBracket, |span, open| {
+                    ParseError::ExpectedListDelimiter { span, open }
+                })?;
+                let span = Span::new(span.start, self.previous_end());
+                Ok(Syntax::new(SyntaxKind::List(items), span))
+            }
+            TokenKind::LParen => {
+                self.advance();
+                let expr = self.parse_expression()?;
+                if self.peek() != Some(&TokenKind::RParen) {
+                    self.report(ParseError::UnclosedParenthesis {
+                        span: self.current_span(),
+                        open: span,
+                    })?;
+                    self.synchronize(&[TokenKind::RParen]);
+                }
+                self.advance();
+
+                // The group covers its parentheses so diagnostics underline them too
+                let span = Span::new(span.start, self.previous_end());
+                Ok(Syntax::new(expr.kind, span))
+            }
+            // When recovering, this was already reported by the tokenizer
+            TokenKind::Invalid(_) if self.recovering() => {
+                self.advance();
+                Ok(Syntax::new(SyntaxKind::Error, span))
+            }
+            // Left in place so the enclosing operator, group or argument list can resume from it
+            found => {
+                self.report(ParseError::UnexpectedToken { found, span })?;
+                Ok(Syntax::new(SyntaxKind::Error, span))
+            }
+        }
+    }
+
+    /// `if(c, a, b)` is the conditional `c ? a : b` spelled as a call
+    fn parse_if(&mut self, args: Vec<Syntax>, span: Span) -> Result<Syntax, ParseError> {
+        match <[Syntax; 3]>::try_from(args) {
+            Ok([condition, then, otherwise]) => {
+                let mut syntax = conditional(condition, then, otherwise);
+                syntax.span = span;
+                Ok(syntax)
+            }
+            Err(args) => {
+                self.report(ParseError::ConditionalArity {
+                    found: args.len(),
+                    span,
+                })?;
+                Ok(Syntax::new(SyntaxKind::Error, span))
+            }
+        }
+    }
+
+    /// Parses a comma separated argument list or list literal ending in `close`, assuming the
+    /// opening bracket at `open` was consumed. `error` reports a missing delimiter
+    fn parse_delimited(
+        &mut self,
+        open: Span,
+        close: TokenKind,
+        error: fn(Span, Span) -> ParseError,
+    ) -> Result<Vec<Syntax>, ParseError> {
+        let mut args = Vec::new();
+        if self.peek() == Some(&close) {
+            self.advance();
+            return Ok(args);
+        }
+
+        loop {
+            args.push(self.parse_expression()?);
+            if !matches!(self.peek(), Some(kind) if *kind == TokenKind::Comma || *kind == close) {
+                self.report(error(self.current_span(), open))?;
+                self.synchronize(&[TokenKind::Comma, close.clone()]);
+            }
+
+            match self.advance().map(|token| &token.kind) {
+                Some(TokenKind::Comma) => continue,
+                _ => return Ok(args),
+            }
+        }
+    }
+}
+
+fn conditional(condition: Syntax, then: Syntax, otherwise: Syntax) -> Syntax {
+    let span = Span::new(condition.span.start, otherwise.span.end);
+    let kind = SyntaxKind::Conditional {
+        condition: Box::new(condition),
+        then: Box::new(then),
+        otherwise: Box::new(otherwise),
+    };
+    Syntax::new(kind, span)
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    first_error(scan(input, false))
+}
+
+/// Tokenizes like [`tokenize`], also accepting `#{...}` splices of Rust code for the `expr!` macro
+pub fn tokenize_interpolated(input: &str) -> Result<Vec<Token>, ParseError> {
+    first_error(scan(input, true))
+}
+
+/// Tokenizes all of `input`, turning anything unrecognised into [`TokenKind::Invalid`] tokens
+pub fn tokenize_recovering(input: &str) -> (Vec<Token>, Vec<ParseError>) {
+    scan(input, false)
+}
+
+fn first_error((tokens, errors): (Vec<Token>, Vec<ParseError>)) -> Result<Vec<Token>, ParseError> {
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(tokens),
+    }
+}
+
+fn scan(input: &str, interpolation: bool) -> (Vec<Token>, Vec<ParseError>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        let kind = match c {
+            ' ' | '\t' | '\r' => {
+                chars.next();
+                continue;
+            }
+            '0'..='9' | '.' => {
+                let mut num = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_ascii_digit() || c == '.' {
+                        num.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                match num.parse() {
+                    Ok(n) => TokenKind::Number(n),
+                    Err(_) => {
+                        let span = Span::new(start, start + num.len());
+                        errors.push(ParseError::InvalidNumber {
+                            text: num.clone(),
+                            span,
+                        });
+                        TokenKind::Invalid(num)
+                    }
+                }
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let mut name = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    // A '.' joins path segments such as `order.qty`
+                    let continues = match c {
+                        '.' => {
+                            let mut ahead = chars.clone();
+                            ahead.next();
+                            matches!(ahead.peek(), Some(&(_, c)) if c.is_ascii_alphabetic() || c == '_')
+                        }
+                        _ => c.is_ascii_alphanumeric() || c == '_',
+                    };
+                    if !continues {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                TokenKind::Variable(name)
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                let mut closed = false;
+                let reported = errors.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            closed = true;
+                            break;
+                        }
+                        '\\' => match chars.next() {
+                            Some((_, '"')) => text.push('"'),
+                            Some((_, '\\')) => text.push('\\'),
+                            Some((_, 'n')) => text.push('\n'),
+                            Some((_, 't')) => text.push('\t'),
+                            Some((j, escape)) => errors.push(ParseError::InvalidEscape {
+                                character: escape,
+                                span: Span::new(i, j + escape.len_utf8()),
+                            }),
+                            None => break,
+                        },
+                        c => text.push(c),
+                    }
+                }
+                let end = chars.peek().map_or(input.len(), |&(i, _)| i);
+                if !closed {
+                    errors.push(ParseError::UnterminatedString {
+                        span: Span::new(start, end),
+                    });
+                }
+                if errors.len() == reported {
+                    TokenKind::String(text)
+                } else {
+                    TokenKind::Invalid(input[start..end].to_string())
+                }
+            }
+            '#' if interpolation && input[start + 1..].starts_with('{') => {
+                chars.next();
+                chars.next();
+                // Braces nest so blocks and struct literals can be spliced whole
+                let mut depth = 0;
+                let code_start = start + 2;
+                let mut code_end = None;
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' if depth == 0 => {
+                            code_end = Some(i);
+                            break;
+                        }
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                }
+                match code_end {
+                    Some(end) => TokenKind::Interpolation(input[code_start..end].to_string()),
+                    None => {
+                        let text = input[start..].to_string();
+                        errors.push(ParseError::UnclosedInterpolation {
+                            span: Span::new(start, input.len()),
+                        });
+                        TokenKind::Invalid(text)
+                    }
+                }
+            }
+            _ => {
+                chars.next();
+                match c {
+                    '+' => TokenKind::Plus,
+                    '-' => TokenKind::Minus,
+                    '*' => TokenKind::Star,
+                    '/' => TokenKind::Slash,
+                    '^' => TokenKind::Caret,
+                    '(' => TokenKind::LParen,
+                    ')' => TokenKind::RParen,
+                    ',' => TokenKind::Comma,
+                    '[' => TokenKind::LBracket,
+                    ']' => TokenKind::RBracket,
+                    '<' if chars.next_if(|&(_, c)| c == '=').is_some() => TokenKind::LessEqual,
+                    '<' => TokenKind::Less,
+                    '>' if chars.next_if(|&(_, c)| c == '=').is_some() => TokenKind::GreaterEqual,
+                    '>' => TokenKind::Greater,
+                    '=' if chars.next_if(|&(_, c)| c == '=').is_some() => TokenKind::EqualEqual,
+                    '!' if chars.next_if(|&(_, c)| c == '=').is_some() => TokenKind::BangEqual,
+                    '!' => TokenKind::Bang,
+                    '&' if chars.next_if(|&(_, c)| c == '&').is_some() => TokenKind::AndAnd,
+                    '|' if chars.next_if(|&(_, c)| c == '|').is_some() => TokenKind::OrOr,
+                    '?' => TokenKind::Question,
+                    ':' => TokenKind::Colon,
+                    '=' => TokenKind::Equal,
+                    ';' => TokenKind::Semicolon,
+                    '\n' => TokenKind::Newline,
+                    _ => {
+                        errors.push(ParseError::UnexpectedCharacter {
+                            character: c,
+                            span: Span::new(start, start + c.len_utf8()),
+                        });
+                        TokenKind::Invalid(c.to_string())
+                    }
+                }
+            }
+        };
+
+        let end = chars.peek().map_or(input.len(), |&(i, _)| i);
+        tokens.push(Token {
+            kind,
+            span: Span::new(start, end),
+        });
+    }
+
+    (tokens, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str) -> Result<Vec<TokenKind>, ParseError> {
+        tokenize(input).map(|tokens| tokens.into_iter().map(|token| token.kind).collect())
+    }
+
+    #[test]
+    fn test_tokenize_numbers() {
+        assert_eq!(kinds("123.45"), Ok(vec![TokenKind::Number(123.45)]));
+
+        assert_eq!(kinds("42"), Ok(vec![TokenKind::Number(42.0)]));
+    }
+
+    #[test]
+    fn test_tokenize_variables() {
+        assert_eq!(
+            kinds("xyz"),
+            Ok(vec![TokenKind::Variable("xyz".to_string())])
+        );
+
+        assert_eq!(kinds("x"), Ok(vec![TokenKind::Variable("x".to_string())]));
+
+        assert_eq!(
+            kinds("log10 _tmp"),
+            Ok(vec![
+                TokenKind::Variable("log10".to_string()),
+                TokenKind::Variable("_tmp".to_string())
+            ])
+        );
+
+        assert_eq!(
+            kinds("order.qty.max x.1"),
+            Ok(vec![
+                TokenKind::Variable("order.qty.max".to_string()),
+                TokenKind::Variable("x".to_string()),
+                TokenKind::Number(0.1)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_function_call() {
+        assert_eq!(
+            kinds("max(x, 2)"),
+            Ok(vec![
+                TokenKind::Variable("max".to_string()),
+                TokenKind::LParen,
+                TokenKind::Variable("x".to_string()),
+                TokenKind::Comma,
+                TokenKind::Number(2.0),
+                TokenKind::RParen
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_operators() {
+        assert_eq!(
+            kinds("+-*/^"),
+            Ok(vec![
+                TokenKind::Plus,
+                TokenKind::Minus,
+                TokenKind::Star,
+                TokenKind::Slash,
+                TokenKind::Caret
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_parentheses() {
+        assert_eq!(
+            kinds("(x)"),
+            Ok(vec![
+                TokenKind::LParen,
+                TokenKind::Variable("x".to_string()),
+                TokenKind::RParen
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_complex_expression() {
+        assert_eq!(
+            kinds("(x + 2.5) * y"),
+            Ok(vec![
+                TokenKind::LParen,
+                TokenKind::Variable("x".to_string()),
+                TokenKind::Plus,
+                TokenKind::Number(2.5),
+                TokenKind::RParen,
+                TokenKind::Star,
+                TokenKind::Variable("y".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_invalid_characters() {
+        assert!(kinds("x @ y").is_err());
+        assert!(kinds("2 $ 3").is_err());
+        assert!(kinds("#123").is_err());
+    }
+
+    #[test]
+    fn test_tokenize_spans() {
+        let spans: Vec<_> = tokenize("sin(x1) + 2.5")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.span)
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(0, 3),
+                Span::new(3, 4),
+                Span::new(4, 6),
+                Span::new(6, 7),
+                Span::new(8, 9),
+                Span::new(10, 13)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_errors() {
+        assert_eq!(
+            tokenize("x @ y"),
+            Err(ParseError::UnexpectedCharacter {
+                character: '@',
+                span: Span::new(2, 3)
+            })
+        );
+        assert_eq!(
+            tokenize("1..2"),
+            Err(ParseError::InvalidNumber {
+                text: "1..2".to_string(),
+                span: Span::new(0, 4)
+            })
+        );
+    }
+
+    #[test]
+    fn test_tokenize_whitespace() {
+        assert_eq!(kinds("x + y"), kinds("x+y"));
+
+        assert_eq!(kinds(" x  +  y "), kinds("x+y"));
+
+        assert_eq!(kinds("x\t+\ry"), kinds("x+y"));
+
+        // Line breaks are tokens, but only separate `let` bindings
+        assert_eq!(
+            kinds("x\n+\r\ny"),
+            Ok(vec![
+                TokenKind::Variable("x".to_string()),
+                TokenKind::Newline,
+                TokenKind::Plus,
+                TokenKind::Newline,
+                TokenKind::Variable("y".to_string())
+            ])
+        );
+        assert_eq!(parsed("x\n\t+\r\ny"), "(+ x@0..1 y@6..7)@0..7");
+    }
+
+    #[test]
+    fn test_tokenize_recovering() {
+        let (tokens, errors) = tokenize_recovering("1 @ 2.3.4");
+        let kinds: Vec<_> = tokens.into_iter().map(|token| token.kind).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Number(1.0),
+                TokenKind::Invalid("@".to_string()),
+                TokenKind::Invalid("2.3.4".to_string())
+            ]
+        );
+        assert_eq!(errors.len(), 2);
+    }
+
+    // Renders the tree as an s-expression with each node's span
+    fn sexpr(syntax: &Syntax) -> String {
+        let inner = match &syntax.kind {
+            SyntaxKind::Number(n) => n.to_string(),
+            SyntaxKind::Bool(b) => b.to_string(),
+            SyntaxKind::String(text) => format!("{:?}", text),
+            SyntaxKind::Null => "null".to_string(),
+            SyntaxKind::List(items) => {
+                let items: Vec<_> = items.iter().map(sexpr).collect();
+                format!("[{}]", items.join(" "))
+            }
+            SyntaxKind::Variable(name) => name.clone(),
+            SyntaxKind::Binary { operator, lhs, rhs } => {
+                format!("({} {} {})", operator.symbol(), sexpr(lhs), sexpr(rhs))
+            }
+            SyntaxKind::Negate(operand) => format!("(neg {})", sexpr(operand)),
+            SyntaxKind::Not(operand) => format!("(! {})", sexpr(operand)),
+            SyntaxKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => format!(
+                "(? {} {} {})",
+                sexpr(condition),
+                sexpr(then),
+                sexpr(otherwise)
+            ),
+            SyntaxKind::Call { name, args } => {
+                let args: Vec<_> = args.iter().map(sexpr).collect();
+                format!("({} {})", name, args.join(" "))
+            }
+            SyntaxKind::Let { name, value, body } => {
+                format!("(let {} {} {})", name, sexpr(value), sexpr(body))
+            }
+            SyntaxKind::Assign { name, value, body } => {
+                format!("(= {} {} {})", name, sexpr(value), sexpr(body))
+            }
+            SyntaxKind::Interpolation(code) => format!("#{{{}}}", code),
+            SyntaxKind::Error => "<error>".to_string(),
+        };
+        format!("{}@{}", inner, syntax.span)
+    }
+
+    fn parsed(input: &str) -> String {
+        sexpr(&Parser::new(tokenize(input).unwrap()).parse().unwrap())
+    }
+
+    #[test]
+    fn test_syntax_spans() {
+        assert_eq!(parsed("x"), "x@0..1");
+        assert_eq!(parsed("1 + 2.5"), "(+ 1@0..1 2.5@4..7)@0..7");
+        assert_eq!(parsed("-x ^ 2"), "(neg (^ x@1..2 2@5..6)@1..6)@0..6");
+        assert_eq!(parsed("+ x"), "x@0..3");
+        assert_eq!(parsed("( x )*y"), "(* x@0..5 y@6..7)@0..7");
+        assert_eq!(parsed("max(a, b)"), "(max a@4..5 b@7..8)@0..9");
+        assert_eq!(parsed("f()"), "(f )@0..3");
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let kinds = |input| {
+            tokenize_interpolated(input).map(|tokens| {
+                tokens
+                    .into_iter()
+                    .map(|token| token.kind)
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            kinds("#{ base }*#{ {rates[0]} }"),
+            Ok(vec![
+                TokenKind::Interpolation(" base ".to_string()),
+                TokenKind::Star,
+                TokenKind::Interpolation(" {rates[0]} ".to_string()),
+            ])
+        );
+        assert_eq!(
+            kinds("1 + #{x"),
+            Err(ParseError::UnclosedInterpolation {
+                span: Span::new(4, 7)
+            })
+        );
+        assert_eq!(
+            kinds("# {x}"),
+            Err(ParseError::UnexpectedCharacter {
+                character: '#',
+                span: Span::new(0, 1)
+            })
+        );
+        assert!(tokenize("#{x}").is_err());
+
+        let syntax = Parser::new(tokenize_interpolated("2 * f(#{a.b})").unwrap()).parse();
+        assert_eq!(
+            sexpr(&syntax.unwrap()),
+            "(* 2@0..1 (f #{a.b}@6..12)@4..13)@0..13"
+        );
+    }
+
+    #[test]
+    fn test_logic_tokens() {
+        let kinds = |input| {
+            tokenize(input)
+                .unwrap()
+                .into_iter()
+                .map(|token| token.kind)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            kinds("< <= > >= == != && || ! ? :"),
+            vec![
+                TokenKind::Less,
+                TokenKind::LessEqual,
+                TokenKind::Greater,
+                TokenKind::GreaterEqual,
+                TokenKind::EqualEqual,
+                TokenKind::BangEqual,
+                TokenKind::AndAnd,
+                TokenKind::OrOr,
+                TokenKind::Bang,
+                TokenKind::Question,
+                TokenKind::Colon,
+            ]
+        );
+        assert_eq!(
+            kinds("!!x<=-1"),
+            vec![
+                TokenKind::Bang,
+                TokenKind::Bang,
+                TokenKind::Variable("x".to_string()),
+                TokenKind::LessEqual,
+                TokenKind::Minus,
+                TokenKind::Number(1.0),
+            ]
+        );
+        for (input, character) in [("a & b", '&'), ("a | b", '|')] {
+            assert!(
+                matches!(tokenize(input), Err(ParseError::UnexpectedCharacter { character: c, .. }) if c == character),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_logic_precedence() {
+        assert_eq!(
+            parsed("x > 1 && y <= 2 || !z"),
+            "(|| (&& (> x@0..1 1@4..5)@0..5 (<= y@9..10 2@14..15)@9..15)@0..15 (! z@20..21)@19..21)@0..21"
+        );
+        assert_eq!(
+            parsed("a == b + 1 != c"),
+            "(!= (== a@0..1 (+ b@5..6 1@9..10)@5..10)@0..10 c@14..15)@0..15"
+        );
+        assert_eq!(
+            parsed("a ? b : c ? d : e"),
+            "(? a@0..1 b@4..5 (? c@8..9 d@12..13 e@16..17)@8..17)@0..17"
+        );
+        assert_eq!(parsed("!a ^ 2"), "(! (^ a@1..2 2@5..6)@1..6)@0..6");
+        assert_eq!(
+            parsed("if(a < 1, 2, f(3))"),
+            "(? (< a@3..4 1@7..8)@3..8 2@10..11 (f 3@15..16)@13..17)@0..18"
+        );
+    }
+
+    #[test]
+    fn test_conditional_errors() {
+        assert_eq!(
+            crate::parse("a ? b"),
+            Err(ParseError::ExpectedColon {
+                span: Span::new(5, 5),
+                question: Span::new(2, 3),
+            })
+        );
+        assert_eq!(
+            crate::parse("1 + if(a, b)"),
+            Err(ParseError::ConditionalArity {
+                found: 2,
+                span: Span::new(4, 12),
+            })
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(
+            kinds(r#"["a\"b\\", null] == true"#),
+            Ok(vec![
+                TokenKind::LBracket,
+                TokenKind::String("a\"b\\".to_string()),
+                TokenKind::Comma,
+                TokenKind::Variable("null".to_string()),
+                TokenKind::RBracket,
+                TokenKind::EqualEqual,
+                TokenKind::Variable("true".to_string()),
+            ])
+        );
+        assert_eq!(
+            parsed(r#"tier == "gold\n" || [1, [x], false, null] == []"#),
+            r#"(|| (== tier@0..4 "gold\n"@8..16)@0..16 (== [1@21..22 [x@25..26]@24..27 false@29..34 null@36..40]@20..41 []@45..47)@20..47)@0..47"#
+        );
+        assert_eq!(
+            TokenKind::String("say \"hi\"\t\\".to_string()).to_string(),
+            r#""say \"hi\"\t\\""#
+        );
+    }
+
+    #[test]
+    fn test_let_bindings() {
+        assert_eq!(
+            kinds("let r = 1;"),
+            Ok(vec![
+                TokenKind::Variable("let".to_string()),
+                TokenKind::Variable("r".to_string()),
+                TokenKind::Equal,
+                TokenKind::Number(1.0),
+                TokenKind::Semicolon,
+            ])
+        );
+        assert_eq!(
+            parsed("let r = x ^ 2; sqrt(r) / r"),
+            "(let r (^ x@8..9 2@12..13)@8..13 (/ (sqrt r@20..21)@15..22 r@25..26)@15..26)@0..26"
+        );
+        // A line break ends a binding unless the value continues on the next line
+        assert_eq!(
+            parsed("let a = x\n  + 1\nlet b = a * 2\nb"),
+            "(let a (+ x@8..9 1@14..15)@8..15 (let b (* a@24..25 2@28..29)@24..29 b@30..31)@16..31)@0..31"
+        );
+        assert_eq!(
+            parsed("(let a = 1; a) + let"),
+            "(+ (let a 1@9..10 a@12..13)@0..14 let@17..20)@0..20"
+        );
+        assert_eq!(
+            parsed("c ? let a = 1; a : let b = 2; b"),
+            "(? c@0..1 (let a 1@12..13 a@15..16)@4..16 (let b 2@27..28 b@30..31)@19..31)@0..31"
+        );
+    }
+
+    #[test]
+    fn test_assignments() {
+        assert_eq!(
+            parsed("v = v + a; x = x + v"),
+            "(= v (+ v@4..5 a@8..9)@4..9 (= x (+ x@15..16 v@19..20)@15..20 x@11..12)@11..20)@0..20"
+        );
+        assert_eq!(
+            parsed("let dt = 2\nx = x * dt;\nx > 1"),
+            "(let dt 2@9..10 (= x (* x@15..16 dt@19..21)@15..21 (> x@23..24 1@27..28)@23..28)@11..28)@0..28"
+        );
+        assert_eq!(parsed("n = 1;"), "(= n 1@4..5 n@0..1)@0..5");
+        assert_eq!(parsed("let = 1"), "(= let 1@6..7 let@0..3)@0..7");
+    }
+
+    #[test]
+    fn test_assignment_errors() {
+        // Assignments are statements of the whole script only
+        for (source, at) in [
+            ("(x = 1)", 3),
+            ("c ? x = 1 : 2", 6),
+            ("let a = b = 1; a", 10),
+        ] {
+            assert_eq!(
+                crate::parse(source).map_err(|e| e.span().start),
+                Err(at),
+                "{}",
+                source
+            );
+        }
+        assert_eq!(
+            crate::parse("x = 1 y = 2"),
+            Err(ParseError::ExpectedSeparator {
+                span: Span::new(6, 7)
+            })
+        );
+        assert_eq!(
+            crate::parse("x =; y"),
+            Err(ParseError::UnexpectedToken {
+                found: TokenKind::Semicolon,
+                span: Span::new(3, 4)
+            })
+        );
+        assert_eq!(
+            crate::parse("1 = 2"),
+            Err(ParseError::TrailingInput {
+                found: TokenKind::Equal,
+                span: Span::new(2, 3)
+            })
+        );
+    }
+
+    #[test]
+    fn test_let_errors() {
+        assert_eq!(
+            crate::parse("let a 1; a"),
+            Err(ParseError::ExpectedEquals {
+                span: Span::new(6, 7)
+            })
+        );
+        assert_eq!(
+            crate::parse("let a = 1 a"),
+            Err(ParseError::ExpectedSeparator {
+                span: Span::new(10, 11)
+            })
+        );
+        assert_eq!(
+            crate::parse("let a = 1;"),
+            Err(ParseError::UnexpectedEnd {
+                span: Span::new(10, 10)
+            })
+        );
+
+        let (syntax, errors) = crate::parse_recovering("let a 1 a");
+        assert_eq!(sexpr(&syntax), "(let a 1@6..7 a@8..9)@0..9");
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_literal_errors() {
+        assert_eq!(
+            tokenize(r#"x + "abc"#),
+            Err(ParseError::UnterminatedString {
+                span: Span::new(4, 8)
+            })
+        );
+        assert_eq!(
+            tokenize(r#""a\qb""#),
+            Err(ParseError::InvalidEscape {
+                character: 'q',
+                span: Span::new(2, 4)
+            })
+        );
+        assert_eq!(
+            crate::parse("[1, 2"),
+            Err(ParseError::ExpectedListDelimiter {
+                span: Span::new(5, 5),
+                open: Span::new(0, 1)
+            })
+        );
+
+        let (tokens, errors) = tokenize_recovering(r#"1 + "\q" + 2"#);
+        assert_eq!(tokens[2].kind, TokenKind::Invalid(r#""\q""#.to_string()));
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_syntax_error_spans() {
+        let (syntax, errors) = Parser::new(tokenize("1 + * 2").unwrap()).parse_recovering();
+
+        assert_eq!(
+            sexpr(&syntax),
+            "(+ 1@0..1 (* <error>@4..5 2@6..7)@4..7)@0..7"
+        );
+        assert_eq!(errors.len(), 1);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::parsing::{BinaryOperator, Span};
+
+/// A node of the tree built by [`Parser`](crate::parsing::Parser), with the span of
+/// input it was parsed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Syntax {
+    pub kind: SyntaxKind,
+    pub span: Span,
+}
+
+impl Syntax {
+    pub fn new(kind: SyntaxKind, span: Span) -> Self {
+        Syntax { kind, span }
+    }
+
+    /// The direct subtrees, in source order
+    pub fn children(&self) -> Vec<&Syntax> {
+        match &self.kind {
+            SyntaxKind::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+            SyntaxKind::Negate(operand) | SyntaxKind::Not(operand) => vec![operand],
+            SyntaxKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => vec![condition, then, otherwise],
+            SyntaxKind::List(items) | SyntaxKind::Call { args: items, .. } => {
+                items.iter().collect()
+            }
+            SyntaxKind::Let { value, body, .. } | SyntaxKind::Assign { value, body, .. } => {
+                vec![value, body]
+            }
+            SyntaxKind::Number(_)
+            | SyntaxKind::Bool(_)
+            | SyntaxKind::String(_)
+            | SyntaxKind::Null
+            | SyntaxKind::Variable(_)
+            | SyntaxKind::Interpolation(_)
+            | SyntaxKind::Error => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxKind {
+    Number(f64),
+    /// `true` or `false`
+    Bool(bool),
+    String(String),
+    Null,
+    /// `[a, b, ...]`
+    List(Vec<Syntax>),
+    Variable(String),
+    Binary {
+        operator: BinaryOperator,
+        lhs: Box<Syntax>,
+        rhs: Box<Syntax>,
+    },
+    Negate(Box<Syntax>),
+    Not(Box<Syntax>),
+    /// `condition ? then : otherwise`, also written `if(condition, then, otherwise)`
+    Conditional {
+        condition: Box<Syntax>,
+        then: Box<Syntax>,
+        otherwise: Box<Syntax>,
+    },
+    Call {
+        name: String,
+        args: Vec<Syntax>,
+    },
+    /// `let name = value; body`, where `name` refers to `value` within `body`
+    Let {
+        name: String,
+        value: Box<Syntax>,
+        body: Box<Syntax>,
+    },
+    /// `name = value; body`, a statement setting the variable `name` for the rest of a script.
+    ///
+    /// The body of an assignment ending the script is the variable itself, spanning its name.
+    Assign {
+        name: String,
+        value: Box<Syntax>,
+        body: Box<Syntax>,
+    },
+    /// Rust code spliced in with `#{...}`, see [`parse_interpolated`](crate::parse_interpolated)
+    Interpolation(String),
+    /// Placeholder for input that failed to parse, see [`parse_recovering`](crate::parse_recovering)
+    Error,
+}
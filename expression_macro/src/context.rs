@@ -0,0 +1,180 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{ToTokens, quote};
+use syn::{Data, DeriveInput, Fields, LitStr, Type};
+
+/// How a field's value is given to expressions
+enum FieldKind {
+    Float,
+    /// An integer that always fits in an `i64`
+    Integer,
+    /// An integer that may not fit in an `i64`, which is widened to a number instead
+    WideInteger,
+    Bool,
+}
+
+/// Field options from `#[expr(...)]`
+#[derive(Default)]
+struct FieldOptions {
+    rename: Option<String>,
+    nested: bool,
+    skip: bool,
+}
+
+impl FieldOptions {
+    fn parse(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut options = FieldOptions::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("expr")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    let name: LitStr = meta.value()?.parse()?;
+                    options.rename = Some(name.value());
+                } else if meta.path.is_ident("nested") {
+                    options.nested = true;
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else {
+                    return Err(meta.error("expected `rename = \"...\"`, `nested` or `skip`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(options)
+    }
+}
+
+fn field_kind(ty: &Type) -> Option<FieldKind> {
+    let ident = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.get_ident()?,
+        _ => return None,
+    };
+    match ident.to_string().as_str() {
+        "f64" | "f32" => Some(FieldKind::Float),
+        "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" => Some(FieldKind::Integer),
+        "isize" | "u64" | "usize" => Some(FieldKind::WideInteger),
+        "bool" => Some(FieldKind::Bool),
+        _ => None,
+    }
+}
+
+/// The path to `expression_parser`, overridden with `#[expr(crate = "path")]` on the struct
+fn crate_path(attrs: &[syn::Attribute]) -> syn::Result<TokenStream2> {
+    let mut krate = quote! { ::expression_parser };
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("expr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                let path: LitStr = meta.value()?.parse()?;
+                krate = path.parse::<syn::Path>()?.into_token_stream();
+                Ok(())
+            } else {
+                Err(meta.error("expected `crate = \"...\"`"))
+            }
+        })?;
+    }
+
+    Ok(krate)
+}
+
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let krate = crate_path(&input.attrs)?;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "ExpressionContext requires a struct with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "ExpressionContext can only be derived for structs",
+            ));
+        }
+    };
+
+    let mut numbers = Vec::new();
+    let mut values = Vec::new();
+    let mut nested = Vec::new();
+    let mut nested_values = Vec::new();
+    for field in fields {
+        let options = FieldOptions::parse(&field.attrs)?;
+        if options.skip {
+            continue;
+        }
+
+        let ident = field.ident.as_ref().unwrap();
+        let name = options.rename.unwrap_or_else(|| ident.to_string());
+        if options.nested {
+            let prefix = format!("{}.", name);
+            nested.push(quote! {
+                if let ::core::option::Option::Some(rest) = name.strip_prefix(#prefix) {
+                    return #krate::context::Context::get(&self.#ident, rest);
+                }
+            });
+            nested_values.push(quote! {
+                if let ::core::option::Option::Some(rest) = name.strip_prefix(#prefix) {
+                    return #krate::context::Context::value(&self.#ident, rest);
+                }
+            });
+            continue;
+        }
+
+        let Some(kind) = field_kind(&field.ty) else {
+            let message = "only numeric and `bool` fields can be used as variables; \
+                 mark this field `#[expr(skip)]`, or `#[expr(nested)]` if it is a `Context`";
+            let attr = field.attrs.iter().find(|attr| attr.path().is_ident("expr"));
+            return Err(match attr {
+                Some(attr) => syn::Error::new_spanned(attr, message),
+                None => syn::Error::new_spanned(&field.ty, message),
+            });
+        };
+
+        let value = quote! { #krate::value::Value };
+        let typed = match kind {
+            FieldKind::Float => quote! { #value::Number(self.#ident as ::core::primitive::f64) },
+            FieldKind::Integer => quote! { #value::Integer(self.#ident as ::core::primitive::i64) },
+            FieldKind::WideInteger => quote! {
+                <::core::primitive::i64 as ::core::convert::TryFrom<_>>::try_from(self.#ident)
+                    .map_or(
+                        #value::Number(self.#ident as ::core::primitive::f64),
+                        #value::Integer,
+                    )
+            },
+            FieldKind::Bool => quote! { #value::Bool(self.#ident) },
+        };
+        // As for contexts holding values, `get` only gives the numbers
+        if !matches!(kind, FieldKind::Bool) {
+            numbers.push(quote! {
+                #name => ::core::option::Option::Some(self.#ident as ::core::primitive::f64),
+            });
+        }
+        values.push(quote! {
+            #name => ::core::option::Option::Some(#typed),
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::context::Context for #ident #ty_generics #where_clause {
+            fn get(&self, name: &str) -> ::core::option::Option<::core::primitive::f64> {
+                #(#nested)*
+                match name {
+                    #(#numbers)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+
+            fn value(&self, name: &str) -> ::core::option::Option<#krate::value::Value> {
+                #(#nested_values)*
+                match name {
+                    #(#values)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+        }
+    })
+}
@@ -1,237 +1,238 @@
+use expression_grammar::parsing::BinaryOperator;
+use expression_grammar::syntax::{Syntax, SyntaxKind};
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{ToTokens, quote};
-use syn::{LitStr, parse_macro_input};
+use syn::parse::{Parse, ParseStream};
+use syn::{DeriveInput, LitStr, Token, parse_macro_input};
+
+mod context;
+mod native;
+mod vars;
+
+/// Arguments of `expr!`: the source literal, then optionally `vars = [...]` declaring the
+/// variables it may use and `crate = path` to reach `expression_parser` through a
+/// re-export, like `$crate` in a `macro_rules!` macro
+struct ExprInput {
+    source: LitStr,
+    krate: TokenStream2,
+    vars: Option<Vec<vars::DeclaredVar>>,
+}
+
+impl Parse for ExprInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let source = input.parse()?;
+        let mut krate = quote! { ::expression_parser };
+        let mut vars = None;
+
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            if input.parse::<Option<Token![crate]>>()?.is_some() {
+                input.parse::<Token![=]>()?;
+                krate = input.parse::<syn::Path>()?.into_token_stream();
+            } else if input.peek(syn::Ident) && input.fork().parse::<syn::Ident>()? == "vars" {
+                input.parse::<syn::Ident>()?;
+                input.parse::<Token![=]>()?;
+                vars = Some(vars::parse_declared(input)?);
+            } else {
+                return Err(input.error("expected `crate = path` or `vars = [...]`"));
+            }
+        }
+        if !input.is_empty() {
+            return Err(input.error("expected `,`"));
+        }
 
+        Ok(ExprInput {
+            source,
+            krate,
+            vars,
+        })
+    }
+}
+
+/// Builds an `Expression` from a literal formula, checking its syntax at compile time.
+///
+/// `#{...}` splices in the value of a Rust expression: an `f64` becomes a number and an
+/// `Expression` (or `&Expression`, which is cloned) becomes a subtree, as in
+/// `expr!("#{base} * (1 + rate)")`.
+///
+/// With `vars = [a, b]` a variable outside the list is a compile error and a listed
+/// variable the formula never uses is a warning.
 #[proc_macro]
 pub fn expr(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as LitStr);
-    let expr_str = input.value();
+    let input = parse_macro_input!(input as ExprInput);
 
-    let tokens = match tokenize(&expr_str) {
-        Ok(t) => t,
+    let syntax = match expression_grammar::parse_interpolated(&input.source.value()) {
+        Ok(syntax) => syntax,
         Err(e) => {
-            return syn::Error::new_spanned(&input, e).to_compile_error().into(); // This makes the error compile time
+            return syn::Error::new_spanned(&input.source, e)
+                .to_compile_error()
+                .into(); // This makes the error compile time
         }
     };
 
-    let mut parser = Parser::new(tokens);
-    let expr = match parser.parse_expression() {
-        Ok(expr) => expr,
-        Err(e) => {
-            return syn::Error::new_spanned(&input, e).to_compile_error().into();
-        }
+    let expanded = match &input.vars {
+        Some(declared) => vars::check(&syntax, &input.source, declared).and_then(|warnings| {
+            let expression = expand(&syntax, &input)?;
+            Ok(quote! {{ #warnings #expression }})
+        }),
+        None => expand(&syntax, &input),
     };
-
-    expr.to_token_stream().into()
-}
-
-#[derive(Debug, Clone, PartialEq)]
-enum Token {
-    Number(f64),
-    Variable(String),
-    Plus,
-    Minus,
-    Star,
-    Slash,
-    Caret,
-    LParen,
-    RParen,
+    expanded
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
 
-enum Expr {
-    Number(f64),
-    Variable(String),
-    Add(Box<Expr>, Box<Expr>),
-    Subtract(Box<Expr>, Box<Expr>),
-    Multiply(Box<Expr>, Box<Expr>),
-    Divide(Box<Expr>, Box<Expr>),
-    Power(Box<Expr>, Box<Expr>),
-}
-
-/// This generates the AST
-impl ToTokens for Expr {
-    fn to_tokens(&self, tokens: &mut TokenStream2) {
-        match self {
-            Expr::Number(n) => {
-                tokens.extend(quote! { Expression::Number(#n) });
-            }
-            Expr::Variable(name) => {
-                tokens.extend(quote! { Expression::Variable(#name.to_string()) });
-            }
-            Expr::Add(a, b) => {
-                tokens.extend(quote! { Expression::Add(Box::new(#a), Box::new(#b)) });
-            }
-            Expr::Subtract(a, b) => {
-                tokens.extend(quote! { Expression::Subtract(Box::new(#a), Box::new(#b)) });
-            }
-            Expr::Multiply(a, b) => {
-                tokens.extend(quote! { Expression::Multiply(Box::new(#a), Box::new(#b)) });
-            }
-            Expr::Divide(a, b) => {
-                tokens.extend(quote! { Expression::Divide(Box::new(#a), Box::new(#b)) });
-            }
-            Expr::Power(a, b) => {
-                tokens.extend(quote! { Expression::Power(Box::new(#a), Box::new(#b)) });
-            }
-        }
-    }
+/// Compiles a formula to a native closure taking the listed variables as `f64` parameters.
+///
+/// `expr_fn!("x^2 + y", x, y)` expands to `|x: f64, y: f64| -> f64 { f64::powf(x, 2.0) + y }`.
+/// Variables that are not listed and unknown functions are compile errors, and subtrees
+/// without variables are folded to constants. Division follows IEEE 754 rather than
+/// reporting `DivisionByZero`, so `1 / x` at `x = 0` returns infinity.
+#[proc_macro]
+pub fn expr_fn(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as native::NativeInput);
+    native::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
 
-struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+/// Implements `Context` for a struct, exposing each numeric and `bool` field as a variable.
+///
+/// Typed lookups give integer fields as `Value::Integer`, widening to `Value::Number` any
+/// `u64` or `usize` beyond `i64`, and `bool` fields as `Value::Bool`, which `get` leaves out.
+///
+/// Fields accept `#[expr(rename = "...")]`, `#[expr(skip)]` and `#[expr(nested)]`, the
+/// last making the variables of a field that is itself a `Context` available as `field.name`.
+/// Any other field must be skipped or nested, so a field of another type is a compile error.
+/// `#[expr(crate = "path")]` on the struct names `expression_parser` through a re-export.
+#[proc_macro_derive(ExpressionContext, attributes(expr))]
+pub fn derive_expression_context(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    context::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
 
-impl Parser {
-    fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
-    }
-
-    fn peek(&self) -> Option<&Token> {
-        self.tokens.get(self.current)
-    }
-
-    fn advance(&mut self) -> Option<&Token> {
-        let token = self.tokens.get(self.current);
-        self.current += 1;
-        token
-    }
-
-    fn parse_expression(&mut self) -> Result<Expr, String> {
-        self.parse_addition()
-    }
-
-    fn parse_addition(&mut self) -> Result<Expr, String> {
-        let mut expr = self.parse_multiplication()?;
+/// Builds the `Expression` constructor calls, with `input.krate` as the path to `expression_parser`
+fn expand(syntax: &Syntax, input: &ExprInput) -> syn::Result<TokenStream2> {
+    let krate = &input.krate;
+    let expression = quote! { #krate::expression::Expression };
 
-        while let Some(token) = self.peek() {
-            match token {
-                Token::Plus => {
-                    self.advance();
-                    expr = Expr::Add(Box::new(expr), Box::new(self.parse_multiplication()?));
-                }
-                Token::Minus => {
-                    self.advance();
-                    expr = Expr::Subtract(Box::new(expr), Box::new(self.parse_multiplication()?));
-                }
-                _ => break,
-            }
+    Ok(match &syntax.kind {
+        SyntaxKind::Number(n) => quote! { #expression::Number(#n) },
+        SyntaxKind::Bool(b) => quote! { #expression::Bool(#b) },
+        SyntaxKind::String(text) => {
+            quote! { #expression::String(::std::string::String::from(#text)) }
         }
-        Ok(expr)
-    }
-
-    fn parse_multiplication(&mut self) -> Result<Expr, String> {
-        let mut expr = self.parse_power()?;
-
-        while let Some(token) = self.peek() {
-            match token {
-                Token::Star => {
-                    self.advance();
-                    expr = Expr::Multiply(Box::new(expr), Box::new(self.parse_power()?));
-                }
-                Token::Slash => {
-                    self.advance();
-                    expr = Expr::Divide(Box::new(expr), Box::new(self.parse_power()?));
-                }
-                _ => break,
+        SyntaxKind::Null => quote! { #expression::Null },
+        SyntaxKind::List(items) => {
+            let items = items
+                .iter()
+                .map(|item| expand(item, input))
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote! { #expression::List(::std::vec![#(#items),*]) }
+        }
+        SyntaxKind::Variable(name) => {
+            quote! { #expression::Variable(::std::string::String::from(#name)) }
+        }
+        SyntaxKind::Binary { operator, lhs, rhs } => {
+            let variant = match operator {
+                BinaryOperator::Add => quote! { Add },
+                BinaryOperator::Subtract => quote! { Subtract },
+                BinaryOperator::Multiply => quote! { Multiply },
+                BinaryOperator::Divide => quote! { Divide },
+                BinaryOperator::Power => quote! { Power },
+                BinaryOperator::Less => quote! { Less },
+                BinaryOperator::LessEqual => quote! { LessEqual },
+                BinaryOperator::Greater => quote! { Greater },
+                BinaryOperator::GreaterEqual => quote! { GreaterEqual },
+                BinaryOperator::Equal => quote! { Equal },
+                BinaryOperator::NotEqual => quote! { NotEqual },
+                BinaryOperator::And => quote! { And },
+                BinaryOperator::Or => quote! { Or },
+            };
+            let (lhs, rhs) = (expand(lhs, input)?, expand(rhs, input)?);
+            quote! {
+                #expression::#variant(::std::boxed::Box::new(#lhs), ::std::boxed::Box::new(#rhs))
             }
         }
-        Ok(expr)
-    }
-
-    fn parse_power(&mut self) -> Result<Expr, String> {
-        let mut expr = self.parse_primary()?;
-
-        while let Some(Token::Caret) = self.peek() {
-            self.advance();
-            expr = Expr::Power(Box::new(expr), Box::new(self.parse_primary()?));
+        SyntaxKind::Negate(operand) => {
+            let operand = expand(operand, input)?;
+            quote! { #expression::Negate(::std::boxed::Box::new(#operand)) }
         }
-        Ok(expr)
-    }
-
-    fn parse_primary(&mut self) -> Result<Expr, String> {
-        let token = self.advance().ok_or("Unexpected end of input")?;
-        match token {
-            Token::Number(n) => Ok(Expr::Number(*n)),
-            Token::Variable(name) => Ok(Expr::Variable(name.clone())),
-            Token::LParen => {
-                let expr = self.parse_expression()?;
-                match self.advance() {
-                    Some(Token::RParen) => Ok(expr),
-                    _ => Err("Expected closing parenthesis".to_string()),
+        SyntaxKind::Not(operand) => {
+            let operand = expand(operand, input)?;
+            quote! { #expression::Not(::std::boxed::Box::new(#operand)) }
+        }
+        SyntaxKind::Conditional {
+            condition,
+            then,
+            otherwise,
+        } => {
+            let condition = expand(condition, input)?;
+            let (then, otherwise) = (expand(then, input)?, expand(otherwise, input)?);
+            quote! {
+                #expression::Conditional {
+                    condition: ::std::boxed::Box::new(#condition),
+                    then: ::std::boxed::Box::new(#then),
+                    otherwise: ::std::boxed::Box::new(#otherwise),
                 }
             }
-            _ => Err("Unexpected token".to_string()),
         }
-    }
-}
-
-fn tokenize(input: &str) -> Result<Vec<Token>, String> {
-    let mut tokens = Vec::new();
-    let mut chars = input.chars().peekable();
-
-    while let Some(&c) = chars.peek() {
-        match c {
-            ' ' | '\t' | '\r' | '\n' => {
-                chars.next();
-            }
-            '0'..='9' | '.' => {
-                let mut num = String::new();
-                while let Some(&c) = chars.peek() {
-                    if c.is_ascii_digit() || c == '.' {
-                        num.push(c);
-                        chars.next();
-                    } else {
-                        break;
-                    }
+        SyntaxKind::Call { name, args } => {
+            let args = args
+                .iter()
+                .map(|arg| expand(arg, input))
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote! {
+                #expression::Call {
+                    name: ::std::string::String::from(#name),
+                    args: ::std::vec![#(#args),*],
                 }
-                tokens.push(Token::Number(
-                    num.parse().map_err(|_| "Invalid number format")?,
-                ));
             }
-            'a'..='z' | 'A'..='Z' => {
-                let mut name = String::new();
-                while let Some(&c) = chars.peek() {
-                    if c.is_ascii_alphabetic() {
-                        name.push(c);
-                        chars.next();
-                    } else {
-                        break;
-                    }
+        }
+        SyntaxKind::Let { name, value, body } | SyntaxKind::Assign { name, value, body } => {
+            let variant = match &syntax.kind {
+                SyntaxKind::Let { .. } => quote!(Let),
+                _ => quote!(Assign),
+            };
+            let (value, body) = (expand(value, input)?, expand(body, input)?);
+            quote! {
+                #expression::#variant {
+                    name: ::std::string::String::from(#name),
+                    value: ::std::boxed::Box::new(#value),
+                    body: ::std::boxed::Box::new(#body),
                 }
-                tokens.push(Token::Variable(name));
-            }
-            '+' => {
-                chars.next();
-                tokens.push(Token::Plus);
-            }
-            '-' => {
-                chars.next();
-                tokens.push(Token::Minus);
-            }
-            '*' => {
-                chars.next();
-                tokens.push(Token::Star);
             }
-            '/' => {
-                chars.next();
-                tokens.push(Token::Slash);
-            }
-            '^' => {
-                chars.next();
-                tokens.push(Token::Caret);
-            }
-            '(' => {
-                chars.next();
-                tokens.push(Token::LParen);
-            }
-            ')' => {
-                chars.next();
-                tokens.push(Token::RParen);
-            }
-            _ => return Err(format!("Unexpected character: {}", c)),
         }
-    }
-    Ok(tokens)
+        SyntaxKind::Interpolation(code) => {
+            let value = syn::parse_str::<syn::Expr>(code).map_err(|e| {
+                syn::Error::new_spanned(
+                    &input.source,
+                    format!("Invalid interpolation '#{{{}}}': {}", code, e),
+                )
+            })?;
+            // Resolve names in the spliced code at the call site, where the formula was written
+            let value = respan(value.into_token_stream(), input.source.span());
+            quote! { <#expression as ::core::convert::From<_>>::from(#value) }
+        }
+        SyntaxKind::Error => unreachable!("strict parsing never produces error nodes"),
+    })
+}
+
+fn respan(tokens: TokenStream2, span: proc_macro2::Span) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            if let proc_macro2::TokenTree::Group(group) = &token {
+                let mut respanned =
+                    proc_macro2::Group::new(group.delimiter(), respan(group.stream(), span));
+                respanned.set_span(span);
+                token = respanned.into();
+            } else {
+                token.set_span(span);
+            }
+            token
+        })
+        .collect()
 }
@@ -0,0 +1,292 @@
+use expression_grammar::builtins::builtin;
+use expression_grammar::parsing::BinaryOperator;
+use expression_grammar::syntax::{Syntax, SyntaxKind};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Ident, LitStr, Token};
+
+/// Arguments of `expr_fn!`: the formula followed by the closure's parameters in order
+pub struct NativeInput {
+    source: LitStr,
+    params: Vec<Ident>,
+}
+
+impl Parse for NativeInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let source = input.parse()?;
+        let mut params: Vec<Ident> = Vec::new();
+        if input.parse::<Option<Token![,]>>()?.is_some() {
+            for param in Punctuated::<Ident, Token![,]>::parse_terminated(input)? {
+                if params.contains(&param) {
+                    return Err(syn::Error::new_spanned(param, "parameter declared twice"));
+                }
+                params.push(param);
+            }
+        }
+
+        Ok(NativeInput { source, params })
+    }
+}
+
+/// A subtree either folded to a constant or lowered to Rust code
+#[derive(Clone)]
+enum Lowered {
+    Constant(f64),
+    Code(TokenStream2),
+}
+
+impl Lowered {
+    fn into_tokens(self) -> TokenStream2 {
+        match self {
+            Lowered::Constant(n) => constant(n),
+            Lowered::Code(code) => code,
+        }
+    }
+}
+
+fn truth(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+/// Converts a Rust `bool` expression to the `1` or `0` the evaluator produces
+fn truth_code(condition: TokenStream2) -> TokenStream2 {
+    quote! { (if #condition { 1.0 } else { 0.0 }) }
+}
+
+fn constant(n: f64) -> TokenStream2 {
+    if n.is_nan() {
+        quote! { f64::NAN }
+    } else if n.is_infinite() {
+        if n > 0.0 {
+            quote! { f64::INFINITY }
+        } else {
+            quote! { f64::NEG_INFINITY }
+        }
+    } else if n.is_sign_negative() {
+        let n = -n;
+        quote! { (-#n) }
+    } else {
+        quote! { #n }
+    }
+}
+
+pub fn expand(input: NativeInput) -> syn::Result<TokenStream2> {
+    let syntax = expression_grammar::parse(&input.source.value())
+        .map_err(|e| syn::Error::new_spanned(&input.source, e))?;
+
+    let body = Lowerer {
+        input: &input,
+        bindings: Vec::new(),
+    }
+    .lower(&syntax)?
+    .into_tokens();
+
+    let params = &input.params;
+    // Parameters the formula never reads, or only reads in branches that were folded away,
+    // would otherwise warn as unused. Folded constants such as `sqrt(2)` would trip
+    // clippy's `approx_constant` in user code
+    Ok(quote! {
+        {
+            #[allow(clippy::approx_constant)]
+            let function = |#(#params: f64),*| -> f64 {
+                let _ = (#(#params,)*);
+                #body
+            };
+            function
+        }
+    })
+}
+
+struct Lowerer<'a> {
+    input: &'a NativeInput,
+    /// `let` bindings in scope, innermost last
+    bindings: Vec<(String, Lowered)>,
+}
+
+impl Lowerer<'_> {
+    fn error(&self, message: String) -> syn::Error {
+        syn::Error::new_spanned(&self.input.source, message)
+    }
+
+    fn lower(&mut self, syntax: &Syntax) -> syn::Result<Lowered> {
+        Ok(match &syntax.kind {
+            SyntaxKind::Number(n) => Lowered::Constant(*n),
+            SyntaxKind::Bool(b) => Lowered::Constant(truth(*b)),
+            SyntaxKind::String(_) | SyntaxKind::Null | SyntaxKind::List(_) => {
+                let kind = match syntax.kind {
+                    SyntaxKind::String(_) => "string",
+                    SyntaxKind::Null => "null",
+                    _ => "list",
+                };
+                return Err(self.error(format!(
+                    "expr_fn! only computes with numbers and booleans, found a {} literal",
+                    kind
+                )));
+            }
+            SyntaxKind::Variable(name) => {
+                if let Some((_, bound)) =
+                    self.bindings.iter().rev().find(|(bound, _)| bound == name)
+                {
+                    return Ok(bound.clone());
+                }
+                let index = self
+                    .input
+                    .params
+                    .iter()
+                    .position(|param| param == name)
+                    .ok_or_else(|| {
+                        self.error(format!(
+                            "Variable '{}' is not a parameter, add it after the formula",
+                            name
+                        ))
+                    })?;
+                let param = &self.input.params[index];
+                Lowered::Code(quote! { #param })
+            }
+            SyntaxKind::Binary { operator, lhs, rhs } => {
+                match (self.lower(lhs)?, self.lower(rhs)?) {
+                    (Lowered::Constant(a), Lowered::Constant(b)) => {
+                        Lowered::Constant(match operator {
+                            BinaryOperator::Add => a + b,
+                            BinaryOperator::Subtract => a - b,
+                            BinaryOperator::Multiply => a * b,
+                            BinaryOperator::Divide => a / b,
+                            BinaryOperator::Power => a.powf(b),
+                            BinaryOperator::Less => truth(a < b),
+                            BinaryOperator::LessEqual => truth(a <= b),
+                            BinaryOperator::Greater => truth(a > b),
+                            BinaryOperator::GreaterEqual => truth(a >= b),
+                            BinaryOperator::Equal => truth(a == b),
+                            BinaryOperator::NotEqual => truth(a != b),
+                            BinaryOperator::And => truth(a != 0.0 && b != 0.0),
+                            BinaryOperator::Or => truth(a != 0.0 || b != 0.0),
+                        })
+                    }
+                    (a, b) => {
+                        let (a, b) = (a.into_tokens(), b.into_tokens());
+                        Lowered::Code(match operator {
+                            BinaryOperator::Add => quote! { (#a + #b) },
+                            BinaryOperator::Subtract => quote! { (#a - #b) },
+                            BinaryOperator::Multiply => quote! { (#a * #b) },
+                            BinaryOperator::Divide => quote! { (#a / #b) },
+                            BinaryOperator::Power => quote! { f64::powf(#a, #b) },
+                            BinaryOperator::Less => truth_code(quote! { (#a) < (#b) }),
+                            BinaryOperator::LessEqual => truth_code(quote! { (#a) <= (#b) }),
+                            BinaryOperator::Greater => truth_code(quote! { (#a) > (#b) }),
+                            BinaryOperator::GreaterEqual => truth_code(quote! { (#a) >= (#b) }),
+                            BinaryOperator::Equal => truth_code(quote! { (#a) == (#b) }),
+                            BinaryOperator::NotEqual => truth_code(quote! { (#a) != (#b) }),
+                            BinaryOperator::And => {
+                                truth_code(quote! { (#a) != 0.0 && (#b) != 0.0 })
+                            }
+                            BinaryOperator::Or => truth_code(quote! { (#a) != 0.0 || (#b) != 0.0 }),
+                        })
+                    }
+                }
+            }
+            SyntaxKind::Negate(operand) => match self.lower(operand)? {
+                Lowered::Constant(n) => Lowered::Constant(-n),
+                Lowered::Code(code) => Lowered::Code(quote! { (-#code) }),
+            },
+            SyntaxKind::Not(operand) => match self.lower(operand)? {
+                Lowered::Constant(n) => Lowered::Constant(truth(n == 0.0)),
+                Lowered::Code(code) => Lowered::Code(truth_code(quote! { (#code) == 0.0 })),
+            },
+            SyntaxKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                // Both branches are lowered even when one is dropped, so that both are checked
+                let condition = self.lower(condition)?;
+                let (then, otherwise) = (self.lower(then)?, self.lower(otherwise)?);
+                match condition {
+                    Lowered::Constant(n) if n != 0.0 => then,
+                    Lowered::Constant(_) => otherwise,
+                    Lowered::Code(condition) => {
+                        let (then, otherwise) = (then.into_tokens(), otherwise.into_tokens());
+                        Lowered::Code(quote! {
+                            (if (#condition) != 0.0 { #then } else { #otherwise })
+                        })
+                    }
+                }
+            }
+            SyntaxKind::Call { name, args } => self.lower_call(name, args)?,
+            // An assignment only rebinds the name for the rest of the script, as in `evaluate`
+            SyntaxKind::Let { name, value, body } | SyntaxKind::Assign { name, value, body } => {
+                // Constants are substituted, anything else is computed once into a local.
+                // Mixed-site hygiene keeps the local apart from the parameters, and the
+                // underscore keeps it from warning if the body never reads it
+                let value = self.lower(value)?;
+                let local = Ident::new(
+                    &format!("_binding{}", self.bindings.len()),
+                    proc_macro2::Span::mixed_site(),
+                );
+                let bound = match value {
+                    Lowered::Constant(n) => Lowered::Constant(n),
+                    Lowered::Code(_) => Lowered::Code(quote! { #local }),
+                };
+                self.bindings.push((name.clone(), bound));
+                let body = self.lower(body);
+                self.bindings.pop();
+                match (value, body?) {
+                    (Lowered::Constant(_), body) => body,
+                    (Lowered::Code(value), body) => {
+                        let body = body.into_tokens();
+                        Lowered::Code(quote! { { let #local: f64 = #value; #body } })
+                    }
+                }
+            }
+            SyntaxKind::Interpolation(_) | SyntaxKind::Error => {
+                unreachable!("strict parsing never produces interpolation or error nodes")
+            }
+        })
+    }
+
+    fn lower_call(&mut self, name: &str, args: &[Syntax]) -> syn::Result<Lowered> {
+        let function =
+            builtin(name).ok_or_else(|| self.error(format!("Unknown function '{}'", name)))?;
+        if !function.arity.accepts(args.len()) {
+            return Err(self.error(format!(
+                "Function '{}' expects {}, got {}",
+                name,
+                function.arity,
+                args.len()
+            )));
+        }
+
+        let args = args
+            .iter()
+            .map(|arg| self.lower(arg))
+            .collect::<syn::Result<Vec<_>>>()?;
+        let constants: Option<Vec<f64>> = args
+            .iter()
+            .map(|arg| match arg {
+                Lowered::Constant(n) => Some(*n),
+                Lowered::Code(_) => None,
+            })
+            .collect();
+        if let Some(constants) = constants {
+            return Ok(Lowered::Constant((function.function)(&constants)));
+        }
+
+        // Each arm must compute exactly what the built-in table in `expression_grammar` does
+        let args: Vec<_> = args.into_iter().map(Lowered::into_tokens).collect();
+        let method = Ident::new(name, proc_macro2::Span::call_site());
+        Ok(Lowered::Code(match (name, args.as_slice()) {
+            ("log", [base, x]) => quote! { f64::log(#x, #base) },
+            ("sign", [x]) => {
+                // Mixed-site hygiene keeps the temporary from shadowing a parameter named `value`
+                let value = Ident::new("value", proc_macro2::Span::mixed_site());
+                quote! {
+                    { let #value: f64 = #x; if #value == 0.0 { 0.0 } else { #value.signum() } }
+                }
+            }
+            ("min", _) => quote! { f64::INFINITY #(.min(#args))* },
+            ("max", _) => quote! { f64::NEG_INFINITY #(.max(#args))* },
+            _ => quote! { f64::#method(#(#args),*) },
+        }))
+    }
+}
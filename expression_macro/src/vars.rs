@@ -0,0 +1,132 @@
+use expression_grammar::syntax::{Syntax, SyntaxKind};
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{ToTokens, format_ident, quote};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::{Ident, LitStr, Token};
+
+/// A variable name in `vars = [...]`, dotted like `order.qty` for nested contexts
+pub struct DeclaredVar {
+    name: String,
+    tokens: TokenStream2,
+    span: Span,
+}
+
+impl Parse for DeclaredVar {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // `parse_any` so that names which are Rust keywords can still be declared
+        let first = Ident::parse_any(input)?;
+        let span = first.span();
+        let mut name = first.to_string();
+        let mut tokens = first.into_token_stream();
+        while input.peek(Token![.]) {
+            let dot: Token![.] = input.parse()?;
+            let segment = Ident::parse_any(input)?;
+            name.push('.');
+            name.push_str(&segment.to_string());
+            dot.to_tokens(&mut tokens);
+            segment.to_tokens(&mut tokens);
+        }
+
+        Ok(DeclaredVar { name, tokens, span })
+    }
+}
+
+pub fn parse_declared(input: ParseStream) -> syn::Result<Vec<DeclaredVar>> {
+    let content;
+    syn::bracketed!(content in input);
+    let mut declared: Vec<DeclaredVar> = Vec::new();
+    for var in content.parse_terminated(DeclaredVar::parse, Token![,])? {
+        if declared.iter().any(|other| other.name == var.name) {
+            return Err(syn::Error::new_spanned(
+                &var.tokens,
+                "variable declared twice",
+            ));
+        }
+        declared.push(var);
+    }
+
+    Ok(declared)
+}
+
+fn collect<'a>(syntax: &'a Syntax, names: &mut Vec<&'a str>) {
+    match &syntax.kind {
+        SyntaxKind::Variable(name) => names.push(name),
+        SyntaxKind::Binary { lhs, rhs, .. } => {
+            collect(lhs, names);
+            collect(rhs, names);
+        }
+        SyntaxKind::Negate(operand) | SyntaxKind::Not(operand) => collect(operand, names),
+        SyntaxKind::Conditional {
+            condition,
+            then,
+            otherwise,
+        } => {
+            collect(condition, names);
+            collect(then, names);
+            collect(otherwise, names);
+        }
+        SyntaxKind::Call { args: items, .. } | SyntaxKind::List(items) => {
+            items.iter().for_each(|item| collect(item, names))
+        }
+        SyntaxKind::Let { name, value, body } => {
+            collect(value, names);
+            // Uses of the binding are not variables of the formula
+            let mut inner = Vec::new();
+            collect(body, &mut inner);
+            names.extend(inner.into_iter().filter(|inner| inner != name));
+        }
+        SyntaxKind::Assign { name, value, body } => {
+            collect(value, names);
+            names.push(name);
+            collect(body, names);
+        }
+        SyntaxKind::Number(_)
+        | SyntaxKind::Bool(_)
+        | SyntaxKind::String(_)
+        | SyntaxKind::Null
+        | SyntaxKind::Interpolation(_)
+        | SyntaxKind::Error => {}
+    }
+}
+
+/// Rejects variables of `syntax` missing from `declared`, returning statements that warn
+/// about the declared variables it never uses.
+///
+/// Variables inside `#{...}` splices are not known until runtime and are not checked.
+pub fn check(
+    syntax: &Syntax,
+    source: &LitStr,
+    declared: &[DeclaredVar],
+) -> syn::Result<TokenStream2> {
+    let mut used = Vec::new();
+    collect(syntax, &mut used);
+    if let Some(name) = used
+        .iter()
+        .find(|name| !declared.iter().any(|var| var.name == **name))
+    {
+        return Err(syn::Error::new_spanned(
+            source,
+            format!("Variable '{}' is not declared in `vars`", name),
+        ));
+    }
+
+    // Stable proc macros cannot emit warnings, but using a deprecated item makes rustc
+    // report one, pointing at the unused name in `vars`
+    let warnings = declared
+        .iter()
+        .filter(|var| !used.contains(&var.name.as_str()))
+        .enumerate()
+        .map(|(i, var)| {
+            let note = format!("variable '{}' is declared but never used", var.name);
+            let item = format_ident!("unused_variable_{}", i, span = var.span);
+            quote! {
+                #[deprecated(note = #note)]
+                #[allow(non_upper_case_globals)]
+                const #item: () = ();
+                let _ = #item;
+            }
+        });
+
+    Ok(quote! { #(#warnings)* })
+}
@@ -0,0 +1,1362 @@
+//! Lowers an [`Expression`] to a flat postfix instruction sequence run by a stack machine.
+//!
+//! Conditionals and the short-circuiting `&&` and `||` jump over the code they skip, always
+//! forward and always properly nested, so a [`Program`] still reads back as a tree. The
+//! values of `let` bindings and assignments are moved from the stack to a separate stack of
+//! locals for the extent of their body.
+//!
+//! Unlike [`Expression::evaluate`], neither lowering, execution nor
+//! [`Program::to_expression`] recurses, so deeply nested input cannot overflow the native
+//! stack. Cloning, comparing, printing and dropping an [`Expression`] still recurse.
+//! Arithmetic is performed in the same order as the tree walker, so results are
+//! bit-identical. A [`Program`] can be saved with [`Program::to_bytes`] and loaded again
+//! with [`Program::from_bytes`].
+//!
+//! Like [`crate::compile`], the machine only has `f64` values: booleans are `1` and `0`,
+//! and string, null and list literals are rejected when lowering, as is `==` or `!=`
+//! between a boolean and a number.
+
+use crate::error::{DecodeError, EvalError};
+use crate::expression::{Expression, Kind, check_equality, expr, non_numeric, truth};
+use crate::functions::{FunctionRef, FunctionRegistry};
+
+const MAGIC: &[u8; 4] = b"EXPB";
+const VERSION: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    /// Pushes a constant
+    Const(f64),
+    /// Pushes the value of a variable slot
+    Load(usize),
+    /// Pushes the value of a local, counting from the outermost enclosing binding
+    Local(usize),
+    /// Pops the value of a `let` binding into a new local, named by `bindings()[index]`
+    Bind(usize),
+    /// Like [`Instruction::Bind`], for the assignment `name = value` in a script
+    Assign(usize),
+    /// Drops the innermost local at the end of the body of its binding
+    Unbind,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Negate,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    Not,
+    /// Replaces the top value with `1` if it is true and `0` otherwise
+    Truth,
+    /// Looks up `functions()[function]` and checks that it accepts `argc` arguments. Comes
+    /// before the arguments of the matching [`Instruction::Call`], so that an unknown
+    /// function is reported before any error in them, as [`Expression::evaluate`] does.
+    Resolve {
+        function: usize,
+        argc: usize,
+    },
+    /// Pops `argc` arguments and pushes the result of `functions()[function]`
+    Call {
+        function: usize,
+        argc: usize,
+    },
+    /// Pops the condition of `c ? a : b`, jumping to the `b` branch at the target if it is
+    /// false. The `a` branch ends with a [`Instruction::Jump`] past the `b` branch.
+    JumpUnless(usize),
+    /// Only valid as the end of the `a` branch of a conditional
+    Jump(usize),
+    /// Left operand of `&&`: if false, replaces it with `0` and jumps to the target,
+    /// otherwise pops it and runs the right operand, which ends with [`Instruction::Truth`]
+    And(usize),
+    /// Left operand of `||`: if true, replaces it with `1` and jumps to the target,
+    /// otherwise pops it and runs the right operand, which ends with [`Instruction::Truth`]
+    Or(usize),
+}
+
+impl Instruction {
+    fn opcode(&self) -> u8 {
+        match self {
+            Instruction::Const(_) => 0x01,
+            Instruction::Load(_) => 0x02,
+            Instruction::Local(_) => 0x03,
+            Instruction::Bind(_) => 0x04,
+            Instruction::Unbind => 0x05,
+            Instruction::Assign(_) => 0x06,
+            Instruction::Add => 0x10,
+            Instruction::Subtract => 0x11,
+            Instruction::Multiply => 0x12,
+            Instruction::Divide => 0x13,
+            Instruction::Power => 0x14,
+            Instruction::Negate => 0x15,
+            Instruction::Less => 0x16,
+            Instruction::LessEqual => 0x17,
+            Instruction::Greater => 0x18,
+            Instruction::GreaterEqual => 0x19,
+            Instruction::Equal => 0x1a,
+            Instruction::NotEqual => 0x1b,
+            Instruction::Not => 0x1c,
+            Instruction::Truth => 0x1d,
+            Instruction::Call { .. } => 0x20,
+            Instruction::Resolve { .. } => 0x21,
+            Instruction::JumpUnless(_) => 0x30,
+            Instruction::Jump(_) => 0x31,
+            Instruction::And(_) => 0x32,
+            Instruction::Or(_) => 0x33,
+        }
+    }
+
+    fn set_target(&mut self, to: usize) {
+        match self {
+            Instruction::JumpUnless(target)
+            | Instruction::Jump(target)
+            | Instruction::And(target)
+            | Instruction::Or(target) => *target = to,
+            _ => unreachable!("only jumps have targets"),
+        }
+    }
+
+    /// Net change in stack depth when falling through to the next instruction, and how
+    /// many values must already be on the stack
+    fn stack_effect(&self) -> (isize, usize) {
+        match *self {
+            Instruction::Const(_) | Instruction::Load(_) | Instruction::Local(_) => (1, 0),
+            Instruction::Bind(_) | Instruction::Assign(_) => (-1, 1),
+            Instruction::Unbind => (0, 0),
+            Instruction::Add
+            | Instruction::Subtract
+            | Instruction::Multiply
+            | Instruction::Divide
+            | Instruction::Power
+            | Instruction::Less
+            | Instruction::LessEqual
+            | Instruction::Greater
+            | Instruction::GreaterEqual
+            | Instruction::Equal
+            | Instruction::NotEqual => (-1, 2),
+            Instruction::Negate | Instruction::Not | Instruction::Truth => (0, 1),
+            Instruction::Call { argc, .. } => (1 - argc as isize, argc),
+            Instruction::JumpUnless(_) | Instruction::And(_) | Instruction::Or(_) => (-1, 1),
+            Instruction::Jump(_) | Instruction::Resolve { .. } => (0, 0),
+        }
+    }
+}
+
+/// A compiled expression with its variables bound to slot indices
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    variables: Vec<String>,
+    functions: Vec<String>,
+    bindings: Vec<String>,
+    instructions: Vec<Instruction>,
+    max_stack: usize,
+}
+
+impl Expression {
+    /// Lowers the expression to bytecode, binding `variables[i]` to slot `i`
+    pub fn to_bytecode(&self, variables: &[&str]) -> Result<Program, EvalError> {
+        enum Visit<'e> {
+            Enter(&'e Expression),
+            Emit(Instruction),
+            /// Emits a jump whose target is set by the matching `Else` or `Close`
+            Open(Instruction),
+            /// Ends the first branch of a conditional, targeting its `JumpUnless` at the second
+            Else,
+            /// Targets the innermost open jump at the next instruction
+            Close,
+            /// Moves the value of a `let` or assignment into a local, in scope until the
+            /// matching `Unbind`
+            Bind(&'e str, fn(usize) -> Instruction),
+            Unbind,
+            /// Emits the comparison of an `==` or `!=` whose operands were emitted
+            Compare(&'e Expression),
+        }
+
+        let mut functions: Vec<String> = Vec::new();
+        let mut bindings: Vec<String> = Vec::new();
+        let mut instructions: Vec<Instruction> = Vec::new();
+        // Names and kinds of the locals in scope, outermost first
+        let mut scope: Vec<(&str, Kind)> = Vec::new();
+        // Kinds of the values the emitted code leaves on the stack
+        let mut kinds: Vec<Kind> = Vec::new();
+        let mut open = Vec::new();
+        let mut pending = vec![Visit::Enter(self)];
+
+        // Children are pushed in reverse so they are emitted left to right, before their operator
+        while let Some(visit) = pending.pop() {
+            let expr = match visit {
+                Visit::Emit(instruction) => {
+                    let (effect, needed) = instruction.stack_effect();
+                    kinds.truncate(kinds.len() - needed);
+                    if effect + needed as isize == 1 {
+                        kinds.push(match instruction {
+                            Instruction::Local(index) => scope[index].1,
+                            Instruction::Less
+                            | Instruction::LessEqual
+                            | Instruction::Greater
+                            | Instruction::GreaterEqual
+                            | Instruction::Not
+                            | Instruction::Truth => Kind::Truth,
+                            _ => Kind::Number,
+                        });
+                    }
+                    instructions.push(instruction);
+                    continue;
+                }
+                Visit::Compare(expr) => {
+                    let (instruction, a, b) = match expr {
+                        Expression::Equal(a, b) => (Instruction::Equal, a, b),
+                        Expression::NotEqual(a, b) => (Instruction::NotEqual, a, b),
+                        _ => unreachable!("only equality is compared"),
+                    };
+                    let b_kind = kinds.pop().unwrap();
+                    let a_kind = kinds.pop().unwrap();
+                    check_equality((a, a_kind), (b, b_kind))?;
+                    kinds.push(Kind::Truth);
+                    instructions.push(instruction);
+                    continue;
+                }
+                Visit::Open(jump) => {
+                    // The condition or left operand is consumed, `&&` and `||` push a truth
+                    // value in its place
+                    kinds.pop();
+                    open.push(instructions.len());
+                    instructions.push(jump);
+                    continue;
+                }
+                Visit::Else => {
+                    let unless = open.pop().unwrap();
+                    open.push(instructions.len());
+                    instructions.push(Instruction::Jump(0));
+                    let target = instructions.len();
+                    instructions[unless].set_target(target);
+                    continue;
+                }
+                Visit::Close => {
+                    let jump = open.pop().unwrap();
+                    if let Instruction::Jump(_) = instructions[jump] {
+                        let otherwise = kinds.pop().unwrap();
+                        let then = kinds.pop().unwrap();
+                        kinds.push(then.merge(otherwise));
+                    }
+                    let target = instructions.len();
+                    instructions[jump].set_target(target);
+                    continue;
+                }
+                Visit::Bind(name, bind) => {
+                    let index = match bindings.iter().position(|b| b == name) {
+                        Some(index) => index,
+                        None => {
+                            bindings.push(name.to_string());
+                            bindings.len() - 1
+                        }
+                    };
+                    instructions.push(bind(index));
+                    scope.push((name, kinds.pop().unwrap()));
+                    continue;
+                }
+                Visit::Unbind => {
+                    instructions.push(Instruction::Unbind);
+                    scope.pop();
+                    continue;
+                }
+                Visit::Enter(expr) => expr,
+            };
+
+            let (instruction, operands) = match expr {
+                Expression::Number(n) => (Instruction::Const(*n), vec![]),
+                Expression::Bool(b) => {
+                    instructions.push(Instruction::Const(truth(*b)));
+                    kinds.push(Kind::Truth);
+                    continue;
+                }
+                Expression::String(_) | Expression::Null | Expression::List(_) => {
+                    return Err(non_numeric(expr));
+                }
+                Expression::Variable(name) => {
+                    if let Some(local) = scope.iter().rposition(|(local, _)| local == name) {
+                        (Instruction::Local(local), vec![])
+                    } else {
+                        let slot = variables
+                            .iter()
+                            .position(|variable| variable == name)
+                            .ok_or_else(|| EvalError::UnknownVariable { name: name.clone() })?;
+                        (Instruction::Load(slot), vec![])
+                    }
+                }
+                Expression::Add(a, b) => (Instruction::Add, vec![&**a, &**b]),
+                Expression::Subtract(a, b) => (Instruction::Subtract, vec![&**a, &**b]),
+                Expression::Multiply(a, b) => (Instruction::Multiply, vec![&**a, &**b]),
+                Expression::Divide(a, b) => (Instruction::Divide, vec![&**a, &**b]),
+                Expression::Power(a, b) => (Instruction::Power, vec![&**a, &**b]),
+                Expression::Negate(a) => (Instruction::Negate, vec![&**a]),
+                Expression::Less(a, b) => (Instruction::Less, vec![&**a, &**b]),
+                Expression::LessEqual(a, b) => (Instruction::LessEqual, vec![&**a, &**b]),
+                Expression::Greater(a, b) => (Instruction::Greater, vec![&**a, &**b]),
+                Expression::GreaterEqual(a, b) => (Instruction::GreaterEqual, vec![&**a, &**b]),
+                Expression::Equal(a, b) | Expression::NotEqual(a, b) => {
+                    pending.extend([Visit::Compare(expr), Visit::Enter(b), Visit::Enter(a)]);
+                    continue;
+                }
+                Expression::Not(a) => (Instruction::Not, vec![&**a]),
+                Expression::And(a, b) | Expression::Or(a, b) => {
+                    let jump = match expr {
+                        Expression::And(..) => Instruction::And(0),
+                        _ => Instruction::Or(0),
+                    };
+                    pending.extend(
+                        [
+                            Visit::Enter(a),
+                            Visit::Open(jump),
+                            Visit::Enter(b),
+                            Visit::Emit(Instruction::Truth),
+                            Visit::Close,
+                        ]
+                        .into_iter()
+                        .rev(),
+                    );
+                    continue;
+                }
+                Expression::Conditional {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    pending.extend(
+                        [
+                            Visit::Enter(condition),
+                            Visit::Open(Instruction::JumpUnless(0)),
+                            Visit::Enter(then),
+                            Visit::Else,
+                            Visit::Enter(otherwise),
+                            Visit::Close,
+                        ]
+                        .into_iter()
+                        .rev(),
+                    );
+                    continue;
+                }
+                Expression::Call { name, args } => {
+                    let function = match functions.iter().position(|f| f == name) {
+                        Some(index) => index,
+                        None => {
+                            functions.push(name.clone());
+                            functions.len() - 1
+                        }
+                    };
+                    let argc = args.len();
+                    pending.push(Visit::Emit(Instruction::Call { function, argc }));
+                    pending.extend(args.iter().rev().map(Visit::Enter));
+                    pending.push(Visit::Emit(Instruction::Resolve { function, argc }));
+                    continue;
+                }
+                Expression::Let { name, value, body }
+                | Expression::Assign { name, value, body } => {
+                    let bind = match expr {
+                        Expression::Let { .. } => Instruction::Bind,
+                        _ => Instruction::Assign,
+                    };
+                    pending.extend(
+                        [
+                            Visit::Enter(value),
+                            Visit::Bind(name, bind),
+                            Visit::Enter(body),
+                            Visit::Unbind,
+                        ]
+                        .into_iter()
+                        .rev(),
+                    );
+                    continue;
+                }
+                Expression::Error => return Err(EvalError::InvalidExpression),
+            };
+
+            pending.push(Visit::Emit(instruction));
+            pending.extend(operands.into_iter().rev().map(Visit::Enter));
+        }
+
+        let variables = variables.iter().map(|name| name.to_string()).collect();
+        Ok(Program::new(variables, functions, bindings, instructions)
+            .expect("lowering always produces a well formed program"))
+    }
+}
+
+impl Program {
+    /// Checks that the instructions are well formed, returning `None` if they are not
+    fn new(
+        variables: Vec<String>,
+        functions: Vec<String>,
+        bindings: Vec<String>,
+        instructions: Vec<Instruction>,
+    ) -> Option<Program> {
+        /// Instructions up to `end` that must leave `depth` values on the stack and `locals`
+        /// locals bound. For the first branch of a conditional, `second` is where the second
+        /// branch starts and ends.
+        struct Range {
+            end: usize,
+            depth: usize,
+            locals: usize,
+            second: Option<(usize, usize)>,
+        }
+
+        let mut ranges = vec![Range {
+            end: instructions.len(),
+            depth: 1,
+            locals: 0,
+            second: None,
+        }];
+        let mut depth = 0usize;
+        let mut locals = 0usize;
+        let mut max_stack = 0;
+        let mut pc = 0;
+        loop {
+            while let Some(range) = ranges.pop_if(|range| range.end == pc) {
+                if depth != range.depth || locals != range.locals {
+                    return None;
+                }
+                // Skip the `Jump` ending the first branch and check the second
+                if let Some((start, end)) = range.second {
+                    depth -= 1;
+                    ranges.push(Range {
+                        end,
+                        depth: range.depth,
+                        locals,
+                        second: None,
+                    });
+                    pc = start;
+                }
+            }
+            let Some(enclosing) = ranges.last() else {
+                break;
+            };
+
+            let instruction = instructions[pc];
+            let (effect, needed) = instruction.stack_effect();
+            if depth < needed {
+                return None;
+            }
+            match instruction {
+                Instruction::Load(slot) if slot >= variables.len() => return None,
+                Instruction::Call { function, .. } | Instruction::Resolve { function, .. }
+                    if function >= functions.len() =>
+                {
+                    return None;
+                }
+                Instruction::Local(index) if index >= locals => return None,
+                Instruction::Bind(index) | Instruction::Assign(index)
+                    if index >= bindings.len() =>
+                {
+                    return None;
+                }
+                Instruction::Bind(_) | Instruction::Assign(_) => locals += 1,
+                Instruction::Unbind => locals = locals.checked_sub(1)?,
+                Instruction::Jump(_) => return None,
+                Instruction::JumpUnless(start) => match instructions.get(start.checked_sub(1)?) {
+                    Some(&Instruction::Jump(end))
+                        if pc + 1 < start && start <= end && end <= enclosing.end =>
+                    {
+                        ranges.push(Range {
+                            end: start - 1,
+                            depth,
+                            locals,
+                            second: Some((start, end)),
+                        });
+                    }
+                    _ => return None,
+                },
+                Instruction::And(end) | Instruction::Or(end) => {
+                    match instructions.get(end.checked_sub(1)?) {
+                        Some(Instruction::Truth) if pc + 1 < end && end <= enclosing.end => {
+                            ranges.push(Range {
+                                end,
+                                depth,
+                                locals,
+                                second: None,
+                            });
+                        }
+                        _ => return None,
+                    }
+                }
+                _ => {}
+            }
+
+            depth = depth.checked_add_signed(effect)?;
+            max_stack = max_stack.max(depth);
+            pc += 1;
+        }
+
+        Some(Program {
+            variables,
+            functions,
+            bindings,
+            instructions,
+            max_stack,
+        })
+    }
+
+    /// Variable names in slot order
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Names of the functions referenced by [`Instruction::Call`]
+    pub fn functions(&self) -> &[String] {
+        &self.functions
+    }
+
+    /// Names bound by [`Instruction::Bind`] and [`Instruction::Assign`]
+    pub fn bindings(&self) -> &[String] {
+        &self.bindings
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn evaluate(&self, slots: &[f64]) -> Result<f64, EvalError> {
+        self.evaluate_with(slots, &FunctionRegistry::new())
+    }
+
+    /// Runs the program with `slots[i]` as the value of `variables()[i]`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slots` is shorter than [`Program::variables`].
+    pub fn evaluate_with(
+        &self,
+        slots: &[f64],
+        functions: &FunctionRegistry,
+    ) -> Result<f64, EvalError> {
+        assert!(
+            slots.len() >= self.variables.len(),
+            "expected {} slots, got {}",
+            self.variables.len(),
+            slots.len()
+        );
+
+        let mut resolved: Vec<Option<FunctionRef<'_>>> = vec![None; self.functions.len()];
+        let mut stack: Vec<f64> = Vec::with_capacity(self.max_stack);
+        let mut locals: Vec<f64> = Vec::new();
+
+        let mut pc = 0;
+        while let Some(instruction) = self.instructions.get(pc) {
+            let current = pc;
+            pc += 1;
+            let value = match *instruction {
+                Instruction::Const(n) => n,
+                Instruction::Load(slot) => slots[slot],
+                Instruction::Local(index) => locals[index],
+                Instruction::Bind(_) | Instruction::Assign(_) => {
+                    locals.push(pop(&mut stack));
+                    continue;
+                }
+                Instruction::Unbind => {
+                    locals.pop();
+                    continue;
+                }
+                Instruction::Negate => -pop(&mut stack),
+                Instruction::Not => truth(pop(&mut stack) == 0.0),
+                Instruction::Truth => truth(pop(&mut stack) != 0.0),
+                Instruction::JumpUnless(target) => {
+                    if pop(&mut stack) == 0.0 {
+                        pc = target;
+                    }
+                    continue;
+                }
+                Instruction::Jump(target) => {
+                    pc = target;
+                    continue;
+                }
+                Instruction::And(target) | Instruction::Or(target) => {
+                    let top = stack
+                        .last_mut()
+                        .expect("programs are validated on construction");
+                    let decided = match instruction {
+                        Instruction::And(_) => *top == 0.0,
+                        _ => *top != 0.0,
+                    };
+                    if decided {
+                        *top = truth(*top != 0.0);
+                        pc = target;
+                    } else {
+                        stack.pop();
+                    }
+                    continue;
+                }
+                Instruction::Resolve { function, argc } => {
+                    self.resolve(&mut resolved, functions, function, argc)?;
+                    continue;
+                }
+                Instruction::Call { function, argc } => {
+                    let function = self.resolve(&mut resolved, functions, function, argc)?;
+                    let start = stack.len() - argc;
+                    let value = function.call(&stack[start..]);
+                    stack.truncate(start);
+                    value
+                }
+                binary => {
+                    let b = pop(&mut stack);
+                    let a = pop(&mut stack);
+                    match binary {
+                        Instruction::Add => a + b,
+                        Instruction::Subtract => a - b,
+                        Instruction::Multiply => a * b,
+                        Instruction::Divide if b == 0.0 => {
+                            // The divisor is whatever was pushed last before this instruction
+                            return Err(EvalError::DivisionByZero {
+                                divisor: self.decompile(current),
+                            });
+                        }
+                        Instruction::Divide => a / b,
+                        Instruction::Power => a.powf(b),
+                        Instruction::Less => truth(a < b),
+                        Instruction::LessEqual => truth(a <= b),
+                        Instruction::Greater => truth(a > b),
+                        Instruction::GreaterEqual => truth(a >= b),
+                        Instruction::Equal => truth(a == b),
+                        Instruction::NotEqual => truth(a != b),
+                        _ => unreachable!(),
+                    }
+                }
+            };
+            stack.push(value);
+        }
+
+        Ok(pop(&mut stack))
+    }
+
+    /// The function `functions()[function]` stands for in `registry`, failing as
+    /// [`Expression::evaluate`] does if it is unknown or does not accept `argc` arguments
+    fn resolve<'f>(
+        &self,
+        resolved: &mut [Option<FunctionRef<'f>>],
+        registry: &'f FunctionRegistry,
+        function: usize,
+        argc: usize,
+    ) -> Result<FunctionRef<'f>, EvalError> {
+        let name = &self.functions[function];
+        let function = match resolved[function] {
+            Some(function) => function,
+            None => *resolved[function].insert(
+                registry
+                    .resolve(name)
+                    .ok_or_else(|| EvalError::UnknownFunction { name: name.clone() })?,
+            ),
+        };
+        if !function.arity().accepts(argc) {
+            return Err(EvalError::ArityMismatch {
+                name: name.clone(),
+                expected: function.arity(),
+                found: argc,
+            });
+        }
+        Ok(function)
+    }
+
+    /// Rebuilds the expression the program was lowered from
+    pub fn to_expression(&self) -> Expression {
+        self.decompile(self.instructions.len())
+    }
+
+    /// Expression for the value on top of the stack just before instruction `end`
+    fn decompile(&self, end: usize) -> Expression {
+        let mut stack: Vec<Expression> = Vec::new();
+        // Constructs whose parts will all be on the stack at the given instruction
+        let mut pending: Vec<(usize, Instruction)> = Vec::new();
+        // Names, values and instructions of the enclosing bindings, outermost first
+        let mut bound: Vec<(&str, Expression, Instruction)> = Vec::new();
+        let mut pc = 0;
+        loop {
+            while let Some((_, construct)) = pending.pop_if(|&mut (close, _)| close == pc) {
+                let b = stack.pop().unwrap();
+                let a = stack.pop().unwrap();
+                let expression = match construct {
+                    Instruction::And(_) => expr::and(a, b),
+                    Instruction::Or(_) => expr::or(a, b),
+                    _ => expr::conditional(stack.pop().unwrap(), a, b),
+                };
+                stack.push(expression);
+            }
+            if pc >= end {
+                break;
+            }
+
+            let instruction = self.instructions[pc];
+            pc += 1;
+            let expression = match instruction {
+                Instruction::JumpUnless(target) => {
+                    let Instruction::Jump(join) = self.instructions[target - 1] else {
+                        unreachable!("programs are validated on construction")
+                    };
+                    if end < target {
+                        // Only the first branch runs up to `end`
+                        stack.pop();
+                    } else if end < join {
+                        stack.pop();
+                        pc = target;
+                    } else {
+                        pending.push((join, instruction));
+                    }
+                    continue;
+                }
+                // Ends a first branch that is decompiled whole, the second follows
+                Instruction::Jump(_) | Instruction::Truth | Instruction::Resolve { .. } => continue,
+                Instruction::And(target) | Instruction::Or(target) => {
+                    if end < target {
+                        stack.pop();
+                    } else {
+                        pending.push((target, instruction));
+                    }
+                    continue;
+                }
+                Instruction::Const(n) => expr::number(n),
+                Instruction::Load(slot) => expr::variable(&self.variables[slot]),
+                Instruction::Local(index) => expr::variable(bound[index].0),
+                Instruction::Bind(index) | Instruction::Assign(index) => {
+                    bound.push((&self.bindings[index], stack.pop().unwrap(), instruction));
+                    continue;
+                }
+                Instruction::Unbind => {
+                    let (name, value, bind) = bound.pop().unwrap();
+                    let build = match bind {
+                        Instruction::Bind(_) => expr::let_in,
+                        _ => expr::assign,
+                    };
+                    build(name, value, stack.pop().unwrap())
+                }
+                Instruction::Negate => expr::negate(stack.pop().unwrap()),
+                Instruction::Not => expr::not(stack.pop().unwrap()),
+                Instruction::Call { function, argc } => {
+                    let args = stack.split_off(stack.len() - argc);
+                    expr::call(&self.functions[function], args)
+                }
+                binary => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    let build = match binary {
+                        Instruction::Add => expr::add,
+                        Instruction::Subtract => expr::subtract,
+                        Instruction::Multiply => expr::multiply,
+                        Instruction::Divide => expr::divide,
+                        Instruction::Power => expr::power,
+                        Instruction::Less => expr::less,
+                        Instruction::LessEqual => expr::less_equal,
+                        Instruction::Greater => expr::greater,
+                        Instruction::GreaterEqual => expr::greater_equal,
+                        Instruction::Equal => expr::equal,
+                        Instruction::NotEqual => expr::not_equal,
+                        _ => unreachable!(),
+                    };
+                    build(a, b)
+                }
+            };
+            stack.push(expression);
+        }
+
+        stack.pop().unwrap()
+    }
+
+    /// Serializes the program into a compact little endian format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+
+        let write_len = |out: &mut Vec<u8>, len: usize| {
+            out.extend_from_slice(&(len as u32).to_le_bytes());
+        };
+        for names in [&self.variables, &self.functions, &self.bindings] {
+            write_len(&mut out, names.len());
+            for name in names {
+                write_len(&mut out, name.len());
+                out.extend_from_slice(name.as_bytes());
+            }
+        }
+
+        write_len(&mut out, self.instructions.len());
+        for instruction in &self.instructions {
+            out.push(instruction.opcode());
+            match *instruction {
+                Instruction::Const(n) => out.extend_from_slice(&n.to_bits().to_le_bytes()),
+                Instruction::Load(slot)
+                | Instruction::Local(slot)
+                | Instruction::Bind(slot)
+                | Instruction::Assign(slot) => write_len(&mut out, slot),
+                Instruction::Call { function, argc } | Instruction::Resolve { function, argc } => {
+                    write_len(&mut out, function);
+                    write_len(&mut out, argc);
+                }
+                Instruction::JumpUnless(target)
+                | Instruction::Jump(target)
+                | Instruction::And(target)
+                | Instruction::Or(target) => write_len(&mut out, target),
+                _ => {}
+            }
+        }
+
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, DecodeError> {
+        let mut reader = Reader { bytes, offset: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(DecodeError::InvalidHeader);
+        }
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion { version });
+        }
+
+        let variables = reader.names()?;
+        let functions = reader.names()?;
+        let bindings = reader.names()?;
+
+        let count = reader.len()?;
+        let mut instructions = Vec::with_capacity(count.min(bytes.len()));
+        for _ in 0..count {
+            let offset = reader.offset;
+            let instruction = match reader.take(1)?[0] {
+                0x01 => Instruction::Const(f64::from_bits(u64::from_le_bytes(
+                    reader.take(8)?.try_into().unwrap(),
+                ))),
+                0x02 => Instruction::Load(reader.len()?),
+                0x03 => Instruction::Local(reader.len()?),
+                0x04 => Instruction::Bind(reader.len()?),
+                0x05 => Instruction::Unbind,
+                0x06 => Instruction::Assign(reader.len()?),
+                0x10 => Instruction::Add,
+                0x11 => Instruction::Subtract,
+                0x12 => Instruction::Multiply,
+                0x13 => Instruction::Divide,
+                0x14 => Instruction::Power,
+                0x15 => Instruction::Negate,
+                0x16 => Instruction::Less,
+                0x17 => Instruction::LessEqual,
+                0x18 => Instruction::Greater,
+                0x19 => Instruction::GreaterEqual,
+                0x1a => Instruction::Equal,
+                0x1b => Instruction::NotEqual,
+                0x1c => Instruction::Not,
+                0x1d => Instruction::Truth,
+                0x20 => Instruction::Call {
+                    function: reader.len()?,
+                    argc: reader.len()?,
+                },
+                0x21 => Instruction::Resolve {
+                    function: reader.len()?,
+                    argc: reader.len()?,
+                },
+                0x30 => Instruction::JumpUnless(reader.len()?),
+                0x31 => Instruction::Jump(reader.len()?),
+                0x32 => Instruction::And(reader.len()?),
+                0x33 => Instruction::Or(reader.len()?),
+                opcode => return Err(DecodeError::InvalidOpcode { opcode, offset }),
+            };
+            instructions.push(instruction);
+        }
+
+        if reader.offset != bytes.len() {
+            return Err(DecodeError::InvalidProgram);
+        }
+        Program::new(variables, functions, bindings, instructions)
+            .ok_or(DecodeError::InvalidProgram)
+    }
+}
+
+fn pop(stack: &mut Vec<f64>) -> f64 {
+    stack.pop().expect("programs are validated on construction")
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    offset: usize,
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, n: usize) -> Result<&'b [u8], DecodeError> {
+        let end = self
+            .offset
+            .checked_add(n)
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        let bytes = self
+            .bytes
+            .get(self.offset..end)
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn len(&mut self) -> Result<usize, DecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize)
+    }
+
+    fn names(&mut self) -> Result<Vec<String>, DecodeError> {
+        (0..self.len()?)
+            .map(|_| {
+                let len = self.len()?;
+                String::from_utf8(self.take(len)?.to_vec()).map_err(|_| DecodeError::InvalidName)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::Arity;
+    use crate::value::Type;
+    use expression_macro::expr;
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_lowering() {
+        let program = expr!("x * (y + 2) - sin(x)")
+            .to_bytecode(&["x", "y"])
+            .unwrap();
+
+        assert_eq!(
+            program.instructions(),
+            [
+                Instruction::Load(0),
+                Instruction::Load(1),
+                Instruction::Const(2.0),
+                Instruction::Add,
+                Instruction::Multiply,
+                Instruction::Resolve {
+                    function: 0,
+                    argc: 1
+                },
+                Instruction::Load(0),
+                Instruction::Call {
+                    function: 0,
+                    argc: 1
+                },
+                Instruction::Subtract,
+            ]
+        );
+        assert_eq!(program.functions(), ["sin"]);
+        assert_eq!(program.to_expression(), expr!("x * (y + 2) - sin(x)"));
+    }
+
+    #[test]
+    fn test_lowering_jumps() {
+        let program = expr!("x ? y && 1 : 2").to_bytecode(&["x", "y"]).unwrap();
+
+        assert_eq!(
+            program.instructions(),
+            [
+                Instruction::Load(0),
+                Instruction::JumpUnless(7),
+                Instruction::Load(1),
+                Instruction::And(6),
+                Instruction::Const(1.0),
+                Instruction::Truth,
+                Instruction::Jump(8),
+                Instruction::Const(2.0),
+            ]
+        );
+        assert_eq!(program.to_expression(), expr!("x ? y && 1 : 2"));
+        assert_eq!(program.evaluate(&[1.0, 0.0]), Ok(0.0));
+        assert_eq!(program.evaluate(&[1.0, 5.0]), Ok(1.0));
+        assert_eq!(program.evaluate(&[0.0, 5.0]), Ok(2.0));
+    }
+
+    #[test]
+    fn test_lowering_let() {
+        let program = expr!("let x = x * 2; let y = x + y; x / y")
+            .to_bytecode(&["x", "y"])
+            .unwrap();
+
+        assert_eq!(
+            program.instructions(),
+            [
+                Instruction::Load(0),
+                Instruction::Const(2.0),
+                Instruction::Multiply,
+                Instruction::Bind(0),
+                Instruction::Local(0),
+                Instruction::Load(1),
+                Instruction::Add,
+                Instruction::Bind(1),
+                Instruction::Local(0),
+                Instruction::Local(1),
+                Instruction::Divide,
+                Instruction::Unbind,
+                Instruction::Unbind,
+            ]
+        );
+        assert_eq!(program.bindings(), ["x", "y"]);
+        assert_eq!(
+            program.to_expression(),
+            expr!("let x = x * 2; let y = x + y; x / y")
+        );
+        assert_eq!(program.evaluate(&[1.0, 2.0]), Ok(0.5));
+        assert_eq!(
+            program.evaluate(&[1.0, -2.0]),
+            Err(EvalError::DivisionByZero {
+                divisor: expr!("y")
+            })
+        );
+    }
+
+    #[test]
+    fn test_bytecode_matches_evaluate() {
+        let vars = HashMap::from([("x".to_string(), 0.3), ("y".to_string(), 7.0)]);
+        for source in [
+            "x + y * 2 - 1 / 3",
+            "-x ^ 2 ^ y + (x - y) / (x + y)",
+            "sqrt(y) * atan2(x, y) + max(x, y, 1, -2) - min(x)",
+            "log(2, y) + hypot(x, y) / exp(-x)",
+            "x < y && !(x >= 1) ? (x == 0.3 ? 4 : 2) : y != 7 ? 1 : 0",
+            "x > 1 || y > 1 ? (x && y ? 3 : 5) + if(x, -1, 1) : 1 / 0",
+            "x <= y || y > x",
+            "let r = x * y; (let r = r + 1; r * 2) + r / (r > 1 ? r : 1)",
+        ] {
+            let e = Expression::parse(source).unwrap();
+            let program = e.to_bytecode(&["x", "y"]).unwrap();
+            let expected = e.evaluate(&vars).unwrap();
+            let actual = program.evaluate(&[0.3, 7.0]).unwrap();
+            assert_eq!(expected.to_bits(), actual.to_bits(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_errors_match_evaluate() {
+        let vars = HashMap::from([("x".to_string(), 0.0), ("y".to_string(), 7.0)]);
+        // Functions are resolved before their arguments are evaluated
+        for source in [
+            "nope(1 / 0)",
+            "sin(1 / 0, 1)",
+            "sin(x) + max(y, 1 / x)",
+            "x ? 1 : sin(1, 1 / x)",
+        ] {
+            let e = Expression::parse(source).unwrap();
+            let expected = e.evaluate(&vars);
+            assert!(expected.is_err(), "{}", source);
+            let program = e.to_bytecode(&["x", "y"]).unwrap();
+            assert_eq!(program.evaluate(&[0.0, 7.0]), expected, "{}", source);
+            let compiled = e.compile(&["x", "y"]).and_then(|c| c.evaluate(&[0.0, 7.0]));
+            assert_eq!(compiled, expected, "{}", source);
+        }
+
+        // ...but not before the arguments of an enclosing call. Compiling resolves every
+        // function up front, so only the bytecode follows evaluation order here
+        for source in ["max(1 / x, nope(y))", "1 / x + nope()"] {
+            let e = Expression::parse(source).unwrap();
+            let expected = e.evaluate(&vars);
+            assert!(matches!(expected, Err(EvalError::DivisionByZero { .. })));
+            let program = e.to_bytecode(&["x", "y"]).unwrap();
+            assert_eq!(program.evaluate(&[0.0, 7.0]), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_mixed_equality() {
+        let vars = HashMap::from([("x".to_string(), 5.0)]);
+        for source in [
+            "(x > 1) == (x < 9)",
+            "x == 5 != false",
+            "let b = x > 1; b == true",
+        ] {
+            let e = Expression::parse(source).unwrap();
+            let program = e.to_bytecode(&["x"]).unwrap();
+            assert_eq!(program.evaluate(&[5.0]), e.evaluate(&vars), "{}", source);
+        }
+
+        // Evaluation finds these false, but the machine cannot tell `true` from `1`
+        assert_eq!(
+            expr!("(x > 1) == 1").to_bytecode(&["x"]),
+            Err(EvalError::TypeMismatch {
+                expected: Type::Bool,
+                found: Type::Number,
+                expression: expr!("1")
+            })
+        );
+        assert_eq!(
+            expr!("let b = x > 1; x != b").to_bytecode(&["x"]),
+            Err(EvalError::TypeMismatch {
+                expected: Type::Number,
+                found: Type::Bool,
+                expression: expr!("b")
+            })
+        );
+        assert_eq!(
+            expr!("(x ? true : 1) == 1").to_bytecode(&["x"]),
+            Err(EvalError::TypeMismatch {
+                expected: Type::Number,
+                found: Type::Bool,
+                expression: expr!("x ? true : 1")
+            })
+        );
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let program = expr!("x + 1 / (y - 1)").to_bytecode(&["x", "y"]).unwrap();
+        assert_eq!(
+            program.evaluate(&[0.0, 1.0]),
+            Err(EvalError::DivisionByZero {
+                divisor: expr!("y - 1")
+            })
+        );
+
+        // Divisions inside a branch only see the code of that branch
+        let program = expr!("(x ? 1 / y : 1 / (y - 1)) + 1 / (x || 0 ? y && x : y)")
+            .to_bytecode(&["x", "y"])
+            .unwrap();
+        for (slots, divisor) in [
+            ([1.0, 0.0], expr!("y")),
+            ([0.0, 1.0], expr!("y - 1")),
+            ([0.0, 0.0], expr!("x || 0 ? y && x : y")),
+        ] {
+            assert_eq!(
+                program.evaluate(&slots),
+                Err(EvalError::DivisionByZero { divisor })
+            );
+        }
+
+        let program = expr!("sin(x, x) + nope(x)").to_bytecode(&["x"]).unwrap();
+        assert_eq!(
+            program.evaluate(&[1.0]),
+            Err(EvalError::ArityMismatch {
+                name: "sin".to_string(),
+                expected: Arity::Exact(1),
+                found: 2
+            })
+        );
+
+        let mut functions = FunctionRegistry::new();
+        functions
+            .register("sin", 2, |a| a[0] * a[1])
+            .register("nope", 1, |a| -a[0]);
+        assert_eq!(program.evaluate_with(&[3.0], &functions), Ok(6.0));
+
+        assert_eq!(
+            expr!("x + z").to_bytecode(&["x"]),
+            Err(EvalError::UnknownVariable {
+                name: "z".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        // Deep enough that the recursive evaluator would overflow a test thread's stack
+        let mut e = expr!("x");
+        for i in 0..20_000 {
+            e = if i % 2 == 0 {
+                expr::add(e, expr::number(1.0))
+            } else {
+                expr::negate(e)
+            };
+        }
+
+        let program = e.to_bytecode(&["x"]).unwrap();
+        drop_deep(e);
+        assert_eq!(program.evaluate(&[5.0]), Ok(5.0));
+
+        let rebuilt = program.to_expression();
+        assert_eq!(rebuilt.to_bytecode(&["x"]).as_ref(), Ok(&program));
+        drop_deep(rebuilt);
+    }
+
+    /// Drops a tree one node at a time, where the default drop would recurse as deep as it
+    fn drop_deep(expr: Expression) {
+        let mut pending = vec![expr];
+        while let Some(expr) = pending.pop() {
+            match expr {
+                Expression::List(items) | Expression::Call { args: items, .. } => {
+                    pending.extend(items)
+                }
+                Expression::Add(a, b)
+                | Expression::Subtract(a, b)
+                | Expression::Multiply(a, b)
+                | Expression::Divide(a, b)
+                | Expression::Power(a, b)
+                | Expression::Less(a, b)
+                | Expression::LessEqual(a, b)
+                | Expression::Greater(a, b)
+                | Expression::GreaterEqual(a, b)
+                | Expression::Equal(a, b)
+                | Expression::NotEqual(a, b)
+                | Expression::And(a, b)
+                | Expression::Or(a, b)
+                | Expression::Let {
+                    value: a, body: b, ..
+                }
+                | Expression::Assign {
+                    value: a, body: b, ..
+                } => pending.extend([*a, *b]),
+                Expression::Negate(a) | Expression::Not(a) => pending.push(*a),
+                Expression::Conditional {
+                    condition,
+                    then,
+                    otherwise,
+                } => pending.extend([*condition, *then, *otherwise]),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_serialization_round_trip() {
+        let program = expr!("max(x, -y, 0.1) / (x - 3) ^ y")
+            .to_bytecode(&["x", "y"])
+            .unwrap();
+        let bytes = program.to_bytes();
+
+        assert_eq!(&bytes[..5], b"EXPB\x04");
+        assert_eq!(Program::from_bytes(&bytes), Ok(program));
+
+        let program = expr!("let a = x + 1; x = a * a; x - a")
+            .to_bytecode(&["x"])
+            .unwrap();
+        assert_eq!(
+            program.to_expression(),
+            expr!("let a = x + 1; x = a * a; x - a")
+        );
+        assert_eq!(program.evaluate(&[1.0]), Ok(2.0));
+        assert_eq!(Program::from_bytes(&program.to_bytes()), Ok(program));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let bytes = expr!("x * 2").to_bytecode(&["x"]).unwrap().to_bytes();
+
+        assert_eq!(
+            Program::from_bytes(b"nope"),
+            Err(DecodeError::InvalidHeader)
+        );
+        assert_eq!(
+            Program::from_bytes(&[&MAGIC[..], &[VERSION + 1]].concat()),
+            Err(DecodeError::UnsupportedVersion {
+                version: VERSION + 1
+            })
+        );
+        assert_eq!(
+            Program::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+
+        let mut bad_opcode = bytes.clone();
+        *bad_opcode.last_mut().unwrap() = 0xff;
+        assert_eq!(
+            Program::from_bytes(&bad_opcode),
+            Err(DecodeError::InvalidOpcode {
+                opcode: 0xff,
+                offset: bytes.len() - 1
+            })
+        );
+
+        let unbalanced = Program {
+            variables: vec!["x".to_string()],
+            functions: vec![],
+            bindings: vec![],
+            instructions: vec![Instruction::Load(0), Instruction::Const(2.0)],
+            max_stack: 2,
+        };
+        assert_eq!(
+            Program::from_bytes(&unbalanced.to_bytes()),
+            Err(DecodeError::InvalidProgram)
+        );
+
+        let conditional = expr!("x ? 1 : 2").to_bytecode(&["x"]).unwrap();
+        let and = expr!("x && 1").to_bytecode(&["x"]).unwrap();
+        let binding = expr!("let a = x; x ? a : 2").to_bytecode(&["x"]).unwrap();
+        for (program, patch) in [
+            // Backwards, past the end and into the middle of the other branch
+            (&conditional, (1, Instruction::JumpUnless(0))),
+            (&conditional, (1, Instruction::JumpUnless(9))),
+            (&conditional, (3, Instruction::Jump(2))),
+            (&conditional, (3, Instruction::Const(0.0))),
+            (&and, (1, Instruction::And(2))),
+            (&and, (3, Instruction::Negate)),
+            // A missing local, one never unbound, one unbound before use and a missing name
+            (&binding, (4, Instruction::Local(1))),
+            (&binding, (7, Instruction::Negate)),
+            (&binding, (2, Instruction::Unbind)),
+            (&binding, (1, Instruction::Bind(1))),
+        ] {
+            let mut broken = program.clone();
+            broken.instructions[patch.0] = patch.1;
+            assert_eq!(
+                Program::from_bytes(&broken.to_bytes()),
+                Err(DecodeError::InvalidProgram),
+                "{:?}",
+                broken.instructions
+            );
+        }
+        assert_eq!(
+            Program::from_bytes(&conditional.to_bytes()),
+            Ok(conditional)
+        );
+    }
+
+    // Truth values are only generated as conditions, since the tree walker rejects
+    // arithmetic on them while the machine treats them as numbers
+    fn condition(number: BoxedStrategy<Expression>) -> impl Strategy<Value = Expression> {
+        let pair = || (number.clone(), number.clone());
+        prop_oneof![
+            number.clone(),
+            pair().prop_map(|(a, b)| expr::less(a, b)),
+            pair().prop_map(|(a, b)| expr::equal(a, b)),
+            (pair(), number.clone()).prop_map(|((a, b), c)| expr::and(expr::less(a, b), c)),
+            (number.clone(), pair()).prop_map(|(a, (b, c))| expr::or(a, expr::equal(b, c))),
+            pair().prop_map(|(a, b)| expr::not(expr::and(a, b))),
+            (pair(), pair()).prop_map(|((a, b), (c, d))| {
+                expr::not_equal(expr::less(a, b), expr::greater(c, d))
+            }),
+            // Rejected when lowering, since evaluation finds a `Bool` unequal to any number
+            (pair(), number.clone()).prop_map(|((a, b), c)| expr::equal(expr::less(a, b), c)),
+        ]
+    }
+
+    fn arb_expression() -> impl Strategy<Value = Expression> {
+        let leaf = prop_oneof![
+            (-10.0..10.0f64).prop_map(Expression::Number),
+            prop_oneof![Just("x"), Just("y")].prop_map(expr::variable),
+        ];
+
+        leaf.prop_recursive(6, 48, 3, |inner| {
+            let pair = || (inner.clone(), inner.clone());
+            prop_oneof![
+                pair().prop_map(|(a, b)| expr::add(a, b)),
+                pair().prop_map(|(a, b)| expr::subtract(a, b)),
+                pair().prop_map(|(a, b)| expr::multiply(a, b)),
+                pair().prop_map(|(a, b)| expr::divide(a, b)),
+                pair().prop_map(|(a, b)| expr::power(a, b)),
+                inner.clone().prop_map(expr::negate),
+                (condition(inner.clone()), inner.clone(), inner.clone())
+                    .prop_map(|(c, a, b)| expr::conditional(c, a, b)),
+                inner.clone().prop_map(|a| expr::call("sin", vec![a])),
+                prop::collection::vec(inner.clone(), 1..4).prop_map(|args| expr::call("max", args)),
+                (prop_oneof![Just("x"), Just("y")], pair())
+                    .prop_map(|(name, (value, body))| expr::let_in(name, value, body)),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_bytecode_is_bit_identical(e in arb_expression(), x in -5.0..5.0f64, y in -5.0..5.0f64) {
+            let vars = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+            let program = match e.to_bytecode(&["x", "y"]) {
+                Ok(program) => program,
+                Err(error) => {
+                    prop_assert!(matches!(error, EvalError::TypeMismatch { .. }), "{:?}", error);
+                    prop_assert!(e.compile(&["x", "y"]).is_err());
+                    return Ok(());
+                }
+            };
+
+            match (e.evaluate(&vars), program.evaluate(&[x, y])) {
+                (Ok(expected), Ok(actual)) => prop_assert_eq!(expected.to_bits(), actual.to_bits()),
+                (expected, actual) => prop_assert_eq!(expected, actual),
+            }
+            prop_assert_eq!(program.to_expression(), e);
+        }
+    }
+}
@@ -0,0 +1,512 @@
+//! Compiles an [`Expression`] into nested closures for repeated evaluation.
+//!
+//! Variable names are resolved to slot indices and functions are looked up once, at
+//! compile time, so evaluating a [`CompiledExpression`] neither walks the boxed tree
+//! nor hashes any names.
+//!
+//! Compiled code computes with `f64` only. Booleans are `1` and `0`, as
+//! [`Expression::evaluate`] returns them, so arithmetic on a comparison is not a type
+//! error here. String, null and list literals are rejected when compiling, and so is `==`
+//! or `!=` between a boolean and a number, which evaluation finds unequal.
+
+use crate::error::EvalError;
+use crate::expression::{Expression, Kind, check_equality, non_numeric, truth};
+use crate::functions::{FunctionRef, FunctionRegistry, builtin};
+use std::fmt;
+
+/// The most `let` locals [`CompiledExpression::evaluate`] keeps on the stack.
+const STACK_LOCALS: usize = 8;
+
+/// Computes a value from the variables' slots and the values of the enclosing `let`s
+type Compiled<'a> = Box<dyn Fn(&[f64], &mut [f64]) -> Result<f64, EvalError> + Send + Sync + 'a>;
+
+/// An expression with its variables bound to positions in a slot slice
+pub struct CompiledExpression<'a> {
+    variables: Vec<String>,
+    /// How many `let` bindings are in scope at most, each with a fixed index in the locals
+    locals: usize,
+    function: Compiled<'a>,
+}
+
+impl fmt::Debug for CompiledExpression<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompiledExpression")
+            .field("variables", &self.variables)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CompiledExpression<'_> {
+    /// Variable names in slot order
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Evaluates with `slots[i]` as the value of `variables()[i]`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slots` is shorter than [`CompiledExpression::variables`].
+    pub fn evaluate(&self, slots: &[f64]) -> Result<f64, EvalError> {
+        assert!(
+            slots.len() >= self.variables.len(),
+            "expected {} slots, got {}",
+            self.variables.len(),
+            slots.len()
+        );
+        if self.locals <= STACK_LOCALS {
+            return (self.function)(slots, &mut [0.0; STACK_LOCALS][..self.locals]);
+        }
+        (self.function)(slots, &mut vec![0.0; self.locals])
+    }
+}
+
+impl Expression {
+    /// Compiles the expression, binding `variables[i]` to slot `i`.
+    ///
+    /// Fails with the error [`Expression::evaluate`] would report for a variable missing
+    /// from `variables`, an unknown function or a wrong argument count, and with a type
+    /// error for a string, null or list literal.
+    pub fn compile(&self, variables: &[&str]) -> Result<CompiledExpression<'static>, EvalError> {
+        Compiler {
+            variables,
+            functions: None,
+            bindings: Vec::new(),
+            locals: 0,
+        }
+        .finish(self)
+    }
+
+    /// Compiles with user registered functions available alongside the built-ins
+    pub fn compile_with<'a>(
+        &self,
+        variables: &[&str],
+        functions: &'a FunctionRegistry,
+    ) -> Result<CompiledExpression<'a>, EvalError> {
+        Compiler {
+            variables,
+            functions: Some(functions),
+            bindings: Vec::new(),
+            locals: 0,
+        }
+        .finish(self)
+    }
+}
+
+struct Compiler<'v, 'a> {
+    variables: &'v [&'v str],
+    functions: Option<&'a FunctionRegistry>,
+    /// Names bound by the enclosing `let`s and the kinds of their values, outermost first.
+    /// The value of `bindings[i]` is in local `i`
+    bindings: Vec<(String, Kind)>,
+    /// The most bindings in scope so far
+    locals: usize,
+}
+
+impl<'a> Compiler<'_, 'a> {
+    fn finish(&mut self, expr: &Expression) -> Result<CompiledExpression<'a>, EvalError> {
+        Ok(CompiledExpression {
+            variables: self.variables.iter().map(|name| name.to_string()).collect(),
+            function: self.compile(expr)?,
+            locals: self.locals,
+        })
+    }
+
+    fn resolve(&self, name: &str) -> Option<FunctionRef<'a>> {
+        match self.functions {
+            Some(functions) => functions.resolve(name),
+            None => builtin(name).map(FunctionRef::Builtin),
+        }
+    }
+
+    /// Whether `expr` computes a number or a truth value
+    fn kind(&mut self, expr: &Expression) -> Kind {
+        match expr {
+            Expression::Bool(_)
+            | Expression::Less(..)
+            | Expression::LessEqual(..)
+            | Expression::Greater(..)
+            | Expression::GreaterEqual(..)
+            | Expression::Equal(..)
+            | Expression::NotEqual(..)
+            | Expression::And(..)
+            | Expression::Or(..)
+            | Expression::Not(_) => Kind::Truth,
+            Expression::Variable(name) => self
+                .bindings
+                .iter()
+                .rev()
+                .find(|(bound, _)| bound == name)
+                .map_or(Kind::Number, |&(_, kind)| kind),
+            Expression::Conditional {
+                then, otherwise, ..
+            } => self.kind(then).merge(self.kind(otherwise)),
+            Expression::Let { name, value, body } | Expression::Assign { name, value, body } => {
+                let kind = self.kind(value);
+                self.bindings.push((name.clone(), kind));
+                let kind = self.kind(body);
+                self.bindings.pop();
+                kind
+            }
+            _ => Kind::Number,
+        }
+    }
+
+    fn compile(&mut self, expr: &Expression) -> Result<Compiled<'a>, EvalError> {
+        Ok(match expr {
+            Expression::Number(n) => {
+                let n = *n;
+                Box::new(move |_, _| Ok(n))
+            }
+            Expression::Bool(b) => {
+                let n = truth(*b);
+                Box::new(move |_, _| Ok(n))
+            }
+            Expression::String(_) | Expression::Null | Expression::List(_) => {
+                return Err(non_numeric(expr));
+            }
+            Expression::Variable(name) => {
+                if let Some(index) = self.bindings.iter().rposition(|(bound, _)| bound == name) {
+                    return Ok(Box::new(move |_, locals| Ok(locals[index])));
+                }
+                let slot = self
+                    .variables
+                    .iter()
+                    .position(|variable| variable == name)
+                    .ok_or_else(|| EvalError::UnknownVariable { name: name.clone() })?;
+                Box::new(move |slots, _| Ok(slots[slot]))
+            }
+            Expression::Add(a, b) => {
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| Ok(a(slots, locals)? + b(slots, locals)?))
+            }
+            Expression::Subtract(a, b) => {
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| Ok(a(slots, locals)? - b(slots, locals)?))
+            }
+            Expression::Multiply(a, b) => {
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| Ok(a(slots, locals)? * b(slots, locals)?))
+            }
+            Expression::Divide(a, b) => {
+                let divisor = (**b).clone();
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| {
+                    let numerator = a(slots, locals)?;
+                    let denominator = b(slots, locals)?;
+                    if denominator == 0.0 {
+                        return Err(EvalError::DivisionByZero {
+                            divisor: divisor.clone(),
+                        });
+                    }
+                    Ok(numerator / denominator)
+                })
+            }
+            Expression::Power(a, b) => {
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| Ok(a(slots, locals)?.powf(b(slots, locals)?)))
+            }
+            Expression::Negate(a) => {
+                let a = self.compile(a)?;
+                Box::new(move |slots, locals| Ok(-a(slots, locals)?))
+            }
+            Expression::Less(a, b) => {
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| Ok(truth(a(slots, locals)? < b(slots, locals)?)))
+            }
+            Expression::LessEqual(a, b) => {
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| Ok(truth(a(slots, locals)? <= b(slots, locals)?)))
+            }
+            Expression::Greater(a, b) => {
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| Ok(truth(a(slots, locals)? > b(slots, locals)?)))
+            }
+            Expression::GreaterEqual(a, b) => {
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| Ok(truth(a(slots, locals)? >= b(slots, locals)?)))
+            }
+            Expression::Equal(a, b) => {
+                check_equality((a, self.kind(a)), (b, self.kind(b)))?;
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| Ok(truth(a(slots, locals)? == b(slots, locals)?)))
+            }
+            Expression::NotEqual(a, b) => {
+                check_equality((a, self.kind(a)), (b, self.kind(b)))?;
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| Ok(truth(a(slots, locals)? != b(slots, locals)?)))
+            }
+            Expression::And(a, b) => {
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| {
+                    Ok(truth(a(slots, locals)? != 0.0 && b(slots, locals)? != 0.0))
+                })
+            }
+            Expression::Or(a, b) => {
+                let (a, b) = (self.compile(a)?, self.compile(b)?);
+                Box::new(move |slots, locals| {
+                    Ok(truth(a(slots, locals)? != 0.0 || b(slots, locals)? != 0.0))
+                })
+            }
+            Expression::Not(a) => {
+                let a = self.compile(a)?;
+                Box::new(move |slots, locals| Ok(truth(a(slots, locals)? == 0.0)))
+            }
+            Expression::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let condition = self.compile(condition)?;
+                let (then, otherwise) = (self.compile(then)?, self.compile(otherwise)?);
+                Box::new(move |slots, locals| {
+                    if condition(slots, locals)? != 0.0 {
+                        then(slots, locals)
+                    } else {
+                        otherwise(slots, locals)
+                    }
+                })
+            }
+            Expression::Call { name, args } => {
+                let function = self
+                    .resolve(name)
+                    .ok_or_else(|| EvalError::UnknownFunction { name: name.clone() })?;
+                if !function.arity().accepts(args.len()) {
+                    return Err(EvalError::ArityMismatch {
+                        name: name.clone(),
+                        expected: function.arity(),
+                        found: args.len(),
+                    });
+                }
+
+                let mut args = args
+                    .iter()
+                    .map(|arg| self.compile(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // Avoid allocating an argument vector for the common small arities
+                match args.len() {
+                    1 => {
+                        let a = args.remove(0);
+                        Box::new(move |slots, locals| Ok(function.call(&[a(slots, locals)?])))
+                    }
+                    2 => {
+                        let (a, b) = (args.remove(0), args.remove(0));
+                        Box::new(move |slots, locals| {
+                            Ok(function.call(&[a(slots, locals)?, b(slots, locals)?]))
+                        })
+                    }
+                    _ => Box::new(move |slots, locals| {
+                        let values = args
+                            .iter()
+                            .map(|arg| arg(slots, locals))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        Ok(function.call(&values))
+                    }),
+                }
+            }
+            Expression::Let { name, value, body } | Expression::Assign { name, value, body } => {
+                let index = self.bindings.len();
+                let kind = self.kind(value);
+                let value = self.compile(value)?;
+                self.bindings.push((name.clone(), kind));
+                self.locals = self.locals.max(self.bindings.len());
+                let body = self.compile(body);
+                self.bindings.pop();
+                let body = body?;
+                Box::new(move |slots, locals| {
+                    // Bindings in `value` are out of scope once it is computed, so they can
+                    // share this binding's local
+                    locals[index] = value(slots, locals)?;
+                    body(slots, locals)
+                })
+            }
+            Expression::Error => return Err(EvalError::InvalidExpression),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::expr;
+    use crate::functions::Arity;
+    use crate::value::Type;
+    use expression_macro::expr;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_compiled_matches_evaluate() {
+        let vars = HashMap::from([("x".to_string(), 2.0), ("y".to_string(), 3.0)]);
+        for source in [
+            "x + y * 2",
+            "(x - y) / (x + y)",
+            "-x ^ 2 + 2 ^ y ^ 0.5",
+            "sqrt(x * 8) + atan2(y, x) - max(x, y, 10)",
+            "log(2, 8) * sin(x) + min(y)",
+            "x < y && !(y == 3) || x >= 2 ? x != y : if(y <= x, 1, -1)",
+        ] {
+            let e = Expression::parse(source).unwrap();
+            let compiled = e.compile(&["x", "y"]).unwrap();
+            assert_eq!(
+                compiled.evaluate(&[2.0, 3.0]),
+                e.evaluate(&vars),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_slot_order() {
+        let compiled = expr!("a - b").compile(&["b", "a"]).unwrap();
+
+        assert_eq!(compiled.variables(), ["b", "a"]);
+        assert_eq!(compiled.evaluate(&[1.0, 10.0]), Ok(9.0));
+        assert_eq!(compiled.evaluate(&[10.0, 1.0]), Ok(-9.0));
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert_eq!(
+            expr!("x + z").compile(&["x"]).unwrap_err(),
+            EvalError::UnknownVariable {
+                name: "z".to_string()
+            }
+        );
+        assert_eq!(
+            expr!("nope(x)").compile(&["x"]).unwrap_err(),
+            EvalError::UnknownFunction {
+                name: "nope".to_string()
+            }
+        );
+        assert_eq!(
+            expr!("sin(x, x)").compile(&["x"]).unwrap_err(),
+            EvalError::ArityMismatch {
+                name: "sin".to_string(),
+                expected: Arity::Exact(1),
+                found: 2
+            }
+        );
+        assert_eq!(
+            Expression::parse_recovering("x +")
+                .0
+                .compile(&["x"])
+                .unwrap_err(),
+            EvalError::InvalidExpression
+        );
+        assert_eq!(
+            expr!(r#"x > 0 ? "a" : x"#).compile(&["x"]).unwrap_err(),
+            EvalError::TypeMismatch {
+                expected: Type::Number,
+                found: Type::String,
+                expression: expr::string("a"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_runtime_division_by_zero() {
+        let compiled = expr!("1 / (x - 1)").compile(&["x"]).unwrap();
+
+        assert_eq!(compiled.evaluate(&[3.0]), Ok(0.5));
+        assert_eq!(
+            compiled.evaluate(&[1.0]),
+            Err(EvalError::DivisionByZero {
+                divisor: expr::subtract(expr::variable("x"), expr::number(1.0))
+            })
+        );
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let compiled = expr!("x != 0 && 1 / x > 1 ? 1 / x : 0")
+            .compile(&["x"])
+            .unwrap();
+
+        assert_eq!(compiled.evaluate(&[0.5]), Ok(2.0));
+        assert_eq!(compiled.evaluate(&[0.0]), Ok(0.0));
+    }
+
+    #[test]
+    fn test_mixed_equality() {
+        let compiled = expr!("(x > 1) == (x < 9) && x == 5 != false")
+            .compile(&["x"])
+            .unwrap();
+        assert_eq!(compiled.evaluate(&[5.0]), Ok(1.0));
+
+        // Evaluation finds these false, but compiled code cannot tell `true` from `1`
+        assert_eq!(
+            expr!("true == 1").compile(&[]).err(),
+            Some(EvalError::TypeMismatch {
+                expected: Type::Bool,
+                found: Type::Number,
+                expression: expr!("1")
+            })
+        );
+        assert_eq!(
+            expr!("let b = x > 1; x != (let c = b; c)")
+                .compile(&["x"])
+                .err(),
+            Some(EvalError::TypeMismatch {
+                expected: Type::Number,
+                found: Type::Bool,
+                expression: expr!("let c = b; c")
+            })
+        );
+    }
+
+    #[test]
+    fn test_let_bindings() {
+        let compiled = expr!("let x = x * 2; let r = x + y; (let x = r; x * r) - x")
+            .compile(&["x", "y"])
+            .unwrap();
+        assert_eq!(compiled.evaluate(&[1.0, 3.0]), Ok(23.0));
+
+        // Bindings in a value and in sibling scopes reuse the same locals
+        let compiled =
+            expr!("let a = (let b = x + 1; b * b); (let c = a; c + a) + (let d = 2; d * a)")
+                .compile(&["x"])
+                .unwrap();
+        assert_eq!(compiled.evaluate(&[1.0]), Ok(16.0));
+        assert_eq!(compiled.evaluate(&[2.0]), Ok(36.0));
+
+        // More locals than fit on the stack
+        let source = (0..STACK_LOCALS + 2).fold("x".to_string(), |body, i| {
+            format!("let v{i} = x + {i}; {body} + v{i}")
+        });
+        let expr = Expression::parse(&source).unwrap();
+        let compiled = expr.compile(&["x"]).unwrap();
+        let vars = HashMap::from([("x".to_string(), 1.0)]);
+        assert_eq!(compiled.evaluate(&[1.0]), expr.evaluate(&vars));
+        assert_eq!(compiled.evaluate(&[1.0]), Ok(56.0));
+
+        assert_eq!(
+            expr!("(let r = 1; r) + r").compile(&["x"]).err(),
+            Some(EvalError::UnknownVariable {
+                name: "r".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_compile_with_custom_functions() {
+        let mut functions = FunctionRegistry::new();
+        functions
+            .register("double", 1, |a| a[0] * 2.0)
+            .register_variadic("sum", 0, |a| a.iter().sum());
+
+        let compiled = expr!("double(x) + sum(x, 1, 2) + sum()")
+            .compile_with(&["x"], &functions)
+            .unwrap();
+        assert_eq!(compiled.evaluate(&[4.0]), Ok(15.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 2 slots, got 1")]
+    fn test_too_few_slots() {
+        let _ = expr!("x + y")
+            .compile(&["x", "y"])
+            .unwrap()
+            .evaluate(&[1.0]);
+    }
+}
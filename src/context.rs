@@ -0,0 +1,382 @@
+//! Variable lookup for [`Expression::evaluate`](crate::expression::Expression::evaluate).
+//!
+//! Any type implementing [`Context`] can supply variables, so callers can evaluate
+//! against the data they already have instead of copying it into a `HashMap<String, f64>`.
+//! Contexts holding [`Value`]s supply booleans, strings and other non-numeric variables.
+//! Maps with owned keys are also a [`ContextMut`], which scripts can assign to.
+
+use crate::expression::truth;
+use crate::value::Value;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+
+/// Source of variable values
+pub trait Context {
+    fn get(&self, name: &str) -> Option<f64>;
+
+    /// Typed lookup used by [`Expression::evaluate_value`], defaulting to the number from `get`
+    ///
+    /// [`Expression::evaluate_value`]: crate::expression::Expression::evaluate_value
+    fn value(&self, name: &str) -> Option<Value> {
+        self.get(name).map(Value::Number)
+    }
+
+    /// Looks names up in `self` first, falling back to `outer`
+    fn chain<'a, O>(&'a self, outer: &'a O) -> Chain<'a, Self, O>
+    where
+        Self: Sized,
+        O: Context + ?Sized,
+    {
+        Chain::new(self, outer)
+    }
+}
+
+/// Variables a script can assign to, see [`Expression::execute`]
+///
+/// [`Expression::execute`]: crate::expression::Expression::execute
+pub trait ContextMut: Context {
+    /// Stores `value` as the variable `name`, handing it back if the variables cannot hold
+    /// its type
+    fn set(&mut self, name: &str, value: Value) -> Result<(), Value>;
+}
+
+/// Numbers as [`Expression::evaluate`] gives them, with a `Bool` as `1` or `0`
+///
+/// [`Expression::evaluate`]: crate::expression::Expression::evaluate
+fn number(value: Value) -> Result<f64, Value> {
+    match value {
+        Value::Bool(b) => Ok(truth(b)),
+        value => value.as_number().ok_or(value),
+    }
+}
+
+impl<K, S> Context for HashMap<K, f64, S>
+where
+    K: Borrow<str> + Hash + Eq,
+    S: BuildHasher,
+{
+    fn get(&self, name: &str) -> Option<f64> {
+        HashMap::get(self, name).copied()
+    }
+}
+
+impl<K> Context for BTreeMap<K, f64>
+where
+    K: Borrow<str> + Ord,
+{
+    fn get(&self, name: &str) -> Option<f64> {
+        BTreeMap::get(self, name).copied()
+    }
+}
+
+impl<K, S> ContextMut for HashMap<K, f64, S>
+where
+    K: Borrow<str> + Hash + Eq + for<'a> From<&'a str>,
+    S: BuildHasher,
+{
+    fn set(&mut self, name: &str, value: Value) -> Result<(), Value> {
+        self.insert(name.into(), number(value)?);
+        Ok(())
+    }
+}
+
+impl<K> ContextMut for BTreeMap<K, f64>
+where
+    K: Borrow<str> + Ord + for<'a> From<&'a str>,
+{
+    fn set(&mut self, name: &str, value: Value) -> Result<(), Value> {
+        self.insert(name.into(), number(value)?);
+        Ok(())
+    }
+}
+
+impl<K, S> Context for HashMap<K, Value, S>
+where
+    K: Borrow<str> + Hash + Eq,
+    S: BuildHasher,
+{
+    fn get(&self, name: &str) -> Option<f64> {
+        HashMap::get(self, name)?.as_number()
+    }
+
+    fn value(&self, name: &str) -> Option<Value> {
+        HashMap::get(self, name).cloned()
+    }
+}
+
+impl<K> Context for BTreeMap<K, Value>
+where
+    K: Borrow<str> + Ord,
+{
+    fn get(&self, name: &str) -> Option<f64> {
+        BTreeMap::get(self, name)?.as_number()
+    }
+
+    fn value(&self, name: &str) -> Option<Value> {
+        BTreeMap::get(self, name).cloned()
+    }
+}
+
+impl<K, S> ContextMut for HashMap<K, Value, S>
+where
+    K: Borrow<str> + Hash + Eq + for<'a> From<&'a str>,
+    S: BuildHasher,
+{
+    fn set(&mut self, name: &str, value: Value) -> Result<(), Value> {
+        self.insert(name.into(), value);
+        Ok(())
+    }
+}
+
+impl<K> ContextMut for BTreeMap<K, Value>
+where
+    K: Borrow<str> + Ord + for<'a> From<&'a str>,
+{
+    fn set(&mut self, name: &str, value: Value) -> Result<(), Value> {
+        self.insert(name.into(), value);
+        Ok(())
+    }
+}
+
+/// Linear search, which beats hashing for the handful of variables most formulas use
+impl<K: AsRef<str>> Context for [(K, f64)] {
+    fn get(&self, name: &str) -> Option<f64> {
+        self.iter()
+            .find(|(key, _)| key.as_ref() == name)
+            .map(|&(_, value)| value)
+    }
+}
+
+impl<K: AsRef<str>, const N: usize> Context for [(K, f64); N] {
+    fn get(&self, name: &str) -> Option<f64> {
+        Context::get(self.as_slice(), name)
+    }
+}
+
+impl<K: AsRef<str>> Context for Vec<(K, f64)> {
+    fn get(&self, name: &str) -> Option<f64> {
+        Context::get(self.as_slice(), name)
+    }
+}
+
+impl<K: AsRef<str>> Context for [(K, Value)] {
+    fn get(&self, name: &str) -> Option<f64> {
+        Context::value(self, name)?.as_number()
+    }
+
+    fn value(&self, name: &str) -> Option<Value> {
+        self.iter()
+            .find(|(key, _)| key.as_ref() == name)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+impl<K: AsRef<str>, const N: usize> Context for [(K, Value); N] {
+    fn get(&self, name: &str) -> Option<f64> {
+        Context::get(self.as_slice(), name)
+    }
+
+    fn value(&self, name: &str) -> Option<Value> {
+        Context::value(self.as_slice(), name)
+    }
+}
+
+impl<F> Context for F
+where
+    F: Fn(&str) -> Option<f64>,
+{
+    fn get(&self, name: &str) -> Option<f64> {
+        self(name)
+    }
+}
+
+/// Two contexts layered together, see [`Context::chain`]
+#[derive(Debug)]
+pub struct Chain<'a, I: ?Sized, O: ?Sized> {
+    inner: &'a I,
+    outer: &'a O,
+}
+
+impl<'a, I: ?Sized, O: ?Sized> Chain<'a, I, O> {
+    pub fn new(inner: &'a I, outer: &'a O) -> Self {
+        Chain { inner, outer }
+    }
+}
+
+impl<I: ?Sized, O: ?Sized> Clone for Chain<'_, I, O> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<I: ?Sized, O: ?Sized> Copy for Chain<'_, I, O> {}
+
+impl<I, O> Context for Chain<'_, I, O>
+where
+    I: Context + ?Sized,
+    O: Context + ?Sized,
+{
+    fn get(&self, name: &str) -> Option<f64> {
+        self.inner.get(name).or_else(|| self.outer.get(name))
+    }
+
+    fn value(&self, name: &str) -> Option<Value> {
+        self.inner.value(name).or_else(|| self.outer.value(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::Expression;
+    use expression_macro::{ExpressionContext, expr};
+
+    #[derive(ExpressionContext)]
+    struct Customer {
+        discount: f32,
+        #[expr(rename = "loyalty")]
+        loyalty_years: u32,
+        vip: bool,
+        #[expr(skip)]
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    #[derive(ExpressionContext)]
+    struct Order {
+        qty: i64,
+        price: f64,
+        views: u64,
+        #[expr(skip)]
+        #[allow(dead_code)]
+        internal_id: u64,
+        #[expr(nested, rename = "buyer")]
+        customer: Customer,
+    }
+
+    #[test]
+    fn test_maps() {
+        let owned = HashMap::from([("x".to_string(), 2.0)]);
+        let borrowed = HashMap::from([("x", 2.0)]);
+        let sorted = BTreeMap::from([("x".to_string(), 2.0)]);
+
+        assert_eq!(Context::get(&owned, "x"), Some(2.0));
+        assert_eq!(Context::get(&borrowed, "x"), Some(2.0));
+        assert_eq!(Context::get(&sorted, "x"), Some(2.0));
+        assert_eq!(Context::get(&sorted, "y"), None);
+    }
+
+    #[test]
+    fn test_pairs() {
+        let pairs = [("x", 1.0), ("y", 2.0), ("x", 3.0)];
+
+        assert_eq!(Context::get(&pairs, "x"), Some(1.0));
+        assert_eq!(Context::get(&pairs[1..], "x"), Some(3.0));
+        assert_eq!(Context::get(&vec![("z".to_string(), 4.0)], "z"), Some(4.0));
+    }
+
+    #[test]
+    fn test_values() {
+        let values = HashMap::from([("n", Value::Integer(2)), ("flag", Value::Bool(true))]);
+        let pairs = [("s", Value::from("text"))];
+
+        assert_eq!(Context::value(&values, "n"), Some(Value::Integer(2)));
+        assert_eq!(Context::get(&values, "n"), Some(2.0));
+        assert_eq!(Context::get(&values, "flag"), None);
+        assert_eq!(pairs.value("s"), Some(Value::from("text")));
+        assert_eq!([("x", 1.5)].value("x"), Some(Value::Number(1.5)));
+
+        let scope = pairs.chain(&values);
+        assert_eq!(scope.value("flag"), Some(Value::Bool(true)));
+        assert_eq!(
+            expr!(r#"flag && s == "text" ? n * n : 0"#).evaluate_value(&scope),
+            Ok(Value::Integer(4))
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let context = |name: &str| name.strip_prefix('x').and_then(|n| n.parse().ok());
+
+        assert_eq!(context.get("x12"), Some(12.0));
+        assert_eq!(context.get("y"), None);
+    }
+
+    #[test]
+    fn test_chained_scopes() {
+        let globals = HashMap::from([("x", 1.0), ("y", 2.0)]);
+        let locals = [("x", 10.0)];
+        let fallback = |_: &str| Some(0.0);
+
+        let scope = locals.chain(&globals);
+        assert_eq!(scope.get("x"), Some(10.0));
+        assert_eq!(scope.get("y"), Some(2.0));
+        assert_eq!(scope.get("z"), None);
+        assert_eq!(scope.chain(&fallback).get("z"), Some(0.0));
+    }
+
+    #[test]
+    fn test_derived_context() {
+        let order = Order {
+            qty: 3,
+            price: 2.5,
+            views: u64::MAX,
+            internal_id: 42,
+            customer: Customer {
+                discount: 0.5,
+                loyalty_years: 4,
+                vip: true,
+                name: "Ada".to_string(),
+            },
+        };
+
+        assert_eq!(order.get("qty"), Some(3.0));
+        assert_eq!(order.get("internal_id"), None);
+        assert_eq!(order.get("buyer.loyalty"), Some(4.0));
+        assert_eq!(order.get("buyer.loyalty_years"), None);
+        assert_eq!(order.get("customer.discount"), None);
+        assert_eq!(order.get("buyer.name"), None);
+        assert_eq!(
+            expr!("qty * price * (1 - buyer.discount) - buyer.loyalty").evaluate(&order),
+            Ok(-0.25)
+        );
+
+        // Typed lookups keep integers and booleans
+        assert_eq!(order.value("qty"), Some(Value::Integer(3)));
+        assert_eq!(order.value("price"), Some(Value::Number(2.5)));
+        assert_eq!(order.value("views"), Some(Value::Number(u64::MAX as f64)));
+        assert_eq!(order.value("buyer.loyalty"), Some(Value::Integer(4)));
+        assert_eq!(order.value("buyer.vip"), Some(Value::Bool(true)));
+        assert_eq!(order.value("buyer.name"), None);
+        assert_eq!(order.get("buyer.vip"), None);
+        assert_eq!(
+            expr!("buyer.vip ? qty * buyer.loyalty : 0").evaluate_value(&order),
+            Ok(Value::Integer(12))
+        );
+    }
+
+    #[test]
+    fn test_evaluate_with_contexts() {
+        let e: Expression = expr!("x * y + 1");
+
+        assert_eq!(e.evaluate(&[("x", 2.0), ("y", 3.0)]), Ok(7.0));
+        assert_eq!(
+            e.evaluate(&BTreeMap::from([("x", 2.0), ("y", 3.0)])),
+            Ok(7.0)
+        );
+        assert_eq!(
+            e.evaluate(&|name: &str| (name.len() == 1).then_some(2.0)),
+            Ok(5.0)
+        );
+
+        let dynamic: &dyn Context = &[("x", 2.0)];
+        assert_eq!(e.evaluate(&Chain::new(dynamic, &[("y", 4.0)])), Ok(9.0));
+        assert_eq!(
+            e.evaluate(dynamic),
+            Err(crate::error::EvalError::UnknownVariable {
+                name: "y".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use crate::expression::{Expression, expr};
+
+impl Expression {
+    /// Whether `var` occurs anywhere in the expression
+    pub fn depends_on(&self, var: &str) -> bool {
+        match self {
+            Expression::Number(_)
+            | Expression::Bool(_)
+            | Expression::String(_)
+            | Expression::Null
+            | Expression::Error => false,
+            Expression::Variable(name) => name == var,
+            Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Divide(a, b)
+            | Expression::Power(a, b)
+            | Expression::Less(a, b)
+            | Expression::LessEqual(a, b)
+            | Expression::Greater(a, b)
+            | Expression::GreaterEqual(a, b)
+            | Expression::Equal(a, b)
+            | Expression::NotEqual(a, b)
+            | Expression::And(a, b)
+            | Expression::Or(a, b) => a.depends_on(var) || b.depends_on(var),
+            Expression::Negate(a) | Expression::Not(a) => a.depends_on(var),
+            Expression::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => condition.depends_on(var) || then.depends_on(var) || otherwise.depends_on(var),
+            Expression::Call { args: items, .. } | Expression::List(items) => {
+                items.iter().any(|item| item.depends_on(var))
+            }
+            // Within the body `var` is the binding, if the binding is named `var`
+            Expression::Let { name, value, body } | Expression::Assign { name, value, body } => {
+                value.depends_on(var) || (name != var && body.depends_on(var))
+            }
+        }
+    }
+
+    /// Symbolic derivative with respect to `var`.
+    ///
+    /// The result is not simplified. Subtrees that do not depend on `var` differentiate
+    /// straight to `0`, and calls to functions without a known derivative (`min`, `max`
+    /// and user registered functions) become [`Expression::Error`], as do lists. So does a
+    /// `let` whose value's derivative refers to a variable the binding shadows, such as
+    /// `let x = x ^ 2; x` with respect to `x`.
+    pub fn derivative(&self, var: &str) -> Expression {
+        if !self.depends_on(var) {
+            return expr::number(0.0);
+        }
+
+        match self {
+            Expression::Number(_)
+            | Expression::Bool(_)
+            | Expression::String(_)
+            | Expression::Null => expr::number(0.0),
+            Expression::Variable(_) => expr::number(1.0),
+            Expression::Add(u, v) => expr::add(u.derivative(var), v.derivative(var)),
+            Expression::Subtract(u, v) => expr::subtract(u.derivative(var), v.derivative(var)),
+            // (uv)' = u'v + uv'
+            Expression::Multiply(u, v) => expr::add(
+                expr::multiply(u.derivative(var), (**v).clone()),
+                expr::multiply((**u).clone(), v.derivative(var)),
+            ),
+            // (u/v)' = (u'v - uv') / v^2
+            Expression::Divide(u, v) => expr::divide(
+                expr::subtract(
+                    expr::multiply(u.derivative(var), (**v).clone()),
+                    expr::multiply((**u).clone(), v.derivative(var)),
+                ),
+                expr::power((**v).clone(), expr::number(2.0)),
+            ),
+            Expression::Power(u, v) => power_derivative(u, v, var),
+            Expression::Negate(u) => expr::negate(u.derivative(var)),
+            // Truth values are piecewise constant, so zero wherever the derivative exists
+            Expression::Less(..)
+            | Expression::LessEqual(..)
+            | Expression::Greater(..)
+            | Expression::GreaterEqual(..)
+            | Expression::Equal(..)
+            | Expression::NotEqual(..)
+            | Expression::And(..)
+            | Expression::Or(..)
+            | Expression::Not(_) => expr::number(0.0),
+            // Differentiates each branch, ignoring the jump where the condition changes
+            Expression::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => expr::conditional(
+                (**condition).clone(),
+                then.derivative(var),
+                otherwise.derivative(var),
+            ),
+            Expression::Call { name, args } => call_derivative(name, args, var),
+            Expression::Let { name, value, body } => {
+                let_derivative(name, value, body, var, expr::let_in)
+            }
+            Expression::Assign { name, value, body } => {
+                let_derivative(name, value, body, var, expr::assign)
+            }
+            Expression::List(_) | Expression::Error => Expression::Error,
+        }
+    }
+}
+
+/// Chain rule through the binding, keeping the binding so `value` is still computed once:
+/// `(let r = v; b)' = let r = v; db/dvar + db/dr * v'`
+fn let_derivative(
+    name: &str,
+    value: &Expression,
+    body: &Expression,
+    var: &str,
+    bind: fn(&str, Expression, Expression) -> Expression,
+) -> Expression {
+    let value_derivative = value.derivative(var);
+    // Inside the binding, `name` in the value's derivative would refer to the binding
+    if value_derivative.depends_on(name) {
+        return Expression::Error;
+    }
+
+    let through_binding = expr::multiply(body.derivative(name), value_derivative);
+    let body_derivative = if name == var {
+        // Every `var` in the body is the binding
+        through_binding
+    } else {
+        expr::add(body.derivative(var), through_binding)
+    };
+    bind(name, value.clone(), body_derivative)
+}
+
+fn power_derivative(u: &Expression, v: &Expression, var: &str) -> Expression {
+    // Power rule: (u^n)' = n * u^(n - 1) * u'
+    if !v.depends_on(var) {
+        return expr::multiply(
+            expr::multiply(
+                v.clone(),
+                expr::power(u.clone(), expr::subtract(v.clone(), expr::number(1.0))),
+            ),
+            u.derivative(var),
+        );
+    }
+
+    // Exponential rule: (a^v)' = a^v * ln(a) * v'
+    if !u.depends_on(var) {
+        return expr::multiply(
+            expr::multiply(
+                expr::power(u.clone(), v.clone()),
+                expr::call("ln", vec![u.clone()]),
+            ),
+            v.derivative(var),
+        );
+    }
+
+    // Logarithmic differentiation: (u^v)' = u^v * (v' * ln(u) + v * u' / u)
+    expr::multiply(
+        expr::power(u.clone(), v.clone()),
+        expr::add(
+            expr::multiply(v.derivative(var), expr::call("ln", vec![u.clone()])),
+            expr::divide(expr::multiply(v.clone(), u.derivative(var)), u.clone()),
+        ),
+    )
+}
+
+fn call_derivative(name: &str, args: &[Expression], var: &str) -> Expression {
+    let call = |name: &str, arg: &Expression| expr::call(name, vec![arg.clone()]);
+    let square = |e: Expression| expr::power(e, expr::number(2.0));
+
+    match (name, args) {
+        ("log", [base, x]) => {
+            // log(b, x) = ln(x) / ln(b)
+            expr::divide(call("ln", x), call("ln", base)).derivative(var)
+        }
+        ("atan2", [y, x]) => expr::divide(
+            expr::subtract(
+                expr::multiply(x.clone(), y.derivative(var)),
+                expr::multiply(y.clone(), x.derivative(var)),
+            ),
+            expr::add(square(x.clone()), square(y.clone())),
+        ),
+        ("hypot", [a, b]) => expr::divide(
+            expr::add(
+                expr::multiply(a.clone(), a.derivative(var)),
+                expr::multiply(b.clone(), b.derivative(var)),
+            ),
+            expr::call("hypot", vec![a.clone(), b.clone()]),
+        ),
+        (_, [u]) => {
+            // Chain rule: f(u)' = f'(u) * u'
+            let outer = match name {
+                "sin" => call("cos", u),
+                "cos" => expr::negate(call("sin", u)),
+                "tan" => expr::divide(expr::number(1.0), square(call("cos", u))),
+                "asin" => expr::divide(
+                    expr::number(1.0),
+                    call(
+                        "sqrt",
+                        &expr::subtract(expr::number(1.0), square(u.clone())),
+                    ),
+                ),
+                "acos" => expr::negate(expr::divide(
+                    expr::number(1.0),
+                    call(
+                        "sqrt",
+                        &expr::subtract(expr::number(1.0), square(u.clone())),
+                    ),
+                )),
+                "atan" => expr::divide(
+                    expr::number(1.0),
+                    expr::add(expr::number(1.0), square(u.clone())),
+                ),
+                "sinh" => call("cosh", u),
+                "cosh" => call("sinh", u),
+                "tanh" => expr::divide(expr::number(1.0), square(call("cosh", u))),
+                "sqrt" => expr::divide(
+                    expr::number(1.0),
+                    expr::multiply(expr::number(2.0), call("sqrt", u)),
+                ),
+                "cbrt" => expr::divide(
+                    expr::number(1.0),
+                    expr::multiply(expr::number(3.0), square(call("cbrt", u))),
+                ),
+                "exp" => call("exp", u),
+                "ln" => expr::divide(expr::number(1.0), u.clone()),
+                "log2" | "log10" => {
+                    let base = if name == "log2" { 2.0 } else { 10.0 };
+                    expr::divide(
+                        expr::number(1.0),
+                        expr::multiply(u.clone(), call("ln", &expr::number(base))),
+                    )
+                }
+                "abs" => call("sign", u),
+                // Piecewise constant, so zero wherever the derivative exists
+                "sign" | "floor" | "ceil" | "round" | "trunc" => return expr::number(0.0),
+                _ => return Expression::Error,
+            };
+            expr::multiply(outer, u.derivative(var))
+        }
+        _ => Expression::Error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expression_macro::expr;
+    use std::collections::HashMap;
+
+    // Compares the symbolic derivative against a central finite difference at `x`
+    fn assert_derivative_matches(e: Expression, x: f64) {
+        let at = |x: f64| HashMap::from([("x".to_string(), x), ("y".to_string(), 1.5)]);
+        let h = 1e-6;
+        let numeric =
+            (e.evaluate(&at(x + h)).unwrap() - e.evaluate(&at(x - h)).unwrap()) / (2.0 * h);
+        let symbolic = e.derivative("x").evaluate(&at(x)).unwrap();
+
+        assert!(
+            (numeric - symbolic).abs() < 1e-4 * numeric.abs().max(1.0),
+            "d/dx {} at {}: expected {}, got {}",
+            e,
+            x,
+            numeric,
+            symbolic
+        );
+    }
+
+    #[test]
+    fn test_derivative_of_atoms() {
+        assert_eq!(expr!("5").derivative("x"), expr::number(0.0));
+        assert_eq!(expr!("x").derivative("x"), expr::number(1.0));
+        assert_eq!(expr!("y").derivative("x"), expr::number(0.0));
+        assert_eq!(expr!("y ^ 2 * sin(y)").derivative("x"), expr::number(0.0));
+    }
+
+    #[test]
+    fn test_derivative_rules() {
+        assert_eq!(expr!("x + y").derivative("x"), expr!("1 + 0"));
+        assert_eq!(expr!("-x").derivative("x"), expr!("-1"));
+        assert_eq!(expr!("x * y").derivative("x"), expr!("1 * y + x * 0"));
+        assert_eq!(expr!("x ^ 3").derivative("x"), expr!("3 * x ^ (3 - 1) * 1"));
+        assert_eq!(expr!("sin(x)").derivative("x"), expr!("cos(x) * 1"));
+    }
+
+    #[test]
+    fn test_derivative_numerically() {
+        for source in [
+            "x ^ 2 + 3 * x - 7",
+            "(x + 1) / (x ^ 2 + y)",
+            "x ^ y",
+            "2 ^ x",
+            "x ^ x",
+            "x ^ sin(x)",
+            "-x ^ 3 / y",
+            "sin(x) * cos(x ^ 2)",
+            "tan(x / 3) + atan(x)",
+            "asin(x / 4) - acos(x / 4)",
+            "sqrt(x) + cbrt(x) + exp(2 * x)",
+            "ln(x) + log(2, x) + log2(x) + log10(x)",
+            "sinh(x) * cosh(x) - tanh(x)",
+            "abs(x - 5)",
+            "atan2(y, x) + hypot(x, y)",
+        ] {
+            let e = Expression::parse(source).unwrap();
+            for x in [0.7, 1.3, 2.9] {
+                assert_derivative_matches(e.clone(), x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_derivative_of_logic() {
+        assert_eq!(
+            expr!("x > 1 ? x * x : -x").derivative("x"),
+            expr!("x > 1 ? 1 * x + x * 1 : -1")
+        );
+        assert_eq!(
+            expr!("(x < y) * 3").derivative("x"),
+            expr!("0 * 3 + (x < y) * 0")
+        );
+        assert_eq!(expr!("!x || x == 2").derivative("x"), expr::number(0.0));
+    }
+
+    #[test]
+    fn test_derivative_of_let() {
+        for source in [
+            "let r = x ^ 2 + y ^ 2; sqrt(r) / r",
+            "let a = x * y; let b = a + x; a * b - x",
+            "let y = x ^ 3; y + y",
+        ] {
+            let e = Expression::parse(source).unwrap();
+            assert_derivative_matches(e.clone(), 1.3);
+            assert!(matches!(e.derivative("x"), Expression::Let { .. }));
+        }
+        assert_eq!(
+            expr!("let y = 2; x * y").derivative("x"),
+            expr!("let y = 2; 1 * y + x * 0 + (0 * y + x * 1) * 0")
+        );
+        assert_eq!(expr!("let x = x ^ 2; x").derivative("x"), Expression::Error);
+        assert_eq!(expr!("let x = 1; x").derivative("x"), expr::number(0.0));
+    }
+
+    #[test]
+    fn test_derivative_unknown_function() {
+        assert_eq!(expr!("max(x, 1)").derivative("x"), Expression::Error);
+        assert_eq!(expr!("max(y, 1)").derivative("x"), expr::number(0.0));
+        assert_eq!(
+            expr!("floor(x) + x").derivative("x"),
+            expr::add(expr::number(0.0), expr::number(1.0))
+        );
+    }
+}
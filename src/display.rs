@@ -0,0 +1,343 @@
+//! Infix printing of [`Expression`] trees.
+//!
+//! Parentheses are only emitted where the default [`ParserConfig`] would otherwise
+//! group the expression differently, so `Expression::parse(&e.to_string())` gives back `e`.
+//! The exceptions are trees the parser can never produce: negative or non-finite
+//! [`Expression::Number`]s, variables named `true`, `false` or `null`, and
+//! [`Expression::Error`] placeholders.
+
+use crate::expression::Expression;
+use crate::parsing::{Associativity, BinaryOperator, ParserConfig};
+use expression_grammar::parsing::write_string;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl Expression {
+    fn as_binary(&self) -> Option<(BinaryOperator, &Expression, &Expression)> {
+        match self {
+            Expression::Add(a, b) => Some((BinaryOperator::Add, a, b)),
+            Expression::Subtract(a, b) => Some((BinaryOperator::Subtract, a, b)),
+            Expression::Multiply(a, b) => Some((BinaryOperator::Multiply, a, b)),
+            Expression::Divide(a, b) => Some((BinaryOperator::Divide, a, b)),
+            Expression::Power(a, b) => Some((BinaryOperator::Power, a, b)),
+            Expression::Less(a, b) => Some((BinaryOperator::Less, a, b)),
+            Expression::LessEqual(a, b) => Some((BinaryOperator::LessEqual, a, b)),
+            Expression::Greater(a, b) => Some((BinaryOperator::Greater, a, b)),
+            Expression::GreaterEqual(a, b) => Some((BinaryOperator::GreaterEqual, a, b)),
+            Expression::Equal(a, b) => Some((BinaryOperator::Equal, a, b)),
+            Expression::NotEqual(a, b) => Some((BinaryOperator::NotEqual, a, b)),
+            Expression::And(a, b) => Some((BinaryOperator::And, a, b)),
+            Expression::Or(a, b) => Some((BinaryOperator::Or, a, b)),
+            _ => None,
+        }
+    }
+}
+
+struct Printer {
+    config: ParserConfig,
+}
+
+impl Printer {
+    fn precedence(&self, expr: &Expression) -> u8 {
+        match expr {
+            Expression::Negate(_) | Expression::Not(_) => self.config.prefix_precedence(),
+            // Looser than any binary operator
+            Expression::Conditional { .. } | Expression::Let { .. } | Expression::Assign { .. } => {
+                0
+            }
+            _ => match expr.as_binary() {
+                Some((operator, _, _)) => self.config.operator(operator).precedence,
+                None => u8::MAX,
+            },
+        }
+    }
+
+    fn needs_parens(&self, child: &Expression, parent: BinaryOperator, side: Side) -> bool {
+        // A prefix operator is parsed as a whole operand wherever an operand may start
+        if side == Side::Right && matches!(child, Expression::Negate(_) | Expression::Not(_)) {
+            return false;
+        }
+
+        let parent = self.config.operator(parent);
+        let child = self.precedence(child);
+        let binds_away = match parent.associativity {
+            Associativity::Left => side == Side::Right,
+            Associativity::Right => side == Side::Left,
+        };
+        child < parent.precedence || (child == parent.precedence && binds_away)
+    }
+
+    fn write_operand(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        expr: &Expression,
+        parens: bool,
+    ) -> fmt::Result {
+        if parens {
+            write!(f, "(")?;
+            self.write(f, expr)?;
+            write!(f, ")")
+        } else {
+            self.write(f, expr)
+        }
+    }
+
+    fn write_items(&self, f: &mut fmt::Formatter<'_>, items: &[Expression]) -> fmt::Result {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            self.write(f, item)?;
+        }
+        Ok(())
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, expr: &Expression) -> fmt::Result {
+        if let Some((operator, lhs, rhs)) = expr.as_binary() {
+            self.write_operand(f, lhs, self.needs_parens(lhs, operator, Side::Left))?;
+            write!(f, " {} ", operator.symbol())?;
+            return self.write_operand(f, rhs, self.needs_parens(rhs, operator, Side::Right));
+        }
+
+        match expr {
+            Expression::Number(n) => write!(f, "{}", n),
+            Expression::Bool(b) => write!(f, "{}", b),
+            Expression::String(text) => write_string(f, text),
+            Expression::Null => write!(f, "null"),
+            Expression::List(items) => {
+                write!(f, "[")?;
+                self.write_items(f, items)?;
+                write!(f, "]")
+            }
+            Expression::Variable(name) => write!(f, "{}", name),
+            Expression::Negate(operand) | Expression::Not(operand) => {
+                let symbol = if matches!(expr, Expression::Not(_)) {
+                    "!"
+                } else {
+                    "-"
+                };
+                write!(f, "{}", symbol)?;
+                let parens = self.precedence(operand) < self.config.prefix_precedence();
+                self.write_operand(f, operand, parens)
+            }
+            Expression::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                // The branches extend as far as possible, only a nested condition needs grouping
+                let parens = matches!(
+                    **condition,
+                    Expression::Conditional { .. }
+                        | Expression::Let { .. }
+                        | Expression::Assign { .. }
+                );
+                self.write_operand(f, condition, parens)?;
+                write!(f, " ? ")?;
+                self.write(f, then)?;
+                write!(f, " : ")?;
+                self.write(f, otherwise)
+            }
+            Expression::Call { name, args } => {
+                write!(f, "{}(", name)?;
+                self.write_items(f, args)?;
+                write!(f, ")")
+            }
+            // The value ends at the `;`, and the body extends as far as possible
+            Expression::Let { name, value, body } => {
+                write!(f, "let {} = ", name)?;
+                self.write(f, value)?;
+                write!(f, "; ")?;
+                self.write(f, body)
+            }
+            // Only parses back as a statement of a script, where the variable itself can
+            // be left out of the end
+            Expression::Assign { name, value, body } => {
+                write!(f, "{} = ", name)?;
+                self.write(f, value)?;
+                match &**body {
+                    Expression::Variable(result) if result == name => Ok(()),
+                    body => {
+                        write!(f, "; ")?;
+                        self.write(f, body)
+                    }
+                }
+            }
+            Expression::Error => write!(f, "<error>"),
+            _ => unreachable!("binary expressions are handled above"),
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let printer = Printer {
+            config: ParserConfig::default(),
+        };
+        printer.write(f, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::expr;
+    use expression_macro::expr;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_display_atoms() {
+        assert_eq!(expr!("42").to_string(), "42");
+        assert_eq!(expr!("2.5").to_string(), "2.5");
+        assert_eq!(expr!("x").to_string(), "x");
+        assert_eq!(expr!("max(x, 1, y)").to_string(), "max(x, 1, y)");
+        assert_eq!(expr!("f()").to_string(), "f()");
+    }
+
+    #[test]
+    fn test_display_minimal_parentheses() {
+        assert_eq!(expr!("((x + y)) * 2").to_string(), "(x + y) * 2");
+        assert_eq!(expr!("x + (y * 2)").to_string(), "x + y * 2");
+        assert_eq!(expr!("(x - y) - z").to_string(), "x - y - z");
+        assert_eq!(expr!("x - (y - z)").to_string(), "x - (y - z)");
+        assert_eq!(expr!("x / (y * z)").to_string(), "x / (y * z)");
+        assert_eq!(expr!("2 ^ 3 ^ 2").to_string(), "2 ^ 3 ^ 2");
+        assert_eq!(expr!("(2 ^ 3) ^ 2").to_string(), "(2 ^ 3) ^ 2");
+    }
+
+    #[test]
+    fn test_display_negation() {
+        assert_eq!(expr!("-x").to_string(), "-x");
+        assert_eq!(expr!("-(x + 1)").to_string(), "-(x + 1)");
+        assert_eq!(expr!("-x ^ 2").to_string(), "-x ^ 2");
+        assert_eq!(expr!("(-x) ^ 2").to_string(), "(-x) ^ 2");
+        assert_eq!(expr!("2 ^ -x").to_string(), "2 ^ -x");
+        assert_eq!(expr!("x * -(y * 2)").to_string(), "x * -(y * 2)");
+        assert_eq!(expr!("--x").to_string(), "--x");
+    }
+
+    #[test]
+    fn test_display_logic() {
+        assert_eq!(expr!("(x > 1) && (y <= 2)").to_string(), "x > 1 && y <= 2");
+        assert_eq!(expr!("(a || b) && c").to_string(), "(a || b) && c");
+        assert_eq!(expr!("(a == b) == c").to_string(), "a == b == c");
+        assert_eq!(expr!("a == (b == c)").to_string(), "a == (b == c)");
+        assert_eq!(expr!("!(x < 1)").to_string(), "!(x < 1)");
+        assert_eq!(expr!("a != !b").to_string(), "a != !b");
+        assert_eq!(expr!("if(c, a, b) + 1").to_string(), "(c ? a : b) + 1");
+        assert_eq!(
+            expr!("(a ? b : c) ? d : e").to_string(),
+            "(a ? b : c) ? d : e"
+        );
+        assert_eq!(
+            expr!("a ? b ? c : d : e ? f : g").to_string(),
+            "a ? b ? c : d : e ? f : g"
+        );
+    }
+
+    #[test]
+    fn test_display_literals() {
+        assert_eq!(
+            expr!(r#"tier == "gold" && !(flag || null == [1, "a\"b", [false]])"#).to_string(),
+            r#"tier == "gold" && !(flag || null == [1, "a\"b", [false]])"#
+        );
+        assert_eq!(
+            expr::string("tab\tline\n\\").to_string(),
+            r#""tab\tline\n\\""#
+        );
+        assert_eq!(expr!("-[x + 1]").to_string(), "-[x + 1]");
+    }
+
+    #[test]
+    fn test_display_let() {
+        assert_eq!(
+            expr!("let r = x^2 + y^2\nsqrt(r) / r").to_string(),
+            "let r = x ^ 2 + y ^ 2; sqrt(r) / r"
+        );
+        assert_eq!(
+            expr!("(let a = 1; a) * (c ? let b = a; b : 2)").to_string(),
+            "(let a = 1; a) * (c ? let b = a; b : 2)"
+        );
+        assert_eq!(
+            expr!("(let a = c; a) ? 1 : -(let b = 2; b)").to_string(),
+            "(let a = c; a) ? 1 : -(let b = 2; b)"
+        );
+    }
+
+    #[test]
+    fn test_display_assignments() {
+        assert_eq!(
+            expr!("v = v + a * dt\nx = x + v * dt;").to_string(),
+            "v = v + a * dt; x = x + v * dt"
+        );
+        assert_eq!(
+            expr!("let h = dt / 2; x = x + h; x * 2").to_string(),
+            "let h = dt / 2; x = x + h; x * 2"
+        );
+        assert_eq!(expr!("x = 1; y").to_string(), "x = 1; y");
+    }
+
+    #[test]
+    fn test_display_error_node() {
+        let e = expr::add(expr::variable("x"), Expression::Error);
+        assert_eq!(e.to_string(), "x + <error>");
+    }
+
+    fn arb_expression() -> impl Strategy<Value = Expression> {
+        let keyword = |name: &String| !matches!(name.as_str(), "true" | "null" | "if");
+        let leaf = prop_oneof![
+            (0.0..1e6f64).prop_map(Expression::Number),
+            any::<u32>().prop_map(|n| Expression::Number(n as f64)),
+            "[a-z_][a-z0-9_]{0,3}"
+                .prop_filter("keywords parse as literals", keyword)
+                .prop_map(Expression::Variable),
+            any::<bool>().prop_map(Expression::Bool),
+            "[a-z\"\\\\\n\t ]{0,4}".prop_map(Expression::String),
+            Just(Expression::Null),
+        ];
+
+        leaf.prop_recursive(6, 64, 4, move |inner| {
+            let pair = || (inner.clone(), inner.clone());
+            prop_oneof![
+                pair().prop_map(|(a, b)| expr::add(a, b)),
+                pair().prop_map(|(a, b)| expr::subtract(a, b)),
+                pair().prop_map(|(a, b)| expr::multiply(a, b)),
+                pair().prop_map(|(a, b)| expr::divide(a, b)),
+                pair().prop_map(|(a, b)| expr::power(a, b)),
+                inner.clone().prop_map(expr::negate),
+                pair().prop_map(|(a, b)| expr::less(a, b)),
+                pair().prop_map(|(a, b)| expr::greater_equal(a, b)),
+                pair().prop_map(|(a, b)| expr::not_equal(a, b)),
+                pair().prop_map(|(a, b)| expr::and(a, b)),
+                pair().prop_map(|(a, b)| expr::or(a, b)),
+                inner.clone().prop_map(expr::not),
+                (inner.clone(), inner.clone(), inner.clone())
+                    .prop_map(|(c, a, b)| expr::conditional(c, a, b)),
+                (
+                    "[a-z][a-z0-9]{0,3}".prop_filter("keywords are not functions", keyword),
+                    prop::collection::vec(inner.clone(), 0..4)
+                )
+                    .prop_map(|(name, args)| Expression::Call { name, args }),
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Expression::List),
+                (
+                    "[a-z_][a-z0-9_]{0,3}".prop_filter("keywords are not bindings", keyword),
+                    inner.clone(),
+                    inner.clone()
+                )
+                    .prop_map(|(name, value, body)| expr::let_in(&name, value, body)),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_display_round_trips(e in arb_expression()) {
+            prop_assert_eq!(Expression::parse(&e.to_string()), Ok(e));
+        }
+    }
+}
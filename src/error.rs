@@ -0,0 +1,333 @@
+use crate::expression::Expression;
+use crate::functions::Arity;
+use crate::parsing::{BinaryOperator, ParserConfig, Span};
+use crate::value::Type;
+use expression_grammar::syntax::Syntax;
+use std::error::Error;
+use std::fmt;
+
+pub use expression_grammar::error::ParseError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    UnknownVariable {
+        name: String,
+    },
+    /// The divisor evaluated to zero
+    DivisionByZero {
+        divisor: Expression,
+    },
+    UnknownFunction {
+        name: String,
+    },
+    ArityMismatch {
+        name: String,
+        expected: Arity,
+        found: usize,
+    },
+    /// A binary operator was applied to operand types it does not accept, such as `true + 1`.
+    ///
+    /// `expression` is the whole operation, see [`crate::value`] for the accepted types.
+    InvalidOperands {
+        operator: BinaryOperator,
+        lhs: Type,
+        rhs: Type,
+        expression: Expression,
+    },
+    /// `expression` evaluated to the wrong type, such as a string used as a condition
+    TypeMismatch {
+        expected: Type,
+        found: Type,
+        expression: Expression,
+    },
+    /// The tree contains [`Expression::Error`] nodes from a recovering parse
+    InvalidExpression,
+    /// An assignment to `name` inside an expression rather than a statement of the script,
+    /// which [`Expression::execute`] cannot store
+    MisplacedAssignment {
+        name: String,
+    },
+}
+
+/// An [`EvalError`] along with the subexpression it is about, from
+/// [`Expression::evaluate_located`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocatedError {
+    pub error: EvalError,
+    /// Child indices from that subexpression up to the root, innermost first
+    path: Vec<usize>,
+}
+
+impl LocatedError {
+    /// The same error raised at the `child`th subexpression of the current one
+    pub(crate) fn within(mut self, child: usize) -> Self {
+        self.path.push(child);
+        self
+    }
+
+    /// Finds where the error occurred in `source`, the text the expression was parsed from.
+    ///
+    /// Returns `None` if `source` does not parse, or does not have the shape of the
+    /// expression. Use [`LocatedError::span_in_with_config`] for expressions parsed with
+    /// another [`ParserConfig`].
+    pub fn span_in(&self, source: &str) -> Option<Span> {
+        self.span_in_with_config(source, ParserConfig::default())
+    }
+
+    pub fn span_in_with_config(&self, source: &str, config: ParserConfig) -> Option<Span> {
+        let syntax = expression_grammar::parse_with_config(source, config).ok()?;
+        let node = self
+            .path
+            .iter()
+            .rev()
+            .try_fold(&syntax, |node: &Syntax, &i| node.children().get(i).copied())?;
+        Some(node.span)
+    }
+}
+
+impl From<EvalError> for LocatedError {
+    /// The error raised at the expression being evaluated
+    fn from(error: EvalError) -> Self {
+        LocatedError {
+            error,
+            path: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::UnknownVariable { name } => write!(f, "Variable '{}' not found", name),
+            EvalError::DivisionByZero { .. } => write!(f, "Division by 0"),
+            EvalError::UnknownFunction { name } => write!(f, "Unknown function '{}'", name),
+            EvalError::ArityMismatch {
+                name,
+                expected,
+                found,
+            } => write!(f, "Function '{}' expects {}, got {}", name, expected, found),
+            EvalError::InvalidOperands {
+                operator, lhs, rhs, ..
+            } => {
+                let verb = match operator {
+                    BinaryOperator::Add => "add",
+                    BinaryOperator::Subtract => "subtract",
+                    BinaryOperator::Multiply => "multiply",
+                    BinaryOperator::Divide => "divide",
+                    BinaryOperator::Power => "exponentiate",
+                    _ => "compare",
+                };
+                write!(f, "Cannot {} {} and {}", verb, lhs, rhs)
+            }
+            EvalError::TypeMismatch {
+                expected, found, ..
+            } => write!(f, "Expected {}, found {}", expected, found),
+            EvalError::InvalidExpression => {
+                write!(f, "Cannot evaluate an expression containing syntax errors")
+            }
+            EvalError::MisplacedAssignment { name } => {
+                write!(
+                    f,
+                    "Assignment to '{}' is not a statement of the script",
+                    name
+                )
+            }
+        }
+    }
+}
+
+impl Error for EvalError {}
+
+impl fmt::Display for LocatedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl Error for LocatedError {}
+
+/// A problem found by [`Schema::check`](crate::typecheck::Schema::check)
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckError {
+    Parse(ParseError),
+    /// An error evaluation would report, located at `span` in the checked source
+    Type {
+        error: EvalError,
+        span: Span,
+    },
+}
+
+impl CheckError {
+    pub fn span(&self) -> Span {
+        match self {
+            CheckError::Parse(error) => error.span(),
+            CheckError::Type { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::Parse(error) => write!(f, "{}", error),
+            CheckError::Type { error, span } => write!(f, "{} at {}", error, span),
+        }
+    }
+}
+
+impl Error for CheckError {}
+
+/// Failure to load a serialized [`Program`](crate::bytecode::Program)
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// The input does not start with the bytecode header
+    InvalidHeader,
+    UnsupportedVersion {
+        version: u8,
+    },
+    UnexpectedEnd,
+    InvalidOpcode {
+        opcode: u8,
+        offset: usize,
+    },
+    InvalidName,
+    /// The instructions decode but would underflow the stack or read a missing slot
+    InvalidProgram,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidHeader => write!(f, "Not an expression bytecode file"),
+            DecodeError::UnsupportedVersion { version } => {
+                write!(f, "Unsupported bytecode version {}", version)
+            }
+            DecodeError::UnexpectedEnd => write!(f, "Unexpected end of bytecode"),
+            DecodeError::InvalidOpcode { opcode, offset } => {
+                write!(f, "Invalid opcode {:#04x} at byte {}", opcode, offset)
+            }
+            DecodeError::InvalidName => write!(f, "Invalid UTF-8 in a variable or function name"),
+            DecodeError::InvalidProgram => write!(f, "Malformed instruction sequence"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::expr;
+    use crate::functions::FunctionRegistry;
+    use crate::parsing::Associativity;
+    use crate::value::Value;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_eval_error_display() {
+        let error = EvalError::UnknownVariable {
+            name: "z".to_string(),
+        };
+        assert_eq!(error.to_string(), "Variable 'z' not found");
+
+        let error = EvalError::DivisionByZero {
+            divisor: expr::number(0.0),
+        };
+        assert_eq!(error.to_string(), "Division by 0");
+
+        let error = EvalError::ArityMismatch {
+            name: "sin".to_string(),
+            expected: Arity::Exact(1),
+            found: 2,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Function 'sin' expects 1 argument, got 2"
+        );
+
+        let error = EvalError::InvalidOperands {
+            operator: BinaryOperator::Power,
+            lhs: Type::String,
+            rhs: Type::Integer,
+            expression: expr::power(expr::string("a"), expr::variable("n")),
+        };
+        assert_eq!(error.to_string(), "Cannot exponentiate String and Integer");
+    }
+
+    #[test]
+    fn test_located_error_span() {
+        let vars = HashMap::from([("x".to_string(), 1.0)]);
+        let locate = |source: &str| {
+            Expression::parse(source)
+                .unwrap()
+                .evaluate_located(&vars, &FunctionRegistry::new())
+                .unwrap_err()
+        };
+
+        let source = "1 / (x - x) + y";
+        let error = locate(source);
+        assert_eq!(
+            error.error,
+            EvalError::DivisionByZero {
+                divisor: expr::subtract(expr::variable("x"), expr::variable("x")),
+            }
+        );
+        assert_eq!(error.span_in(source), Some(Span::new(4, 11)));
+        assert_eq!(
+            locate("(x + y) * 2").span_in("(x + y) * 2"),
+            Some(Span::new(5, 6))
+        );
+
+        // The error is located where it was raised, not at the first equal subexpression
+        assert_eq!(
+            locate("0 + 1 / 0").span_in("0 + 1 / 0"),
+            Some(Span::new(8, 9))
+        );
+        let source = "x - 1 + x / (x - 1)";
+        assert_eq!(locate(source).span_in(source), Some(Span::new(12, 19)));
+        let source = "[x, x > 0 ? sqrt(x, y) : 0]";
+        assert_eq!(locate(source).span_in(source), Some(Span::new(12, 22)));
+        assert_eq!(locate("f(y)").span_in("f(y)"), Some(Span::new(0, 4)));
+
+        // Sources that do not parse to the expression have no span
+        let error = locate("y + 1");
+        assert_eq!(error.span_in("y +"), None);
+        assert_eq!(error.span_in("y"), None);
+    }
+
+    #[test]
+    fn test_located_error_span_with_config() {
+        // With `+` binding tighter than `*`: 2 * 3 + s => 2 * (3 + s)
+        let config =
+            ParserConfig::default().with_operator(BinaryOperator::Add, 30, Associativity::Left);
+        let source = "2 * 3 + s";
+        let vars = HashMap::from([("s".to_string(), Value::from("a"))]);
+        let error = Expression::parse_with_config(source, config.clone())
+            .unwrap()
+            .evaluate_located(&vars, &FunctionRegistry::new())
+            .unwrap_err();
+
+        assert_eq!(
+            error.span_in_with_config(source, config),
+            Some(Span::new(4, 9))
+        );
+        // Parsed with the default config, the source is (2 * 3) + s and the path leads to `s`
+        assert_eq!(error.span_in(source), Some(Span::new(8, 9)));
+    }
+
+    #[test]
+    fn test_errors_are_std_errors() {
+        let error: Box<dyn Error> = Box::new(ParseError::UnexpectedEnd {
+            span: Span::new(0, 0),
+        });
+        assert_eq!(error.to_string(), "Unexpected end of input at 0..0");
+
+        let error: Box<dyn Error> = Box::new(CheckError::Type {
+            error: EvalError::UnknownVariable {
+                name: "y".to_string(),
+            },
+            span: Span::new(4, 5),
+        });
+        assert_eq!(error.to_string(), "Variable 'y' not found at 4..5");
+    }
+}
@@ -1,43 +1,565 @@
+use crate::context::{Context, ContextMut};
+use crate::error::{EvalError, LocatedError, ParseError};
+use crate::functions::FunctionRegistry;
 use crate::parsing::*;
-use std::collections::HashMap;
+use crate::value::{Type, Value};
+use expression_grammar::syntax::{Syntax, SyntaxKind};
+use std::collections::BTreeSet;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     Number(f64),
+    Bool(bool),
+    String(String),
+    Null,
+    List(Vec<Expression>),
     Variable(String),
     Add(Box<Expression>, Box<Expression>),
     Subtract(Box<Expression>, Box<Expression>),
     Multiply(Box<Expression>, Box<Expression>),
     Divide(Box<Expression>, Box<Expression>),
     Power(Box<Expression>, Box<Expression>),
+    Negate(Box<Expression>),
+    Less(Box<Expression>, Box<Expression>),
+    LessEqual(Box<Expression>, Box<Expression>),
+    Greater(Box<Expression>, Box<Expression>),
+    GreaterEqual(Box<Expression>, Box<Expression>),
+    Equal(Box<Expression>, Box<Expression>),
+    NotEqual(Box<Expression>, Box<Expression>),
+    /// Only evaluates the right operand if the left is true
+    And(Box<Expression>, Box<Expression>),
+    /// Only evaluates the right operand if the left is false
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    /// Only evaluates the branch selected by `condition`
+    Conditional {
+        condition: Box<Expression>,
+        then: Box<Expression>,
+        otherwise: Box<Expression>,
+    },
+    Call {
+        name: String,
+        args: Vec<Expression>,
+    },
+    /// Evaluates `value` once and makes it available as the variable `name` within `body`,
+    /// shadowing any other variable of that name
+    Let {
+        name: String,
+        value: Box<Expression>,
+        body: Box<Expression>,
+    },
+    /// The statement `name = value; body` of a script. Evaluating it binds `name` just like
+    /// [`Expression::Let`], while [`Expression::execute`] also stores the value in the variables.
+    Assign {
+        name: String,
+        value: Box<Expression>,
+        body: Box<Expression>,
+    },
+    /// Placeholder for input that failed to parse, see [`Expression::parse_recovering`]
+    Error,
+}
+
+/// The numeric backends have no booleans: they compute comparisons and logical operators
+/// as `1` or `0`, the same numbers [`Expression::evaluate`] gives for a `Bool` result
+pub(crate) fn truth(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+/// Whether a numeric backend's `f64` stands for a number or a `Bool`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Kind {
+    Number,
+    Truth,
+    /// A conditional with a branch of each
+    Mixed,
+}
+
+impl Kind {
+    /// The kind of a conditional with branches of kinds `self` and `other`
+    pub(crate) fn merge(self, other: Kind) -> Kind {
+        if self == other { self } else { Kind::Mixed }
+    }
+}
+
+/// Checks that `==` or `!=` compares like with like in a numeric backend.
+///
+/// [`Value::equals`] finds a `Bool` unequal to every number, which a backend holding both
+/// as `f64` cannot tell from `1 == 1`, so such comparisons are rejected when lowering.
+pub(crate) fn check_equality(
+    (a, a_kind): (&Expression, Kind),
+    (b, b_kind): (&Expression, Kind),
+) -> Result<(), EvalError> {
+    let type_of = |kind| match kind {
+        Kind::Truth => Type::Bool,
+        _ => Type::Number,
+    };
+    let opposite = |t| match t {
+        Type::Bool => Type::Number,
+        _ => Type::Bool,
+    };
+    let (expected, expression) = match (a_kind, b_kind) {
+        (Kind::Number, Kind::Number) | (Kind::Truth, Kind::Truth) => return Ok(()),
+        (Kind::Mixed, other) => (type_of(other), a),
+        (other, _) => (type_of(other), b),
+    };
+    Err(EvalError::TypeMismatch {
+        expected,
+        found: opposite(expected),
+        expression: expression.clone(),
+    })
+}
+
+/// The error for a string, null or list literal reaching a numeric backend
+pub(crate) fn non_numeric(literal: &Expression) -> EvalError {
+    let found = match literal {
+        Expression::String(_) => Type::String,
+        Expression::Null => Type::Null,
+        _ => Type::List,
+    };
+    EvalError::TypeMismatch {
+        expected: Type::Number,
+        found,
+        expression: literal.clone(),
+    }
 }
 
 impl Expression {
-    pub fn evaluate(&self, variables: &HashMap<String, f64>) -> Result<f64, String> {
+    /// Evaluates using `variables` to look up every variable, see [`Context`]
+    pub fn evaluate<C: Context + ?Sized>(&self, variables: &C) -> Result<f64, EvalError> {
+        self.evaluate_with(variables, &FunctionRegistry::new())
+    }
+
+    /// Evaluates with user registered functions available alongside the built-ins.
+    ///
+    /// The result must be a number, or a `Bool` which gives `1` or `0`. Use
+    /// [`Expression::evaluate_value_with`] for formulas producing other types.
+    pub fn evaluate_with<C: Context + ?Sized>(
+        &self,
+        variables: &C,
+        functions: &FunctionRegistry,
+    ) -> Result<f64, EvalError> {
+        match self.evaluate_value_with(variables, functions)? {
+            Value::Bool(b) => Ok(truth(b)),
+            value => value.as_number().ok_or_else(|| EvalError::TypeMismatch {
+                expected: Type::Number,
+                found: value.type_of(),
+                expression: self.clone(),
+            }),
+        }
+    }
+
+    /// Evaluates to a typed [`Value`], following the coercion rules in [`crate::value`]
+    pub fn evaluate_value<C: Context + ?Sized>(&self, variables: &C) -> Result<Value, EvalError> {
+        self.evaluate_value_with(variables, &FunctionRegistry::new())
+    }
+
+    pub fn evaluate_value_with<C: Context + ?Sized>(
+        &self,
+        variables: &C,
+        functions: &FunctionRegistry,
+    ) -> Result<Value, EvalError> {
+        self.evaluate_located(variables, functions)
+            .map_err(|located| located.error)
+    }
+
+    /// Evaluates like [`Expression::evaluate_value_with`], also recording which
+    /// subexpression an error is about, see [`LocatedError::span_in`]
+    pub fn evaluate_located<C: Context + ?Sized>(
+        &self,
+        variables: &C,
+        functions: &FunctionRegistry,
+    ) -> Result<Value, LocatedError> {
+        // Operands are evaluated left to right, so an error in the left one is reported first.
+        // `child` is the operand's index in the syntax tree, added to the error's path
+        let eval = |child: usize, e: &Expression| {
+            e.evaluate_located(variables, functions)
+                .map_err(|error| error.within(child))
+        };
+        let condition = |child: usize, e: &Expression| {
+            e.evaluate_condition(variables, functions)
+                .map_err(|error| error.within(child))
+        };
         match self {
-            Expression::Number(n) => Ok(*n),
+            Expression::Number(n) => Ok(Value::Number(*n)),
+            Expression::Bool(b) => Ok(Value::Bool(*b)),
+            Expression::String(text) => Ok(Value::String(text.clone())),
+            Expression::Null => Ok(Value::Null),
+            Expression::List(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| eval(i, item))
+                .collect::<Result<_, _>>()
+                .map(Value::List),
             Expression::Variable(name) => variables
-                .get(name)
-                .copied()
-                .ok_or(format!("Variable '{}' not found", name)),
-            Expression::Add(a, b) => Ok(a.evaluate(variables)? + b.evaluate(variables)?),
-            Expression::Subtract(a, b) => Ok(a.evaluate(variables)? - b.evaluate(variables)?),
-            Expression::Multiply(a, b) => Ok(a.evaluate(variables)? * b.evaluate(variables)?),
+                .value(name)
+                .ok_or_else(|| EvalError::UnknownVariable { name: name.clone() }.into()),
+            Expression::Add(a, b) => self.arithmetic(BinaryOperator::Add, eval(0, a)?, eval(1, b)?),
+            Expression::Subtract(a, b) => {
+                self.arithmetic(BinaryOperator::Subtract, eval(0, a)?, eval(1, b)?)
+            }
+            Expression::Multiply(a, b) => {
+                self.arithmetic(BinaryOperator::Multiply, eval(0, a)?, eval(1, b)?)
+            }
             Expression::Divide(a, b) => {
-                let denominator = b.evaluate(variables)?;
-                if denominator == 0.0 {
-                    return Err("Division by 0".to_string());
+                let (numerator, denominator) = (eval(0, a)?, eval(1, b)?);
+                if numerator.as_number().is_some() && denominator.as_number() == Some(0.0) {
+                    let error = EvalError::DivisionByZero {
+                        divisor: (**b).clone(),
+                    };
+                    return Err(LocatedError::from(error).within(1));
+                }
+                self.arithmetic(BinaryOperator::Divide, numerator, denominator)
+            }
+            Expression::Power(base, exponent) => {
+                self.arithmetic(BinaryOperator::Power, eval(0, base)?, eval(1, exponent)?)
+            }
+            Expression::Negate(operand) => match eval(0, operand)? {
+                Value::Number(n) => Ok(Value::Number(-n)),
+                Value::Integer(i) => Ok(i
+                    .checked_neg()
+                    .map_or(Value::Number(-(i as f64)), Value::Integer)),
+                value => {
+                    let error = EvalError::TypeMismatch {
+                        expected: Type::Number,
+                        found: value.type_of(),
+                        expression: (**operand).clone(),
+                    };
+                    Err(LocatedError::from(error).within(0))
                 }
-                Ok(a.evaluate(variables)? / denominator)
+            },
+            Expression::Less(a, b) => self.compare(BinaryOperator::Less, eval(0, a)?, eval(1, b)?),
+            Expression::LessEqual(a, b) => {
+                self.compare(BinaryOperator::LessEqual, eval(0, a)?, eval(1, b)?)
+            }
+            Expression::Greater(a, b) => {
+                self.compare(BinaryOperator::Greater, eval(0, a)?, eval(1, b)?)
+            }
+            Expression::GreaterEqual(a, b) => {
+                self.compare(BinaryOperator::GreaterEqual, eval(0, a)?, eval(1, b)?)
+            }
+            Expression::Equal(a, b) => Ok(Value::Bool(eval(0, a)?.equals(&eval(1, b)?))),
+            Expression::NotEqual(a, b) => Ok(Value::Bool(!eval(0, a)?.equals(&eval(1, b)?))),
+            Expression::And(a, b) => Ok(Value::Bool(condition(0, a)? && condition(1, b)?)),
+            Expression::Or(a, b) => Ok(Value::Bool(condition(0, a)? || condition(1, b)?)),
+            Expression::Not(operand) => Ok(Value::Bool(!condition(0, operand)?)),
+            Expression::Conditional {
+                condition: test,
+                then,
+                otherwise,
+            } => {
+                if condition(0, test)? {
+                    eval(1, then)
+                } else {
+                    eval(2, otherwise)
+                }
+            }
+            Expression::Call { name, args } => {
+                let function = functions
+                    .resolve(name)
+                    .ok_or_else(|| EvalError::UnknownFunction { name: name.clone() })?;
+                if !function.arity().accepts(args.len()) {
+                    return Err(EvalError::ArityMismatch {
+                        name: name.clone(),
+                        expected: function.arity(),
+                        found: args.len(),
+                    }
+                    .into());
+                }
+
+                let args = args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, arg)| {
+                        let value = eval(i, arg)?;
+                        value.as_number().ok_or_else(|| {
+                            let error = EvalError::TypeMismatch {
+                                expected: Type::Number,
+                                found: value.type_of(),
+                                expression: arg.clone(),
+                            };
+                            LocatedError::from(error).within(i)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Number(function.call(&args)))
+            }
+            Expression::Let { name, value, body } | Expression::Assign { name, value, body } => {
+                let binding = [(name.as_str(), eval(0, value)?)];
+                // Scopes nest without limit, so the chain is passed on as a trait object
+                let scope: &dyn Context = &binding.chain(variables);
+                body.evaluate_located(scope, functions)
+                    .map_err(|error| error.within(1))
             }
-            Expression::Power(base, exponent) => Ok(base
-                .evaluate(variables)?
-                .powf(exponent.evaluate(variables)?)),
+            Expression::Error => Err(EvalError::InvalidExpression.into()),
         }
     }
 
-    pub fn parse(input: &str) -> Result<Expression, String> {
-        Parser::new(tokenize(input)?).parse_expression()
+    /// Runs a script, storing the values of its assignments in `variables`.
+    ///
+    /// Returns the names of the variables assigned. The whole script is evaluated before
+    /// anything is stored, so an error leaves `variables` unchanged unless it is a value
+    /// `variables` cannot hold. Assigning to a name bound by an enclosing `let` only changes
+    /// the binding.
+    ///
+    /// Assignments must be statements of the script, as the parser produces them. One
+    /// nested in an expression, as [`expr::assign`] can build, fails with
+    /// [`EvalError::MisplacedAssignment`].
+    pub fn execute<C: ContextMut + ?Sized>(
+        &self,
+        variables: &mut C,
+    ) -> Result<BTreeSet<String>, EvalError> {
+        self.execute_with(variables, &FunctionRegistry::new())
+    }
+
+    pub fn execute_with<C: ContextMut + ?Sized>(
+        &self,
+        variables: &mut C,
+        functions: &FunctionRegistry,
+    ) -> Result<BTreeSet<String>, EvalError> {
+        let mut assigned = Vec::new();
+        self.run(&*variables, functions, &mut Vec::new(), &mut assigned)?;
+
+        let mut names = BTreeSet::new();
+        for (name, value, expression) in assigned {
+            variables
+                .set(name, value)
+                .map_err(|value| EvalError::TypeMismatch {
+                    expected: Type::Number,
+                    found: value.type_of(),
+                    expression: expression.clone(),
+                })?;
+            names.insert(name.to_string());
+        }
+        Ok(names)
+    }
+
+    /// Evaluates the statements of a script, collecting the last value assigned to each
+    /// variable not bound by a `let`, along with the expression it came from
+    fn run<'e, C: Context + ?Sized>(
+        &'e self,
+        variables: &C,
+        functions: &FunctionRegistry,
+        bound: &mut Vec<&'e str>,
+        assigned: &mut Vec<(&'e str, Value, &'e Expression)>,
+    ) -> Result<(), EvalError> {
+        let (name, value, body) = match self {
+            Expression::Let { name, value, body } | Expression::Assign { name, value, body } => {
+                (name.as_str(), value, body)
+            }
+            _ => {
+                self.check_statements()?;
+                return self.evaluate_value_with(variables, functions).map(drop);
+            }
+        };
+
+        value.check_statements()?;
+        let result = value.evaluate_value_with(variables, functions)?;
+        let is_let = matches!(self, Expression::Let { .. });
+        if is_let {
+            bound.push(name);
+        } else if !bound.contains(&name) {
+            assigned.retain(|&(assigned, ..)| assigned != name);
+            assigned.push((name, result.clone(), value));
+        }
+
+        let binding = [(name, result)];
+        let scope: &dyn Context = &binding.chain(variables);
+        body.run(scope, functions, bound, assigned)?;
+        if is_let {
+            bound.pop();
+        }
+        Ok(())
+    }
+
+    fn evaluate_condition<C: Context + ?Sized>(
+        &self,
+        variables: &C,
+        functions: &FunctionRegistry,
+    ) -> Result<bool, LocatedError> {
+        let value = self.evaluate_located(variables, functions)?;
+        value.as_condition().ok_or_else(|| {
+            EvalError::TypeMismatch {
+                expected: Type::Bool,
+                found: value.type_of(),
+                expression: self.clone(),
+            }
+            .into()
+        })
+    }
+
+    fn arithmetic(
+        &self,
+        operator: BinaryOperator,
+        a: Value,
+        b: Value,
+    ) -> Result<Value, LocatedError> {
+        Value::arithmetic(operator, &a, &b).ok_or_else(|| self.invalid_operands(operator, &a, &b))
+    }
+
+    fn compare(&self, operator: BinaryOperator, a: Value, b: Value) -> Result<Value, LocatedError> {
+        Value::compare(operator, &a, &b)
+            .map(Value::Bool)
+            .ok_or_else(|| self.invalid_operands(operator, &a, &b))
+    }
+
+    fn invalid_operands(&self, operator: BinaryOperator, a: &Value, b: &Value) -> LocatedError {
+        EvalError::InvalidOperands {
+            operator,
+            lhs: a.type_of(),
+            rhs: b.type_of(),
+            expression: self.clone(),
+        }
+        .into()
+    }
+
+    pub fn parse(input: &str) -> Result<Expression, ParseError> {
+        Parser::new(tokenize(input)?).parse()
+    }
+
+    pub fn parse_with_config(input: &str, config: ParserConfig) -> Result<Expression, ParseError> {
+        Parser::with_config(tokenize(input)?, config).parse()
+    }
+
+    /// Parses `input` reporting every syntax error, for editors that re-parse on each keystroke.
+    ///
+    /// Always produces a tree; the parts that could not be parsed are [`Expression::Error`] nodes.
+    pub fn parse_recovering(input: &str) -> (Expression, Vec<ParseError>) {
+        let (syntax, errors) = expression_grammar::parse_recovering(input);
+        (syntax.into(), errors)
+    }
+
+    /// Parses the leading expression of `input`, returning the tokens after it
+    pub fn parse_prefix(input: &str) -> Result<(Expression, Vec<Token>), ParseError> {
+        Parser::new(tokenize(input)?).parse_prefix()
+    }
+}
+
+/// Drops the spans from a tree built by the shared grammar
+impl From<Syntax> for Expression {
+    fn from(syntax: Syntax) -> Self {
+        match syntax.kind {
+            SyntaxKind::Number(n) => Expression::Number(n),
+            SyntaxKind::Bool(b) => Expression::Bool(b),
+            SyntaxKind::String(text) => Expression::String(text),
+            SyntaxKind::Null => Expression::Null,
+            SyntaxKind::List(items) => {
+                Expression::List(items.into_iter().map(Expression::from).collect())
+            }
+            SyntaxKind::Variable(name) => Expression::Variable(name),
+            SyntaxKind::Binary { operator, lhs, rhs } => {
+                let (lhs, rhs) = (Box::new((*lhs).into()), Box::new((*rhs).into()));
+                match operator {
+                    BinaryOperator::Add => Expression::Add(lhs, rhs),
+                    BinaryOperator::Subtract => Expression::Subtract(lhs, rhs),
+                    BinaryOperator::Multiply => Expression::Multiply(lhs, rhs),
+                    BinaryOperator::Divide => Expression::Divide(lhs, rhs),
+                    BinaryOperator::Power => Expression::Power(lhs, rhs),
+                    BinaryOperator::Less => Expression::Less(lhs, rhs),
+                    BinaryOperator::LessEqual => Expression::LessEqual(lhs, rhs),
+                    BinaryOperator::Greater => Expression::Greater(lhs, rhs),
+                    BinaryOperator::GreaterEqual => Expression::GreaterEqual(lhs, rhs),
+                    BinaryOperator::Equal => Expression::Equal(lhs, rhs),
+                    BinaryOperator::NotEqual => Expression::NotEqual(lhs, rhs),
+                    BinaryOperator::And => Expression::And(lhs, rhs),
+                    BinaryOperator::Or => Expression::Or(lhs, rhs),
+                }
+            }
+            SyntaxKind::Negate(operand) => Expression::Negate(Box::new((*operand).into())),
+            SyntaxKind::Not(operand) => Expression::Not(Box::new((*operand).into())),
+            SyntaxKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => Expression::Conditional {
+                condition: Box::new((*condition).into()),
+                then: Box::new((*then).into()),
+                otherwise: Box::new((*otherwise).into()),
+            },
+            SyntaxKind::Call { name, args } => Expression::Call {
+                name,
+                args: args.into_iter().map(Expression::from).collect(),
+            },
+            SyntaxKind::Let { name, value, body } => Expression::Let {
+                name,
+                value: Box::new((*value).into()),
+                body: Box::new((*body).into()),
+            },
+            SyntaxKind::Assign { name, value, body } => Expression::Assign {
+                name,
+                value: Box::new((*value).into()),
+                body: Box::new((*body).into()),
+            },
+            // Rust code only means something to the `expr!` macro
+            SyntaxKind::Interpolation(_) | SyntaxKind::Error => Expression::Error,
+        }
+    }
+}
+
+impl Expression {
+    /// The direct subexpressions, in the order of [`Syntax::children`]
+    fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Number(_)
+            | Expression::Bool(_)
+            | Expression::String(_)
+            | Expression::Null
+            | Expression::Variable(_)
+            | Expression::Error => Vec::new(),
+            Expression::List(items) | Expression::Call { args: items, .. } => {
+                items.iter().collect()
+            }
+            Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Divide(a, b)
+            | Expression::Power(a, b)
+            | Expression::Less(a, b)
+            | Expression::LessEqual(a, b)
+            | Expression::Greater(a, b)
+            | Expression::GreaterEqual(a, b)
+            | Expression::Equal(a, b)
+            | Expression::NotEqual(a, b)
+            | Expression::And(a, b)
+            | Expression::Or(a, b)
+            | Expression::Let {
+                value: a, body: b, ..
+            }
+            | Expression::Assign {
+                value: a, body: b, ..
+            } => vec![a, b],
+            Expression::Negate(a) | Expression::Not(a) => vec![a],
+            Expression::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => vec![condition, then, otherwise],
+        }
+    }
+
+    /// Fails if an expression that is not a statement of a script contains an assignment
+    fn check_statements(&self) -> Result<(), EvalError> {
+        let mut pending = vec![self];
+        while let Some(expr) = pending.pop() {
+            if let Expression::Assign { name, .. } = expr {
+                return Err(EvalError::MisplacedAssignment { name: name.clone() });
+            }
+            pending.extend(expr.children().into_iter().rev());
+        }
+        Ok(())
+    }
+}
+
+/// Lets `expr!` splice numbers into a tree with `#{...}`
+impl From<f64> for Expression {
+    fn from(n: f64) -> Self {
+        Expression::Number(n)
+    }
+}
+
+/// Lets `expr!` splice a borrowed tree with `#{&e}`, leaving the original usable
+impl From<&Expression> for Expression {
+    fn from(expr: &Expression) -> Self {
+        expr.clone()
     }
 }
 
@@ -48,6 +570,22 @@ pub mod expr {
         Expression::Number(n)
     }
 
+    pub fn boolean(b: bool) -> Expression {
+        Expression::Bool(b)
+    }
+
+    pub fn string(text: &str) -> Expression {
+        Expression::String(text.to_string())
+    }
+
+    pub fn null() -> Expression {
+        Expression::Null
+    }
+
+    pub fn list(items: Vec<Expression>) -> Expression {
+        Expression::List(items)
+    }
+
     pub fn variable(name: &str) -> Expression {
         Expression::Variable(name.to_string())
     }
@@ -71,11 +609,89 @@ pub mod expr {
     pub fn power(base: Expression, power: Expression) -> Expression {
         Expression::Power(Box::new(base), Box::new(power))
     }
+
+    pub fn negate(operand: Expression) -> Expression {
+        Expression::Negate(Box::new(operand))
+    }
+
+    pub fn less(a: Expression, b: Expression) -> Expression {
+        Expression::Less(Box::new(a), Box::new(b))
+    }
+
+    pub fn less_equal(a: Expression, b: Expression) -> Expression {
+        Expression::LessEqual(Box::new(a), Box::new(b))
+    }
+
+    pub fn greater(a: Expression, b: Expression) -> Expression {
+        Expression::Greater(Box::new(a), Box::new(b))
+    }
+
+    pub fn greater_equal(a: Expression, b: Expression) -> Expression {
+        Expression::GreaterEqual(Box::new(a), Box::new(b))
+    }
+
+    pub fn equal(a: Expression, b: Expression) -> Expression {
+        Expression::Equal(Box::new(a), Box::new(b))
+    }
+
+    pub fn not_equal(a: Expression, b: Expression) -> Expression {
+        Expression::NotEqual(Box::new(a), Box::new(b))
+    }
+
+    pub fn and(a: Expression, b: Expression) -> Expression {
+        Expression::And(Box::new(a), Box::new(b))
+    }
+
+    pub fn or(a: Expression, b: Expression) -> Expression {
+        Expression::Or(Box::new(a), Box::new(b))
+    }
+
+    pub fn not(operand: Expression) -> Expression {
+        Expression::Not(Box::new(operand))
+    }
+
+    pub fn conditional(
+        condition: Expression,
+        then: Expression,
+        otherwise: Expression,
+    ) -> Expression {
+        Expression::Conditional {
+            condition: Box::new(condition),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise),
+        }
+    }
+
+    pub fn call(name: &str, args: Vec<Expression>) -> Expression {
+        Expression::Call {
+            name: name.to_string(),
+            args,
+        }
+    }
+
+    /// `let name = value; body`
+    pub fn let_in(name: &str, value: Expression, body: Expression) -> Expression {
+        Expression::Let {
+            name: name.to_string(),
+            value: Box::new(value),
+            body: Box::new(body),
+        }
+    }
+
+    /// `name = value; body`
+    pub fn assign(name: &str, value: Expression, body: Expression) -> Expression {
+        Expression::Assign {
+            name: name.to_string(),
+            value: Box::new(value),
+            body: Box::new(body),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::functions::Arity;
     use expression_macro::expr;
     use std::collections::HashMap;
 
@@ -129,16 +745,55 @@ mod tests {
         assert_eq!(expr!("2 * 3 ^ 2").evaluate(&vars).unwrap(), 18.0);
     }
 
+    #[test]
+    fn test_operator_associativity() {
+        let vars = create_vars();
+
+        assert_eq!(expr!("2 ^ 3 ^ 2").evaluate(&vars).unwrap(), 512.0);
+        assert_eq!(expr!("10 - 4 - 3").evaluate(&vars).unwrap(), 3.0);
+        assert_eq!(expr!("16 / 4 / 2").evaluate(&vars).unwrap(), 2.0);
+        assert_eq!(
+            Expression::parse("2 ^ 3 ^ 2").unwrap(),
+            expr!("2 ^ (3 ^ 2)")
+        );
+    }
+
+    #[test]
+    fn test_parse_with_config() {
+        let vars = create_vars();
+        let config = ParserConfig::default()
+            .with_operator(BinaryOperator::Power, 40, Associativity::Left)
+            .with_prefix_precedence(50);
+
+        let expr = Expression::parse_with_config("2 ^ 3 ^ 2", config.clone()).unwrap();
+        assert_eq!(expr.evaluate(&vars).unwrap(), 64.0);
+
+        let expr = Expression::parse_with_config("-x ^ 2", config).unwrap();
+        assert_eq!(expr.evaluate(&vars).unwrap(), 4.0);
+    }
+
     #[test]
     fn test_error_handling() {
         let vars = create_vars();
-        let empty_vars = HashMap::new();
+        let empty_vars: HashMap<String, f64> = HashMap::new();
 
         // Division by zero
         assert!(expr!("x / 0").evaluate(&vars).is_err());
+        assert_eq!(
+            expr!("x / (y - 3)").evaluate(&vars),
+            Err(EvalError::DivisionByZero {
+                divisor: expr::subtract(expr::variable("y"), expr::number(3.0))
+            })
+        );
 
         // Unknown variable
         assert!(expr!("z + 1").evaluate(&vars).is_err());
+        assert_eq!(
+            expr!("z + 1").evaluate(&vars),
+            Err(EvalError::UnknownVariable {
+                name: "z".to_string()
+            })
+        );
 
         // Missing variable
         assert!(expr!("x + y").evaluate(&empty_vars).is_err());
@@ -161,6 +816,209 @@ mod tests {
         assert_eq!(expr!("(x + y) * (x - y)").evaluate(&vars).unwrap(), -5.0);
     }
 
+    #[test]
+    fn test_unary_operators() {
+        let vars = create_vars();
+
+        assert_eq!(expr!("-x").evaluate(&vars).unwrap(), -2.0);
+        assert_eq!(expr!("+x").evaluate(&vars).unwrap(), 2.0);
+        assert_eq!(expr!("-(x + y)").evaluate(&vars).unwrap(), -5.0);
+        assert_eq!(expr!("2 * -3").evaluate(&vars).unwrap(), -6.0);
+        assert_eq!(expr!("--x").evaluate(&vars).unwrap(), 2.0);
+        assert_eq!(expr!("x - -y").evaluate(&vars).unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_unary_precedence() {
+        let vars = create_vars();
+
+        assert_eq!(expr!("-2 ^ 2").evaluate(&vars).unwrap(), -4.0);
+        assert_eq!(expr!("(-2) ^ 2").evaluate(&vars).unwrap(), 4.0);
+        assert_eq!(expr!("2 ^ -1").evaluate(&vars).unwrap(), 0.5);
+        assert_eq!(expr!("-x * y").evaluate(&vars).unwrap(), -6.0);
+        assert_eq!(
+            Expression::parse("-x ^ 2")
+                .unwrap()
+                .evaluate(&vars)
+                .unwrap(),
+            -4.0
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_trailing_input() {
+        assert!(Expression::parse("2 3").is_err());
+        assert!(Expression::parse("x + y) * 4").is_err());
+        assert!(Expression::parse("(x + y) * 4").is_ok());
+        assert_eq!(
+            Expression::parse("x + y) * 4"),
+            Err(ParseError::TrailingInput {
+                found: TokenKind::RParen,
+                span: Span::new(5, 6)
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_error_spans() {
+        assert_eq!(
+            Expression::parse("x + @"),
+            Err(ParseError::UnexpectedCharacter {
+                character: '@',
+                span: Span::new(4, 5)
+            })
+        );
+        assert_eq!(
+            Expression::parse("1.2.3 + x"),
+            Err(ParseError::InvalidNumber {
+                text: "1.2.3".to_string(),
+                span: Span::new(0, 5)
+            })
+        );
+        assert_eq!(
+            Expression::parse("2 * (x + 1"),
+            Err(ParseError::UnclosedParenthesis {
+                span: Span::new(10, 10),
+                open: Span::new(4, 5)
+            })
+        );
+        assert_eq!(
+            Expression::parse("2 * / 3"),
+            Err(ParseError::UnexpectedToken {
+                found: TokenKind::Slash,
+                span: Span::new(4, 5)
+            })
+        );
+        assert_eq!(
+            Expression::parse("x +"),
+            Err(ParseError::UnexpectedEnd {
+                span: Span::new(3, 3)
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_recovering() {
+        let (expr, errors) = Expression::parse_recovering("2 * (x + ) + y @ 3");
+
+        assert_eq!(
+            expr,
+            expr::add(
+                expr::multiply(
+                    expr::number(2.0),
+                    expr::add(expr::variable("x"), Expression::Error)
+                ),
+                expr::variable("y")
+            )
+        );
+        let spans: Vec<_> = errors.iter().map(|error| error.span()).collect();
+        assert_eq!(
+            spans,
+            vec![Span::new(9, 10), Span::new(15, 16), Span::new(17, 18)]
+        );
+        assert_eq!(
+            expr.evaluate(&create_vars()),
+            Err(EvalError::InvalidExpression)
+        );
+
+        let (expr, errors) = Expression::parse_recovering("x + 1");
+        assert_eq!(expr, expr!("x + 1"));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        let (expr, rest) = Expression::parse_prefix("x + y) * 4").unwrap();
+
+        assert_eq!(expr, expr::add(expr::variable("x"), expr::variable("y")));
+        let kinds: Vec<_> = rest.into_iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![TokenKind::RParen, TokenKind::Star, TokenKind::Number(4.0)]
+        );
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let vars = create_vars();
+
+        assert_eq!(expr!("sqrt(16)").evaluate(&vars).unwrap(), 4.0);
+        assert_eq!(expr!("abs(x - 5)").evaluate(&vars).unwrap(), 3.0);
+        assert_eq!(expr!("log(2, 8)").evaluate(&vars).unwrap(), 3.0);
+        assert_eq!(expr!("ln(exp(y))").evaluate(&vars).unwrap(), 3.0);
+        assert_eq!(expr!("min(x, y, 1)").evaluate(&vars).unwrap(), 1.0);
+        assert_eq!(expr!("max(x, y) ^ 2").evaluate(&vars).unwrap(), 9.0);
+        assert_eq!(
+            expr!("floor(2.7) + ceil(2.2)").evaluate(&vars).unwrap(),
+            5.0
+        );
+        assert_eq!(expr!("round(-x / 4)").evaluate(&vars).unwrap(), -1.0);
+        assert_eq!(expr!("sin(0) + cos(0)").evaluate(&vars).unwrap(), 1.0);
+        assert_eq!(
+            Expression::parse("hypot(3, 4)").unwrap().evaluate(&vars),
+            Ok(5.0)
+        );
+    }
+
+    #[test]
+    fn test_function_call_errors() {
+        let vars = create_vars();
+
+        assert_eq!(
+            expr!("sin(x, y)").evaluate(&vars),
+            Err(EvalError::ArityMismatch {
+                name: "sin".to_string(),
+                expected: Arity::Exact(1),
+                found: 2
+            })
+        );
+        assert_eq!(
+            expr!("max()").evaluate(&vars),
+            Err(EvalError::ArityMismatch {
+                name: "max".to_string(),
+                expected: Arity::AtLeast(1),
+                found: 0
+            })
+        );
+        assert_eq!(
+            expr!("nope(x)").evaluate(&vars),
+            Err(EvalError::UnknownFunction {
+                name: "nope".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_custom_functions() {
+        let vars = create_vars();
+        let mut functions = FunctionRegistry::new();
+        functions
+            .register("clamp", 3, |a| a[0].clamp(a[1], a[2]))
+            .register("lerp", 3, |a| a[0] + (a[1] - a[0]) * a[2])
+            .register_variadic("mean", 1, |a| a.iter().sum::<f64>() / a.len() as f64);
+
+        let eval = |e: Expression| e.evaluate_with(&vars, &functions);
+
+        assert_eq!(eval(expr!("clamp(x * 10, 0, 5)")), Ok(5.0));
+        assert_eq!(eval(expr!("lerp(0, 10, 0.25)")), Ok(2.5));
+        assert_eq!(eval(expr!("mean(x, y, 7)")), Ok(4.0));
+        assert_eq!(eval(expr!("sqrt(mean(x, 30))")), Ok(4.0));
+        assert_eq!(
+            eval(expr!("clamp(x, 0)")),
+            Err(EvalError::ArityMismatch {
+                name: "clamp".to_string(),
+                expected: Arity::Exact(3),
+                found: 2
+            })
+        );
+        assert_eq!(
+            expr!("lerp(0, 10, 0.25)").evaluate(&vars),
+            Err(EvalError::UnknownFunction {
+                name: "lerp".to_string()
+            })
+        );
+    }
+
     #[test]
     fn test_builder_api() {
         let vars = create_vars();
@@ -173,6 +1031,278 @@ mod tests {
     }
 
     #[test]
+    fn test_comparison_and_logic() {
+        let vars = create_vars();
+
+        assert_eq!(expr!("x < y").evaluate(&vars), Ok(1.0));
+        assert_eq!(expr!("x >= y").evaluate(&vars), Ok(0.0));
+        assert_eq!(expr!("x + 1 == y && y != 2").evaluate(&vars), Ok(1.0));
+        assert_eq!(expr!("x > 10 || y <= 3").evaluate(&vars), Ok(1.0));
+        assert_eq!(expr!("!x || !0").evaluate(&vars), Ok(1.0));
+        assert_eq!(expr!("x && 5").evaluate(&vars), Ok(1.0));
+        assert_eq!(expr!("x > 1 ? y * 2 : y / 2").evaluate(&vars), Ok(6.0));
+        assert_eq!(expr!("if(x < 1, 10, 20)").evaluate(&vars), Ok(20.0));
+        assert_eq!(
+            expr!("x < y == 1"),
+            expr::equal(
+                expr::less(expr::variable("x"), expr::variable("y")),
+                expr::number(1.0)
+            )
+        );
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let vars = create_vars();
+
+        // The right operand and the untaken branch would fail if evaluated
+        assert_eq!(expr!("x < 0 && 1 / 0").evaluate(&vars), Ok(0.0));
+        assert_eq!(expr!("x > 0 || missing").evaluate(&vars), Ok(1.0));
+        assert_eq!(expr!("y ? x : nope(1)").evaluate(&vars), Ok(2.0));
+        assert_eq!(expr!("if(0, 1 / 0, 7)").evaluate(&vars), Ok(7.0));
+        assert_eq!(
+            expr!("x > 0 && missing").evaluate(&vars),
+            Err(EvalError::UnknownVariable {
+                name: "missing".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_let_bindings() {
+        let vars = create_vars();
+        let eval = |source: &str| Expression::parse(source).unwrap().evaluate(&vars);
+
+        assert_eq!(
+            eval("let r = x ^ 2 + y ^ 2; sqrt(r) / r"),
+            Ok(13f64.sqrt() / 13.0)
+        );
+        assert_eq!(eval("let a = x + 1\nlet b = a * y\n\nb - a"), Ok(6.0));
+        // Bindings shadow variables and outer bindings, but only within their body
+        assert_eq!(eval("let x = x * 10; let x = x + 1; x"), Ok(21.0));
+        assert_eq!(eval("(let x = 0; x) + x"), Ok(2.0));
+        assert_eq!(eval("let y = y; let x = y; x * y"), Ok(9.0));
+        assert_eq!(
+            eval("(let r = 1; r) + r"),
+            Err(EvalError::UnknownVariable {
+                name: "r".to_string()
+            })
+        );
+        assert_eq!(
+            eval("let r = 1 / (x - 2); 0"),
+            Err(EvalError::DivisionByZero {
+                divisor: expr!("x - 2")
+            })
+        );
+
+        let e = expr::let_in(
+            "r",
+            expr::variable("x"),
+            expr::multiply(expr::variable("r"), expr::variable("r")),
+        );
+        assert_eq!(e, expr!("let r = x; r * r"));
+        assert_eq!(e.evaluate(&vars), Ok(4.0));
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut state = HashMap::from([
+            ("x".to_string(), 1.0),
+            ("v".to_string(), 2.0),
+            ("a".to_string(), -1.0),
+            ("dt".to_string(), 0.5),
+        ]);
+        let step = expr!("v = v + a * dt; x = x + v * dt");
+
+        assert_eq!(
+            step.execute(&mut state),
+            Ok(BTreeSet::from(["v".to_string(), "x".to_string()]))
+        );
+        assert_eq!((state["v"], state["x"]), (1.5, 1.75));
+        // Evaluating runs the same statements without storing them
+        assert_eq!(step.evaluate(&state), Ok(2.25));
+        assert_eq!(state["x"], 1.75);
+
+        // Nothing is stored if a later statement fails
+        assert_eq!(
+            expr!("x = 0; v = v / x").execute(&mut state),
+            Err(EvalError::DivisionByZero {
+                divisor: expr!("x")
+            })
+        );
+        assert_eq!(state["x"], 1.75);
+
+        // Assigning to a `let` binding only changes the binding, and a Bool is stored as 1
+        let script = expr!("let v = 10; v = v * 2; x = v > 15; v");
+        assert_eq!(
+            script.execute(&mut state),
+            Ok(BTreeSet::from(["x".to_string()]))
+        );
+        assert_eq!((state["v"], state["x"]), (1.5, 1.0));
+
+        assert_eq!(
+            expr!(r#"x = "fast""#).execute(&mut state),
+            Err(EvalError::TypeMismatch {
+                expected: Type::Number,
+                found: Type::String,
+                expression: expr::string("fast")
+            })
+        );
+        let mut values = HashMap::from([("label".to_string(), Value::from("a"))]);
+        assert!(
+            expr!(r#"label = label + "b"; n = 2"#)
+                .execute(&mut values)
+                .is_ok()
+        );
+        assert_eq!(values["label"], Value::from("ab"));
+        assert_eq!(values["n"], Value::Number(2.0));
+
+        // Built assignments outside the statements are rejected rather than dropped, though
+        // evaluating them still works
+        for script in [
+            expr::add(
+                expr::number(1.0),
+                expr::assign("x", 0.0.into(), expr::variable("x")),
+            ),
+            expr::let_in(
+                "y",
+                expr::assign("x", 0.0.into(), expr::variable("x")),
+                expr::variable("y"),
+            ),
+            expr!("v = 0; #{expr::assign(\"x\", 0.0.into(), expr::variable(\"x\"))} + v"),
+        ] {
+            assert_eq!(
+                script.execute(&mut state),
+                Err(EvalError::MisplacedAssignment {
+                    name: "x".to_string()
+                })
+            );
+            assert!(script.evaluate(&state).is_ok());
+        }
+        assert_eq!((state["v"], state["x"]), (1.5, 1.0));
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let vars = HashMap::from([
+            ("n".to_string(), Value::Integer(7)),
+            ("k".to_string(), Value::Integer(3)),
+            ("name".to_string(), Value::from("Ada")),
+            ("active".to_string(), Value::Bool(true)),
+            ("tags".to_string(), Value::from(vec!["a", "b"])),
+            ("missing".to_string(), Value::Null),
+        ]);
+        let eval = |source: &str| Expression::parse(source).unwrap().evaluate_value(&vars);
+
+        assert_eq!(eval("n * k - k"), Ok(Value::Integer(18)));
+        assert_eq!(eval("n * 2"), Ok(Value::Number(14.0)));
+        assert_eq!(eval("-n"), Ok(Value::Integer(-7)));
+        assert_eq!(eval("n / 2"), Ok(Value::Number(3.5)));
+        assert_eq!(eval("n + 0.5"), Ok(Value::Number(7.5)));
+        assert_eq!(eval(r#"name + "!""#), Ok(Value::from("Ada!")));
+        assert_eq!(
+            eval(r#"tags + ["c"]"#),
+            Ok(Value::from(vec!["a", "b", "c"]))
+        );
+        assert_eq!(eval("active && n > 5"), Ok(Value::Bool(true)));
+        assert_eq!(eval(r#"[n, name] == [7.0, "Ada"]"#), Ok(Value::Bool(true)));
+        assert_eq!(
+            eval("missing == null ? sqrt(n + 2) : 0"),
+            Ok(Value::Number(3.0))
+        );
+
+        // `evaluate` reads a Bool as 1 or 0 and rejects results that are not numbers
+        assert_eq!(expr!("active").evaluate(&vars), Ok(1.0));
+        assert_eq!(expr!("n").evaluate(&vars), Ok(7.0));
+        assert_eq!(
+            expr!("name").evaluate(&vars),
+            Err(EvalError::TypeMismatch {
+                expected: Type::Number,
+                found: Type::String,
+                expression: expr!("name")
+            })
+        );
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let vars = HashMap::from([
+            ("x".to_string(), Value::Number(2.0)),
+            ("s".to_string(), Value::from("a")),
+        ]);
+        let locate = |source: &str| {
+            Expression::parse(source)
+                .unwrap()
+                .evaluate_located(&vars, &FunctionRegistry::new())
+                .unwrap_err()
+        };
+        let eval = |source: &str| locate(source).error;
+
+        let source = "x + (true + x) * 2";
+        let error = locate(source);
+        assert_eq!(
+            error.error,
+            EvalError::InvalidOperands {
+                operator: BinaryOperator::Add,
+                lhs: Type::Bool,
+                rhs: Type::Number,
+                expression: expr!("true + x")
+            }
+        );
+        assert_eq!(
+            format!("{} at {}", error, error.span_in(source).unwrap()),
+            "Cannot add Bool and Number at 4..14"
+        );
+
+        let source = "sqrt(x) + sqrt(s)";
+        let error = locate(source);
+        assert_eq!(
+            error.error,
+            EvalError::TypeMismatch {
+                expected: Type::Number,
+                found: Type::String,
+                expression: expr!("s")
+            }
+        );
+        assert_eq!(error.span_in(source), Some(Span::new(15, 16)));
+
+        assert_eq!(
+            eval("s < 1").to_string(),
+            "Cannot compare String and Number"
+        );
+        assert_eq!(eval("s ? 1 : 2").to_string(), "Expected Bool, found String");
+        assert_eq!(eval("!null").to_string(), "Expected Bool, found Null");
+        assert_eq!(eval("-[x]").to_string(), "Expected Number, found List");
+        assert_eq!(
+            eval("x - false").to_string(),
+            "Cannot subtract Number and Bool"
+        );
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let vars = create_vars();
+        let base = expr!("x + 1");
+        let rates = [0.5, 0.25];
+
+        let e = expr!("#{&base} * (1 + #{rates[1]}) ^ #{ { let n = 2.0; n } }");
+        assert_eq!(
+            e,
+            expr::multiply(
+                base.clone(),
+                expr::power(
+                    expr::add(expr::number(1.0), expr::number(0.25)),
+                    expr::number(2.0)
+                )
+            )
+        );
+        assert_eq!(e.evaluate(&vars), Ok(4.6875));
+        assert_eq!(expr!("#{base}"), expr!("x + 1"));
+        assert!(Expression::parse("#{x}").is_err());
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
     fn test_floating_point_numbers() {
         let vars = create_vars();
 
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::fmt;
+
+pub use expression_grammar::builtins::{Arity, Builtin, builtin};
+
+type NativeFunction = Box<dyn Fn(&[f64]) -> f64 + Send + Sync>;
+
+/// A user supplied function stored in a [`FunctionRegistry`]
+pub struct Function {
+    arity: Arity,
+    function: NativeFunction,
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A function resolved by name, either from a registry or from the built-ins
+#[derive(Debug, Clone, Copy)]
+pub enum FunctionRef<'a> {
+    Custom(&'a Function),
+    Builtin(Builtin),
+}
+
+impl FunctionRef<'_> {
+    pub fn arity(&self) -> Arity {
+        match self {
+            FunctionRef::Custom(function) => function.arity,
+            FunctionRef::Builtin(builtin) => builtin.arity,
+        }
+    }
+
+    pub fn call(&self, args: &[f64]) -> f64 {
+        match self {
+            FunctionRef::Custom(function) => (function.function)(args),
+            FunctionRef::Builtin(builtin) => (builtin.function)(args),
+        }
+    }
+}
+
+/// Custom functions made available to [`Expression::evaluate_with`].
+///
+/// Registered functions take priority over built-ins of the same name.
+///
+/// [`Expression::evaluate_with`]: crate::expression::Expression::evaluate_with
+#[derive(Debug, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Function>,
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        FunctionRegistry::default()
+    }
+
+    /// Registers a function taking exactly `arity` arguments
+    pub fn register<F>(&mut self, name: &str, arity: usize, function: F) -> &mut Self
+    where
+        F: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    {
+        self.insert(name, Arity::Exact(arity), Box::new(function))
+    }
+
+    /// Registers a function taking `min_args` or more arguments
+    pub fn register_variadic<F>(&mut self, name: &str, min_args: usize, function: F) -> &mut Self
+    where
+        F: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    {
+        self.insert(name, Arity::AtLeast(min_args), Box::new(function))
+    }
+
+    fn insert(&mut self, name: &str, arity: Arity, function: NativeFunction) -> &mut Self {
+        self.functions
+            .insert(name.to_string(), Function { arity, function });
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    /// Finds `name` among the registered functions, falling back to the built-ins
+    pub fn resolve(&self, name: &str) -> Option<FunctionRef<'_>> {
+        match self.functions.get(name) {
+            Some(function) => Some(FunctionRef::Custom(function)),
+            None => builtin(name).map(FunctionRef::Builtin),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_resolve() {
+        let mut functions = FunctionRegistry::new();
+        functions
+            .register("double", 1, |a| a[0] * 2.0)
+            .register_variadic("sum", 0, |a| a.iter().sum())
+            .register("sqrt", 1, |_| -1.0);
+
+        let double = functions.resolve("double").unwrap();
+        assert_eq!(double.arity(), Arity::Exact(1));
+        assert_eq!(double.call(&[4.0]), 8.0);
+
+        let sum = functions.resolve("sum").unwrap();
+        assert_eq!(sum.arity(), Arity::AtLeast(0));
+        assert_eq!(sum.call(&[1.0, 2.0, 3.0]), 6.0);
+
+        // Registered functions shadow built-ins, which remain available otherwise
+        assert_eq!(functions.resolve("sqrt").unwrap().call(&[4.0]), -1.0);
+        assert_eq!(functions.resolve("abs").unwrap().call(&[-4.0]), 4.0);
+        assert!(functions.resolve("nope").is_none());
+    }
+}
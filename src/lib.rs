@@ -1,4 +1,18 @@
+pub mod bytecode;
+pub mod compile;
+pub mod context;
+mod derivative;
+mod display;
+pub mod error;
 pub mod expression;
+pub mod functions;
 pub mod parsing;
+pub mod simplify;
+pub mod typecheck;
+pub mod value;
 
-pub use expression_macro::expr;
\ No newline at end of file
+pub use expression_grammar::diagnostic;
+pub use expression_macro::{ExpressionContext, expr, expr_fn};
+
+// Lets derived impls name `::expression_parser` from inside this crate's own tests
+extern crate self as expression_parser;
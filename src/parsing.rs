@@ -1,308 +1,405 @@
+//! The runtime face of the shared grammar in `expression_grammar`.
+//!
+//! [`Parser`] wraps the grammar's parser and converts its syntax tree into an
+//! [`Expression`], so existing callers keep working unchanged.
+
+use crate::error::ParseError;
 use crate::expression::Expression;
+use expression_grammar::parsing as grammar;
 
-#[derive(Debug, PartialEq)]
-pub enum Token {
-    Number(f64),
-    Variable(String),
-    Plus,   // +
-    Minus,  // -
-    Star,   // *
-    Slash,  // /
-    Caret,  // ^
-    LParen, // (
-    RParen, // )
-}
+pub use expression_grammar::parsing::{
+    Associativity, BinaryOperator, OperatorInfo, ParserConfig, Span, Token, TokenKind, tokenize,
+    tokenize_recovering,
+};
 
 pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+    inner: grammar::Parser,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            inner: grammar::Parser::new(tokens),
+        }
     }
 
-    fn peek(&self) -> Option<&Token> {
-        self.tokens.get(self.current)
+    pub fn with_config(tokens: Vec<Token>, config: ParserConfig) -> Self {
+        Parser {
+            inner: grammar::Parser::with_config(tokens, config),
+        }
     }
 
-    fn advance(&mut self) -> Option<&Token> {
-        let token = self.tokens.get(self.current);
-        self.current += 1;
-        token
+    /// Parses the whole token stream, failing if anything is left after the expression
+    pub fn parse(&mut self) -> Result<Expression, ParseError> {
+        self.inner.parse().map(Expression::from)
     }
 
-    pub fn parse_expression(&mut self) -> Result<Expression, String> {
-        self.parse_addition()
+    /// Parses the whole token stream, reporting every syntax error instead of stopping at the first.
+    ///
+    /// Unparseable parts of the input become [`Expression::Error`] nodes in the returned tree.
+    pub fn parse_recovering(&mut self) -> (Expression, Vec<ParseError>) {
+        let (syntax, errors) = self.inner.parse_recovering();
+        (syntax.into(), errors)
     }
 
-    fn parse_addition(&mut self) -> Result<Expression, String> {
-        let mut expr = self.parse_multiplication()?;
-
-        while let Some(token) = self.peek() {
-            match token {
-                Token::Plus => {
-                    self.advance();
-                    expr = Expression::Add(Box::new(expr), Box::new(self.parse_multiplication()?));
-                }
-                Token::Minus => {
-                    self.advance();
-                    expr = Expression::Subtract(
-                        Box::new(expr),
-                        Box::new(self.parse_multiplication()?),
-                    );
-                }
-                _ => break,
-            }
-        }
-
-        Ok(expr)
+    /// Parses the longest leading expression and hands back the tokens that follow it
+    pub fn parse_prefix(self) -> Result<(Expression, Vec<Token>), ParseError> {
+        let (syntax, rest) = self.inner.parse_prefix()?;
+        Ok((syntax.into(), rest))
     }
 
-    fn parse_multiplication(&mut self) -> Result<Expression, String> {
-        let mut expr = self.parse_power()?;
-
-        while let Some(token) = self.peek() {
-            match token {
-                Token::Star => {
-                    self.advance();
-                    expr = Expression::Multiply(Box::new(expr), Box::new(self.parse_power()?));
-                }
-                Token::Slash => {
-                    self.advance();
-                    expr = Expression::Divide(Box::new(expr), Box::new(self.parse_power()?));
-                }
-                _ => break,
-            }
-        }
-
-        Ok(expr)
+    pub fn parse_expression(&mut self) -> Result<Expression, ParseError> {
+        self.inner.parse_expression().map(Expression::from)
     }
+}
 
-    fn parse_power(&mut self) -> Result<Expression, String> {
-        let mut expr = self.parse_primary()?;
-
-        while let Some(Token::Caret) = self.peek() {
-            self.advance();
-            expr = Expression::Power(Box::new(expr), Box::new(self.parse_primary()?));
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Ok(expr)
+    // Gives each token a one byte span at its index
+    fn spanned(kinds: Vec<TokenKind>) -> Vec<Token> {
+        kinds
+            .into_iter()
+            .enumerate()
+            .map(|(i, kind)| Token {
+                kind,
+                span: Span::new(i, i + 1),
+            })
+            .collect()
     }
 
-    fn parse_primary(&mut self) -> Result<Expression, String> {
-        let token = self.advance().ok_or("Unexpected end of input")?;
-        match token {
-            Token::Number(n) => Ok(Expression::Number(*n)),
-            Token::Variable(name) => Ok(Expression::Variable(name.clone())),
-            Token::LParen => {
-                let expr = self.parse_expression()?;
-                if self.advance() != Some(&Token::RParen) {
-                    return Err("Expected closing parenthesis".to_string());
-                }
+    #[test]
+    fn test_parse_number() {
+        let tokens = vec![TokenKind::Number(42.0)];
+        let mut parser = Parser::new(spanned(tokens));
 
-                Ok(expr)
-            }
-            _ => Err("Unexpected token".to_string()),
-        }
+        assert_eq!(parser.parse_expression(), Ok(Expression::Number(42.0)));
     }
-}
 
-pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
-    let mut tokens = Vec::new();
-    let mut chars = input.chars().peekable();
+    #[test]
+    fn test_parse_variable() {
+        let tokens = vec![TokenKind::Variable("x".to_string())];
+        let mut parser = Parser::new(spanned(tokens));
 
-    while let Some(&c) = chars.peek() {
-        match c {
-            ' ' | '\t' | '\r' => {
-                chars.next();
-            }
-            '0'..='9' | '.' => {
-                let mut num = String::new();
-                while let Some(&c) = chars.peek() {
-                    if c.is_ascii_digit() || c == '.' {
-                        num.push(c);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-                tokens.push(Token::Number(num.parse().map_err(|_| "Invalid number")?));
-            }
-            'a'..='z' | 'A'..='Z' => {
-                let mut name = String::new();
-                while let Some(&c) = chars.peek() {
-                    if c.is_ascii_alphabetic() {
-                        name.push(c);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-                tokens.push(Token::Variable(name));
-            }
-            '+' => {
-                tokens.push(Token::Plus);
-                chars.next();
-            }
-            '-' => {
-                tokens.push(Token::Minus);
-                chars.next();
-            }
-            '*' => {
-                tokens.push(Token::Star);
-                chars.next();
-            }
-            '/' => {
-                tokens.push(Token::Slash);
-                chars.next();
-            }
-            '^' => {
-                tokens.push(Token::Caret);
-                chars.next();
-            }
-            '(' => {
-                tokens.push(Token::LParen);
-                chars.next();
-            }
-            ')' => {
-                tokens.push(Token::RParen);
-                chars.next();
-            }
-            _ => return Err(format!("Unexpected character: {}", c)),
-        }
+        assert_eq!(
+            parser.parse_expression(),
+            Ok(Expression::Variable("x".to_string()))
+        );
     }
 
-    Ok(tokens)
-}
+    #[test]
+    fn test_parse_addition() {
+        let tokens = vec![
+            TokenKind::Number(2.0),
+            TokenKind::Plus,
+            TokenKind::Number(3.0),
+        ];
+        let mut parser = Parser::new(spanned(tokens));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        assert_eq!(
+            parser.parse_expression(),
+            Ok(Expression::Add(
+                Box::new(Expression::Number(2.0)),
+                Box::new(Expression::Number(3.0))
+            ))
+        );
+    }
 
     #[test]
-    fn test_tokenize_numbers() {
-        assert_eq!(tokenize("123.45"), Ok(vec![Token::Number(123.45)]));
+    fn test_parse_operator_precedence() {
+        let tokens = vec![
+            TokenKind::Number(2.0),
+            TokenKind::Plus,
+            TokenKind::Number(3.0),
+            TokenKind::Star,
+            TokenKind::Number(4.0),
+        ];
+        let mut parser = Parser::new(spanned(tokens));
 
-        assert_eq!(tokenize("42"), Ok(vec![Token::Number(42.0)]));
+        assert_eq!(
+            parser.parse_expression(),
+            Ok(Expression::Add(
+                Box::new(Expression::Number(2.0)),
+                Box::new(Expression::Multiply(
+                    Box::new(Expression::Number(3.0)),
+                    Box::new(Expression::Number(4.0))
+                ))
+            ))
+        );
     }
 
     #[test]
-    fn test_tokenize_variables() {
+    fn test_parse_parentheses() {
+        let tokens = vec![
+            TokenKind::LParen,
+            TokenKind::Number(2.0),
+            TokenKind::Plus,
+            TokenKind::Number(3.0),
+            TokenKind::RParen,
+            TokenKind::Star,
+            TokenKind::Number(4.0),
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+
         assert_eq!(
-            tokenize("xyz"),
-            Ok(vec![Token::Variable("xyz".to_string())])
+            parser.parse_expression(),
+            Ok(Expression::Multiply(
+                Box::new(Expression::Add(
+                    Box::new(Expression::Number(2.0)),
+                    Box::new(Expression::Number(3.0))
+                )),
+                Box::new(Expression::Number(4.0))
+            ))
         );
+    }
 
-        assert_eq!(tokenize("x"), Ok(vec![Token::Variable("x".to_string())]));
+    #[test]
+    fn test_parse_unmatched_parentheses() {
+        let tokens = vec![
+            TokenKind::LParen,
+            TokenKind::Number(2.0),
+            TokenKind::Plus,
+            TokenKind::Number(3.0),
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+
+        assert!(parser.parse_expression().is_err());
     }
 
     #[test]
-    fn test_tokenize_operators() {
+    fn test_parse_power() {
+        let tokens = vec![
+            TokenKind::Number(2.0),
+            TokenKind::Caret,
+            TokenKind::Number(3.0),
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+
         assert_eq!(
-            tokenize("+-*/^"),
-            Ok(vec![
-                Token::Plus,
-                Token::Minus,
-                Token::Star,
-                Token::Slash,
-                Token::Caret
-            ])
+            parser.parse_expression(),
+            Ok(Expression::Power(
+                Box::new(Expression::Number(2.0)),
+                Box::new(Expression::Number(3.0))
+            ))
         );
     }
 
     #[test]
-    fn test_tokenize_parentheses() {
+    fn test_parse_negation() {
+        let tokens = vec![TokenKind::Minus, TokenKind::Variable("x".to_string())];
+        let mut parser = Parser::new(spanned(tokens));
+
         assert_eq!(
-            tokenize("(x)"),
-            Ok(vec![
-                Token::LParen,
-                Token::Variable("x".to_string()),
-                Token::RParen
-            ])
+            parser.parse_expression(),
+            Ok(Expression::Negate(Box::new(Expression::Variable(
+                "x".to_string()
+            ))))
         );
     }
 
     #[test]
-    fn test_tokenize_complex_expression() {
+    fn test_parse_unary_plus() {
+        let tokens = vec![TokenKind::Plus, TokenKind::Number(3.0)];
+        let mut parser = Parser::new(spanned(tokens));
+
+        assert_eq!(parser.parse_expression(), Ok(Expression::Number(3.0)));
+    }
+
+    #[test]
+    fn test_parse_negation_precedence() {
+        // -2 ^ 2 => -(2 ^ 2)
+        let tokens = vec![
+            TokenKind::Minus,
+            TokenKind::Number(2.0),
+            TokenKind::Caret,
+            TokenKind::Number(2.0),
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+
         assert_eq!(
-            tokenize("(x + 2.5) * y"),
-            Ok(vec![
-                Token::LParen,
-                Token::Variable("x".to_string()),
-                Token::Plus,
-                Token::Number(2.5),
-                Token::RParen,
-                Token::Star,
-                Token::Variable("y".to_string())
-            ])
+            parser.parse_expression(),
+            Ok(Expression::Negate(Box::new(Expression::Power(
+                Box::new(Expression::Number(2.0)),
+                Box::new(Expression::Number(2.0))
+            ))))
+        );
+
+        // 2 * -3 => 2 * (-3)
+        let tokens = vec![
+            TokenKind::Number(2.0),
+            TokenKind::Star,
+            TokenKind::Minus,
+            TokenKind::Number(3.0),
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+
+        assert_eq!(
+            parser.parse_expression(),
+            Ok(Expression::Multiply(
+                Box::new(Expression::Number(2.0)),
+                Box::new(Expression::Negate(Box::new(Expression::Number(3.0))))
+            ))
         );
     }
 
     #[test]
-    fn test_tokenize_invalid_characters() {
-        assert!(tokenize("x @ y").is_err());
-        assert!(tokenize("2 $ 3").is_err());
-        assert!(tokenize("#123").is_err());
+    fn test_parse_rejects_trailing_tokens() {
+        let tokens = vec![TokenKind::Number(2.0), TokenKind::Number(3.0)];
+        let mut parser = Parser::new(spanned(tokens));
+
+        assert_eq!(
+            parser.parse(),
+            Err(ParseError::TrailingInput {
+                found: TokenKind::Number(3.0),
+                span: Span::new(1, 2)
+            })
+        );
+
+        let tokens = vec![
+            TokenKind::Variable("x".to_string()),
+            TokenKind::RParen,
+            TokenKind::Star,
+            TokenKind::Number(4.0),
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+
+        assert_eq!(
+            parser.parse(),
+            Err(ParseError::TrailingInput {
+                found: TokenKind::RParen,
+                span: Span::new(1, 2)
+            })
+        );
     }
 
     #[test]
-    fn test_tokenize_whitespace() {
-        assert_eq!(tokenize("x + y"), tokenize("x+y"));
+    fn test_parse_prefix() {
+        let tokens = vec![
+            TokenKind::Number(2.0),
+            TokenKind::Plus,
+            TokenKind::Number(3.0),
+            TokenKind::RParen,
+            TokenKind::Star,
+        ];
+        let parser = Parser::new(spanned(tokens));
+
+        assert_eq!(
+            parser.parse_prefix(),
+            Ok((
+                Expression::Add(
+                    Box::new(Expression::Number(2.0)),
+                    Box::new(Expression::Number(3.0))
+                ),
+                vec![
+                    Token {
+                        kind: TokenKind::RParen,
+                        span: Span::new(3, 4)
+                    },
+                    Token {
+                        kind: TokenKind::Star,
+                        span: Span::new(4, 5)
+                    }
+                ]
+            ))
+        );
 
-        assert_eq!(tokenize(" x  +  y "), tokenize("x+y"));
+        let parser = Parser::new(spanned(vec![TokenKind::Number(1.0)]));
+        assert_eq!(parser.parse_prefix(), Ok((Expression::Number(1.0), vec![])));
     }
 
     #[test]
-    fn test_parse_number() {
-        let tokens = vec![Token::Number(42.0)];
-        let mut parser = Parser::new(tokens);
+    fn test_parse_power_right_associative() {
+        // 2 ^ 3 ^ 2 => 2 ^ (3 ^ 2)
+        let tokens = vec![
+            TokenKind::Number(2.0),
+            TokenKind::Caret,
+            TokenKind::Number(3.0),
+            TokenKind::Caret,
+            TokenKind::Number(2.0),
+        ];
+        let mut parser = Parser::new(spanned(tokens));
 
-        assert_eq!(parser.parse_expression(), Ok(Expression::Number(42.0)));
+        assert_eq!(
+            parser.parse_expression(),
+            Ok(Expression::Power(
+                Box::new(Expression::Number(2.0)),
+                Box::new(Expression::Power(
+                    Box::new(Expression::Number(3.0)),
+                    Box::new(Expression::Number(2.0))
+                ))
+            ))
+        );
     }
 
     #[test]
-    fn test_parse_variable() {
-        let tokens = vec![Token::Variable("x".to_string())];
-        let mut parser = Parser::new(tokens);
+    fn test_parse_subtraction_left_associative() {
+        // 5 - 3 - 1 => (5 - 3) - 1
+        let tokens = vec![
+            TokenKind::Number(5.0),
+            TokenKind::Minus,
+            TokenKind::Number(3.0),
+            TokenKind::Minus,
+            TokenKind::Number(1.0),
+        ];
+        let mut parser = Parser::new(spanned(tokens));
 
         assert_eq!(
             parser.parse_expression(),
-            Ok(Expression::Variable("x".to_string()))
+            Ok(Expression::Subtract(
+                Box::new(Expression::Subtract(
+                    Box::new(Expression::Number(5.0)),
+                    Box::new(Expression::Number(3.0))
+                )),
+                Box::new(Expression::Number(1.0))
+            ))
         );
     }
 
     #[test]
-    fn test_parse_addition() {
-        let tokens = vec![Token::Number(2.0), Token::Plus, Token::Number(3.0)];
-        let mut parser = Parser::new(tokens);
+    fn test_parse_with_left_associative_power() {
+        let config =
+            ParserConfig::default().with_operator(BinaryOperator::Power, 40, Associativity::Left);
+        let tokens = vec![
+            TokenKind::Number(2.0),
+            TokenKind::Caret,
+            TokenKind::Number(3.0),
+            TokenKind::Caret,
+            TokenKind::Number(2.0),
+        ];
+        let mut parser = Parser::with_config(spanned(tokens), config);
 
         assert_eq!(
             parser.parse_expression(),
-            Ok(Expression::Add(
-                Box::new(Expression::Number(2.0)),
-                Box::new(Expression::Number(3.0))
+            Ok(Expression::Power(
+                Box::new(Expression::Power(
+                    Box::new(Expression::Number(2.0)),
+                    Box::new(Expression::Number(3.0))
+                )),
+                Box::new(Expression::Number(2.0))
             ))
         );
     }
 
     #[test]
-    fn test_parse_operator_precedence() {
+    fn test_parse_with_custom_precedence() {
+        // With `+` binding tighter than `*`: 2 * 3 + 4 => 2 * (3 + 4)
+        let config =
+            ParserConfig::default().with_operator(BinaryOperator::Add, 30, Associativity::Left);
         let tokens = vec![
-            Token::Number(2.0),
-            Token::Plus,
-            Token::Number(3.0),
-            Token::Star,
-            Token::Number(4.0),
+            TokenKind::Number(2.0),
+            TokenKind::Star,
+            TokenKind::Number(3.0),
+            TokenKind::Plus,
+            TokenKind::Number(4.0),
         ];
-        let mut parser = Parser::new(tokens);
+        let mut parser = Parser::with_config(spanned(tokens), config);
 
         assert_eq!(
             parser.parse_expression(),
-            Ok(Expression::Add(
+            Ok(Expression::Multiply(
                 Box::new(Expression::Number(2.0)),
-                Box::new(Expression::Multiply(
+                Box::new(Expression::Add(
                     Box::new(Expression::Number(3.0)),
                     Box::new(Expression::Number(4.0))
                 ))
@@ -311,23 +408,30 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_parentheses() {
+    fn test_parse_with_maximum_precedence() {
+        // Nothing binds tighter than the top level: 1 + 2 * 3 + 4 => ((1 + 2) * 3) + 4
+        let config = ParserConfig::default()
+            .with_operator(BinaryOperator::Add, u8::MAX, Associativity::Left)
+            .with_operator(BinaryOperator::Multiply, u8::MAX, Associativity::Left);
         let tokens = vec![
-            Token::LParen,
-            Token::Number(2.0),
-            Token::Plus,
-            Token::Number(3.0),
-            Token::RParen,
-            Token::Star,
-            Token::Number(4.0),
+            TokenKind::Number(1.0),
+            TokenKind::Plus,
+            TokenKind::Number(2.0),
+            TokenKind::Star,
+            TokenKind::Number(3.0),
+            TokenKind::Plus,
+            TokenKind::Number(4.0),
         ];
-        let mut parser = Parser::new(tokens);
+        let mut parser = Parser::with_config(spanned(tokens), config);
 
         assert_eq!(
             parser.parse_expression(),
-            Ok(Expression::Multiply(
-                Box::new(Expression::Add(
-                    Box::new(Expression::Number(2.0)),
+            Ok(Expression::Add(
+                Box::new(Expression::Multiply(
+                    Box::new(Expression::Add(
+                        Box::new(Expression::Number(1.0)),
+                        Box::new(Expression::Number(2.0))
+                    )),
                     Box::new(Expression::Number(3.0))
                 )),
                 Box::new(Expression::Number(4.0))
@@ -336,44 +440,217 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_unmatched_parentheses() {
+    fn test_parse_with_high_prefix_precedence() {
+        // Spreadsheet convention: -2 ^ 2 => (-2) ^ 2
+        let config = ParserConfig::default().with_prefix_precedence(50);
         let tokens = vec![
-            Token::LParen,
-            Token::Number(2.0),
-            Token::Plus,
-            Token::Number(3.0),
+            TokenKind::Minus,
+            TokenKind::Number(2.0),
+            TokenKind::Caret,
+            TokenKind::Number(2.0),
         ];
-        let mut parser = Parser::new(tokens);
+        let mut parser = Parser::with_config(spanned(tokens), config);
 
-        assert!(parser.parse_expression().is_err());
+        assert_eq!(
+            parser.parse_expression(),
+            Ok(Expression::Power(
+                Box::new(Expression::Negate(Box::new(Expression::Number(2.0)))),
+                Box::new(Expression::Number(2.0))
+            ))
+        );
     }
 
     #[test]
-    fn test_parse_power() {
-        let tokens = vec![Token::Number(2.0), Token::Caret, Token::Number(3.0)];
-        let mut parser = Parser::new(tokens);
+    fn test_parse_function_call() {
+        let tokens = vec![
+            TokenKind::Variable("log".to_string()),
+            TokenKind::LParen,
+            TokenKind::Number(2.0),
+            TokenKind::Comma,
+            TokenKind::Variable("x".to_string()),
+            TokenKind::Plus,
+            TokenKind::Number(1.0),
+            TokenKind::RParen,
+        ];
+        let mut parser = Parser::new(spanned(tokens));
 
         assert_eq!(
             parser.parse_expression(),
-            Ok(Expression::Power(
-                Box::new(Expression::Number(2.0)),
-                Box::new(Expression::Number(3.0))
-            ))
+            Ok(Expression::Call {
+                name: "log".to_string(),
+                args: vec![
+                    Expression::Number(2.0),
+                    Expression::Add(
+                        Box::new(Expression::Variable("x".to_string())),
+                        Box::new(Expression::Number(1.0))
+                    )
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_function_call_without_arguments() {
+        let tokens = vec![
+            TokenKind::Variable("f".to_string()),
+            TokenKind::LParen,
+            TokenKind::RParen,
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+
+        assert_eq!(
+            parser.parse_expression(),
+            Ok(Expression::Call {
+                name: "f".to_string(),
+                args: vec![]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_argument_list() {
+        let tokens = vec![
+            TokenKind::Variable("max".to_string()),
+            TokenKind::LParen,
+            TokenKind::Number(1.0),
+            TokenKind::Number(2.0),
+            TokenKind::RParen,
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+        assert!(parser.parse_expression().is_err());
+
+        let tokens = vec![
+            TokenKind::Variable("max".to_string()),
+            TokenKind::LParen,
+            TokenKind::Number(1.0),
+            TokenKind::Comma,
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+        assert!(parser.parse_expression().is_err());
+    }
+
+    #[test]
+    fn test_parse_recovering_reports_every_error() {
+        // (1 + ) * (2 3) + )
+        let tokens = vec![
+            TokenKind::LParen,
+            TokenKind::Number(1.0),
+            TokenKind::Plus,
+            TokenKind::RParen,
+            TokenKind::Star,
+            TokenKind::LParen,
+            TokenKind::Number(2.0),
+            TokenKind::Number(3.0),
+            TokenKind::RParen,
+            TokenKind::Plus,
+            TokenKind::RParen,
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+
+        let (expr, errors) = parser.parse_recovering();
+        assert_eq!(
+            expr,
+            Expression::Add(
+                Box::new(Expression::Multiply(
+                    Box::new(Expression::Add(
+                        Box::new(Expression::Number(1.0)),
+                        Box::new(Expression::Error)
+                    )),
+                    Box::new(Expression::Number(2.0))
+                )),
+                Box::new(Expression::Error)
+            )
+        );
+        assert_eq!(
+            errors,
+            vec![
+                ParseError::UnexpectedToken {
+                    found: TokenKind::RParen,
+                    span: Span::new(3, 4)
+                },
+                ParseError::UnclosedParenthesis {
+                    span: Span::new(7, 8),
+                    open: Span::new(5, 6)
+                },
+                ParseError::UnexpectedToken {
+                    found: TokenKind::RParen,
+                    span: Span::new(10, 11)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_recovering_arguments() {
+        // max(1 2, 3)
+        let tokens = vec![
+            TokenKind::Variable("max".to_string()),
+            TokenKind::LParen,
+            TokenKind::Number(1.0),
+            TokenKind::Number(2.0),
+            TokenKind::Comma,
+            TokenKind::Number(3.0),
+            TokenKind::RParen,
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+
+        let (expr, errors) = parser.parse_recovering();
+        assert_eq!(
+            expr,
+            Expression::Call {
+                name: "max".to_string(),
+                args: vec![Expression::Number(1.0), Expression::Number(3.0)]
+            }
         );
+        assert_eq!(
+            errors,
+            vec![ParseError::ExpectedArgumentDelimiter {
+                span: Span::new(3, 4),
+                open: Span::new(1, 2)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_recovering_valid_input() {
+        let tokens = vec![
+            TokenKind::Number(2.0),
+            TokenKind::Plus,
+            TokenKind::Number(3.0),
+        ];
+        let mut parser = Parser::new(spanned(tokens));
+
+        assert_eq!(
+            parser.parse_recovering(),
+            (
+                Expression::Add(
+                    Box::new(Expression::Number(2.0)),
+                    Box::new(Expression::Number(3.0))
+                ),
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_tokens() {
+        let mut parser = Parser::new(spanned(vec![TokenKind::Invalid("@".to_string())]));
+
+        assert!(parser.parse().is_err());
     }
 
     #[test]
     fn test_parse_empty() {
         let tokens = vec![];
-        let mut parser = Parser::new(tokens);
+        let mut parser = Parser::new(spanned(tokens));
 
         assert!(parser.parse_expression().is_err());
     }
 
     #[test]
     fn test_parse_incomplete_expression() {
-        let tokens = vec![Token::Number(2.0), Token::Plus];
-        let mut parser = Parser::new(tokens);
+        let tokens = vec![TokenKind::Number(2.0), TokenKind::Plus];
+        let mut parser = Parser::new(spanned(tokens));
 
         assert!(parser.parse_expression().is_err());
     }
@@ -0,0 +1,732 @@
+//! Algebraic simplification of [`Expression`] trees.
+//!
+//! Simplification folds constants, drops identities such as `x + 0`, `x * 1` and `x ^ 1`,
+//! and combines like terms (`2 * x + 3 * x` becomes `5 * x`, `x * x` becomes `x ^ 2`).
+//! Conditionals with a constant condition are replaced by the selected branch, as are
+//! `&&` and `||` whose left operand decides the result. Unused `let` bindings are removed.
+//! Results match [`Expression::evaluate`] up to floating point rounding for finite
+//! variable values. Variables are assumed to hold numbers. Identities are only dropped
+//! around operands known to be numbers, since strings, lists and other values either
+//! concatenate or fail where a number would not: `"a" + 0` is kept as it is.
+//!
+//! A subexpression is only removed when evaluating it can never fail, provided every
+//! variable it reads is bound: `x - x` becomes `0`, but `1 / y - 1 / y` is kept because it
+//! fails for `y = 0`. Evaluating against a context missing `x` reports `x` as unknown
+//! before simplifying and gives `0` after. For the same reason `x / x`
+//! is only collapsed when [`SimplifyConfig::with_assume_nonzero`] is enabled. Function
+//! calls are never folded since a registered function may shadow a built-in, and calls
+//! to anything but a built-in are never dropped.
+
+use crate::expression::{Expression, expr};
+use crate::functions::builtin;
+use crate::parsing::BinaryOperator;
+use crate::value::Value;
+
+/// Upper bound on simplification passes when looking for a fixed point
+const MAX_PASSES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SimplifyConfig {
+    assume_nonzero: bool,
+}
+
+impl SimplifyConfig {
+    /// Treats every divisor as non-zero, allowing `x / x` to become `1` and `0 / x` to become `0`
+    pub fn with_assume_nonzero(mut self, assume_nonzero: bool) -> Self {
+        self.assume_nonzero = assume_nonzero;
+        self
+    }
+}
+
+impl Expression {
+    /// Simplifies for evaluation against contexts that bind every variable to a number,
+    /// see the [module documentation](self)
+    pub fn simplify(&self) -> Expression {
+        self.simplify_with(SimplifyConfig::default())
+    }
+
+    /// Repeatedly simplifies until the tree stops changing
+    pub fn simplify_with(&self, config: SimplifyConfig) -> Expression {
+        let simplifier = Simplifier {
+            config,
+            non_numeric: Vec::new(),
+        };
+        let mut current = self.clone();
+        for _ in 0..MAX_PASSES {
+            let next = simplifier.simplify(&current);
+            if next == current {
+                break;
+            }
+            current = next;
+        }
+
+        current
+    }
+}
+
+/// Constant and `coefficient * term` parts of a sum
+#[derive(Default)]
+struct Terms {
+    constant: f64,
+    terms: Vec<(f64, Expression)>,
+}
+
+/// Coefficient and `base ^ exponent` parts of a product
+struct Factors {
+    coefficient: f64,
+    factors: Vec<(Expression, f64)>,
+}
+
+#[derive(Clone)]
+struct Simplifier {
+    config: SimplifyConfig,
+    /// Names bound by an enclosing `let` to a value that may not be a number
+    non_numeric: Vec<String>,
+}
+
+impl Simplifier {
+    /// The simplifier for the body of a binding of `name` to `value`
+    fn scope(&self, name: &str, value: &Expression) -> Simplifier {
+        let numeric = self.is_numeric(value);
+        let mut inner = self.clone();
+        inner.non_numeric.retain(|bound| bound != name);
+        if !numeric {
+            inner.non_numeric.push(name.to_string());
+        }
+        inner
+    }
+
+    /// Whether `expr` gives a number whenever it evaluates without failing, so that an
+    /// arithmetic identity around it may be dropped without hiding a type error
+    fn is_numeric(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Number(_) | Expression::Call { .. } => true,
+            Expression::Variable(name) => !self.non_numeric.contains(name),
+            Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Divide(a, b)
+            | Expression::Power(a, b) => self.is_numeric(a) && self.is_numeric(b),
+            Expression::Negate(a) => self.is_numeric(a),
+            Expression::Conditional {
+                then, otherwise, ..
+            } => self.is_numeric(then) && self.is_numeric(otherwise),
+            Expression::Let { name, value, body } | Expression::Assign { name, value, body } => {
+                self.scope(name, value).is_numeric(body)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether evaluating `expr` always gives a number without failing, so that it may be
+    /// dropped from an arithmetic expression
+    fn is_total(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Number(_) => true,
+            Expression::Variable(_) => self.is_numeric(expr),
+            // Give values that arithmetic rejects
+            Expression::Bool(_)
+            | Expression::String(_)
+            | Expression::Null
+            | Expression::List(_)
+            | Expression::Less(..)
+            | Expression::LessEqual(..)
+            | Expression::Greater(..)
+            | Expression::GreaterEqual(..)
+            | Expression::Equal(..)
+            | Expression::NotEqual(..)
+            | Expression::And(..)
+            | Expression::Or(..)
+            | Expression::Not(_)
+            | Expression::Error => false,
+            // Unknown functions and arity mismatches are the only ways a call can fail
+            Expression::Call { name, args } => {
+                builtin(name).is_some_and(|f| f.arity.accepts(args.len()))
+                    && args.iter().all(|arg| self.is_total(arg))
+            }
+            Expression::Divide(a, b) => {
+                self.config.assume_nonzero && self.is_total(a) && self.is_total(b)
+            }
+            Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Power(a, b) => self.is_total(a) && self.is_total(b),
+            Expression::Negate(a) => self.is_total(a),
+            Expression::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => self.is_total(condition) && self.is_total(then) && self.is_total(otherwise),
+            Expression::Let { name, value, body } | Expression::Assign { name, value, body } => {
+                self.is_total(value) && self.scope(name, value).is_total(body)
+            }
+        }
+    }
+
+    fn simplify(&self, expr: &Expression) -> Expression {
+        match expr {
+            Expression::Number(_)
+            | Expression::Bool(_)
+            | Expression::String(_)
+            | Expression::Null
+            | Expression::Variable(_)
+            | Expression::Error => expr.clone(),
+            Expression::List(items) => {
+                expr::list(items.iter().map(|item| self.simplify(item)).collect())
+            }
+            Expression::Add(a, b) | Expression::Subtract(a, b) => {
+                let mut terms = Terms::default();
+                self.collect_terms(expr, 1.0, &mut terms);
+                if terms.terms.iter().all(|(_, term)| self.is_numeric(term)) {
+                    return self.build_sum(terms);
+                }
+                // Other types concatenate or fail, so only the operands are simplified
+                let build = match expr {
+                    Expression::Add(..) => expr::add,
+                    _ => expr::subtract,
+                };
+                build(self.simplify(a), self.simplify(b))
+            }
+            Expression::Multiply(a, b) => {
+                let mut factors = Factors {
+                    coefficient: 1.0,
+                    factors: Vec::new(),
+                };
+                self.collect_factors(expr, &mut factors);
+                if factors
+                    .factors
+                    .iter()
+                    .all(|(base, _)| self.is_numeric(base))
+                {
+                    self.build_product(factors)
+                } else {
+                    expr::multiply(self.simplify(a), self.simplify(b))
+                }
+            }
+            Expression::Divide(a, b) => self.simplify_quotient(a, b),
+            Expression::Power(a, b) => self.simplify_power(a, b),
+            Expression::Negate(a) => match self.simplify(a) {
+                Expression::Number(n) => expr::number(-n),
+                Expression::Negate(inner) if self.is_numeric(&inner) => *inner,
+                a => expr::negate(a),
+            },
+            Expression::Less(a, b) => self.simplify_comparison(a, b, expr::less, |a, b| {
+                Value::compare(BinaryOperator::Less, a, b)
+            }),
+            Expression::LessEqual(a, b) => {
+                self.simplify_comparison(a, b, expr::less_equal, |a, b| {
+                    Value::compare(BinaryOperator::LessEqual, a, b)
+                })
+            }
+            Expression::Greater(a, b) => self.simplify_comparison(a, b, expr::greater, |a, b| {
+                Value::compare(BinaryOperator::Greater, a, b)
+            }),
+            Expression::GreaterEqual(a, b) => {
+                self.simplify_comparison(a, b, expr::greater_equal, |a, b| {
+                    Value::compare(BinaryOperator::GreaterEqual, a, b)
+                })
+            }
+            Expression::Equal(a, b) => {
+                self.simplify_comparison(a, b, expr::equal, |a, b| Some(a.equals(b)))
+            }
+            Expression::NotEqual(a, b) => {
+                self.simplify_comparison(a, b, expr::not_equal, |a, b| Some(!a.equals(b)))
+            }
+            // The right operand is never evaluated when the left decides the result
+            Expression::And(a, b) => match self.simplify(a) {
+                a if condition(&a) == Some(false) => expr::boolean(false),
+                a => self.simplify_comparison(&a, b, expr::and, |a, b| {
+                    Some(a.as_condition()? && b.as_condition()?)
+                }),
+            },
+            Expression::Or(a, b) => match self.simplify(a) {
+                a if condition(&a) == Some(true) => expr::boolean(true),
+                a => self.simplify_comparison(&a, b, expr::or, |a, b| {
+                    Some(a.as_condition()? || b.as_condition()?)
+                }),
+            },
+            Expression::Not(a) => {
+                let a = self.simplify(a);
+                match condition(&a) {
+                    Some(c) => expr::boolean(!c),
+                    None => expr::not(a),
+                }
+            }
+            Expression::Conditional {
+                condition: test,
+                then,
+                otherwise,
+            } => {
+                let test = self.simplify(test);
+                match condition(&test) {
+                    Some(true) => self.simplify(then),
+                    Some(false) => self.simplify(otherwise),
+                    None => expr::conditional(test, self.simplify(then), self.simplify(otherwise)),
+                }
+            }
+            Expression::Call { name, args } => {
+                expr::call(name, args.iter().map(|arg| self.simplify(arg)).collect())
+            }
+            Expression::Let { name, value, body } => {
+                let value = self.simplify(value);
+                let body = self.scope(name, &value).simplify(body);
+                if !body.depends_on(name) && self.is_total(&value) {
+                    body
+                } else {
+                    expr::let_in(name, value, body)
+                }
+            }
+            // Kept even when unused, since executing the script stores the value
+            Expression::Assign { name, value, body } => {
+                let value = self.simplify(value);
+                let body = self.scope(name, &value).simplify(body);
+                expr::assign(name, value, body)
+            }
+        }
+    }
+
+    /// Folds a comparison or logical operator whose operands simplify to literals, leaving
+    /// it in place when `compare` rejects their types so that evaluation still fails
+    fn simplify_comparison(
+        &self,
+        a: &Expression,
+        b: &Expression,
+        build: fn(Expression, Expression) -> Expression,
+        compare: fn(&Value, &Value) -> Option<bool>,
+    ) -> Expression {
+        let (a, b) = (self.simplify(a), self.simplify(b));
+        match (literal(&a), literal(&b)) {
+            (Some(x), Some(y)) => match compare(&x, &y) {
+                Some(result) => expr::boolean(result),
+                None => build(a, b),
+            },
+            _ => build(a, b),
+        }
+    }
+
+    fn collect_terms(&self, expr: &Expression, sign: f64, terms: &mut Terms) {
+        match expr {
+            Expression::Add(a, b) => {
+                self.collect_terms(a, sign, terms);
+                self.collect_terms(b, sign, terms);
+            }
+            Expression::Subtract(a, b) => {
+                self.collect_terms(a, sign, terms);
+                self.collect_terms(b, -sign, terms);
+            }
+            Expression::Negate(a) => self.collect_terms(a, -sign, terms),
+            _ => match self.simplify(expr) {
+                simplified @ (Expression::Add(..)
+                | Expression::Subtract(..)
+                | Expression::Negate(_)) => self.collect_terms(&simplified, sign, terms),
+                simplified => match split_coefficient(simplified) {
+                    (c, None) => terms.constant += sign * c,
+                    (c, Some(term)) => match terms.terms.iter_mut().find(|(_, t)| *t == term) {
+                        Some((coefficient, _)) => *coefficient += sign * c,
+                        None => terms.terms.push((sign * c, term)),
+                    },
+                },
+            },
+        }
+    }
+
+    fn build_sum(&self, terms: Terms) -> Expression {
+        let mut result: Option<Expression> = None;
+        for (coefficient, term) in terms.terms {
+            if coefficient == 0.0 && self.is_total(&term) {
+                continue;
+            }
+
+            let magnitude = scale(coefficient.abs(), term);
+            result = Some(match result {
+                None if coefficient < 0.0 => expr::negate(magnitude),
+                None => magnitude,
+                Some(sum) if coefficient < 0.0 => expr::subtract(sum, magnitude),
+                Some(sum) => expr::add(sum, magnitude),
+            });
+        }
+
+        match result {
+            None => expr::number(terms.constant),
+            Some(sum) if terms.constant < 0.0 => expr::subtract(sum, expr::number(-terms.constant)),
+            Some(sum) if terms.constant > 0.0 => expr::add(sum, expr::number(terms.constant)),
+            Some(sum) => sum,
+        }
+    }
+
+    fn collect_factors(&self, expr: &Expression, factors: &mut Factors) {
+        match expr {
+            Expression::Multiply(a, b) => {
+                self.collect_factors(a, factors);
+                self.collect_factors(b, factors);
+            }
+            Expression::Negate(a) => {
+                factors.coefficient = -factors.coefficient;
+                self.collect_factors(a, factors);
+            }
+            _ => {
+                let (base, exponent) = match self.simplify(expr) {
+                    Expression::Number(n) => {
+                        factors.coefficient *= n;
+                        return;
+                    }
+                    simplified @ (Expression::Multiply(..) | Expression::Negate(_)) => {
+                        return self.collect_factors(&simplified, factors);
+                    }
+                    Expression::Power(base, exponent) => match *exponent {
+                        Expression::Number(n) if is_positive_integer(n) => (*base, n),
+                        exponent => (expr::power(*base, exponent), 1.0),
+                    },
+                    simplified => (simplified, 1.0),
+                };
+
+                // x^m * x^n = x^(m + n) holds for every x when m and n are positive integers
+                match factors.factors.iter_mut().find(|(b, _)| *b == base) {
+                    Some((_, existing)) => *existing += exponent,
+                    None => factors.factors.push((base, exponent)),
+                }
+            }
+        }
+    }
+
+    fn build_product(&self, mut factors: Factors) -> Expression {
+        let coefficient = factors.coefficient;
+        if coefficient == 0.0 && factors.factors.iter().all(|(b, _)| self.is_total(b)) {
+            return expr::number(0.0);
+        }
+
+        // Ordered by their printed form, so that `x * y` and `y * x` are the same term
+        factors
+            .factors
+            .sort_by_cached_key(|(base, _)| base.to_string());
+        let product = factors
+            .factors
+            .into_iter()
+            .map(|(base, exponent)| match exponent {
+                1.0 => base,
+                n => expr::power(base, expr::number(n)),
+            })
+            .reduce(expr::multiply);
+
+        match product {
+            None => expr::number(coefficient),
+            Some(product) if coefficient == 1.0 => product,
+            Some(product) if coefficient == -1.0 => expr::negate(product),
+            Some(product) => scale(coefficient, product),
+        }
+    }
+
+    fn simplify_quotient(&self, a: &Expression, b: &Expression) -> Expression {
+        let (numerator, denominator) = (self.simplify(a), self.simplify(b));
+        let assume_nonzero = self.config.assume_nonzero;
+
+        match (numerator, denominator) {
+            (numerator, Expression::Number(1.0)) if self.is_numeric(&numerator) => numerator,
+            (Expression::Number(x), Expression::Number(y)) if y != 0.0 => expr::number(x / y),
+            (numerator, denominator)
+                if assume_nonzero && numerator == denominator && self.is_total(&numerator) =>
+            {
+                expr::number(1.0)
+            }
+            (Expression::Number(0.0), denominator)
+                if assume_nonzero && self.is_total(&denominator) =>
+            {
+                expr::number(0.0)
+            }
+            (numerator, denominator) => expr::divide(numerator, denominator),
+        }
+    }
+
+    fn simplify_power(&self, a: &Expression, b: &Expression) -> Expression {
+        let (base, exponent) = (self.simplify(a), self.simplify(b));
+
+        match (base, exponent) {
+            (Expression::Number(x), Expression::Number(y)) => expr::number(x.powf(y)),
+            // powf(x, 0) and powf(1, y) are 1 for every x and y, including NaN
+            (base, Expression::Number(0.0)) if self.is_total(&base) => expr::number(1.0),
+            (Expression::Number(1.0), exponent) if self.is_total(&exponent) => expr::number(1.0),
+            (base, Expression::Number(1.0)) if self.is_numeric(&base) => base,
+            (Expression::Power(inner, m), Expression::Number(n)) => match *m {
+                Expression::Number(m) if is_positive_integer(m) && is_positive_integer(n) => {
+                    expr::power(*inner, expr::number(m * n))
+                }
+                m => expr::power(expr::power(*inner, m), expr::number(n)),
+            },
+            (base, exponent) => expr::power(base, exponent),
+        }
+    }
+}
+
+fn is_positive_integer(n: f64) -> bool {
+    n >= 1.0 && n.fract() == 0.0
+}
+
+/// Splits the leading numeric factor off a left-nested product, `None` for a bare number
+fn split_coefficient(expr: Expression) -> (f64, Option<Expression>) {
+    match expr {
+        Expression::Number(n) => (n, None),
+        Expression::Multiply(a, b) => match split_coefficient(*a) {
+            (c, None) => (c, Some(*b)),
+            (c, Some(rest)) => (c, Some(expr::multiply(rest, *b))),
+        },
+        expr => (1.0, Some(expr)),
+    }
+}
+
+/// Inverse of [`split_coefficient`]: puts `coefficient` at the front of a product
+fn scale(coefficient: f64, term: Expression) -> Expression {
+    match term {
+        _ if coefficient == 1.0 => term,
+        Expression::Multiply(a, b) => expr::multiply(scale(coefficient, *a), *b),
+        term => expr::multiply(expr::number(coefficient), term),
+    }
+}
+
+/// The value of a literal, or of a list built only from literals
+fn literal(expr: &Expression) -> Option<Value> {
+    match expr {
+        Expression::Number(n) => Some(Value::Number(*n)),
+        Expression::Bool(b) => Some(Value::Bool(*b)),
+        Expression::String(text) => Some(Value::String(text.clone())),
+        Expression::Null => Some(Value::Null),
+        Expression::List(items) => items
+            .iter()
+            .map(literal)
+            .collect::<Option<_>>()
+            .map(Value::List),
+        _ => None,
+    }
+}
+
+/// Whether a literal counts as true in a condition
+fn condition(expr: &Expression) -> Option<bool> {
+    literal(expr)?.as_condition()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expression_macro::expr;
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+    use std::mem::discriminant;
+
+    fn simplified(source: &str) -> String {
+        Expression::parse(source).unwrap().simplify().to_string()
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        assert_eq!(simplified("2 * 3"), "6");
+        assert_eq!(simplified("2 ^ 3 + 1 - 4 / 2"), "7");
+        assert_eq!(simplified("-(2 + 3)"), "-5");
+        assert_eq!(simplified("x * (2 + 3)"), "5 * x");
+        assert_eq!(simplified("sqrt(2 * 8)"), "sqrt(16)");
+        assert_eq!(simplified("x / 0"), "x / 0");
+    }
+
+    #[test]
+    fn test_identity_elimination() {
+        assert_eq!(simplified("x * 1"), "x");
+        assert_eq!(simplified("1 * x"), "x");
+        assert_eq!(simplified("0 + y"), "y");
+        assert_eq!(simplified("y - 0"), "y");
+        assert_eq!(simplified("0 - y"), "-y");
+        assert_eq!(simplified("x ^ 1"), "x");
+        assert_eq!(simplified("x ^ 0"), "1");
+        assert_eq!(simplified("1 ^ x"), "1");
+        assert_eq!(simplified("x / 1"), "x");
+        assert_eq!(simplified("x * 0 + y"), "y");
+        assert_eq!(simplified("--x"), "x");
+    }
+
+    #[test]
+    fn test_like_terms() {
+        assert_eq!(simplified("x - x"), "0");
+        assert_eq!(simplified("2 * x + 3 * x"), "5 * x");
+        assert_eq!(simplified("x + y + x - 2 * y"), "2 * x - y");
+        assert_eq!(simplified("x * y * 2 + y * 3 * x"), "5 * x * y");
+        assert_eq!(simplified("y * x - x * y"), "0");
+        assert_eq!(simplified("x * x * x"), "x ^ 3");
+        assert_eq!(simplified("x ^ 2 * x ^ 3 * 2"), "2 * x ^ 5");
+        assert_eq!(simplified("(x ^ 2) ^ 3"), "x ^ 6");
+        assert_eq!(simplified("-x * -y"), "x * y");
+        assert_eq!(simplified("1 + x + 2"), "x + 3");
+    }
+
+    #[test]
+    fn test_domain_caveats() {
+        // Dropping these would hide a division by zero or an unknown function
+        assert_eq!(simplified("1 / y - 1 / y"), "0 * (1 / y)");
+        assert_eq!(simplified("f(x) * 0"), "0 * f(x)");
+        assert_eq!(simplified("sin(x) * 0"), "0");
+        assert_eq!(simplified("sin(x, y) * 0"), "0 * sin(x, y)");
+        assert_eq!(simplified("x / x"), "x / x");
+        assert_eq!(simplified("0 / x"), "0 / x");
+
+        // Variables are assumed to be bound, so an unbound one is no longer reported
+        let empty: HashMap<String, f64> = HashMap::new();
+        assert_eq!(
+            expr!("x - x").evaluate(&empty),
+            Err(crate::error::EvalError::UnknownVariable {
+                name: "x".to_string()
+            })
+        );
+        assert_eq!(expr!("x - x").simplify().evaluate(&empty), Ok(0.0));
+
+        let config = SimplifyConfig::default().with_assume_nonzero(true);
+        assert_eq!(expr!("x / x").simplify_with(config), expr::number(1.0));
+        assert_eq!(expr!("0 / x").simplify_with(config), expr::number(0.0));
+        assert_eq!(
+            expr!("1 / y - 1 / y").simplify_with(config),
+            expr::number(0.0)
+        );
+    }
+
+    #[test]
+    fn test_logic_folding() {
+        assert_eq!(simplified("1 < 2 && 3 == 3"), "true");
+        assert_eq!(simplified("!(2 >= 3)"), "true");
+        assert_eq!(simplified("1 + 1 == 2 ? x * 1 : 1 / 0"), "x");
+        assert_eq!(simplified("0 && 1 / 0"), "false");
+        assert_eq!(simplified("2 || f(x)"), "true");
+        assert_eq!(simplified("x > 0 + 1 ? x : y"), "x > 1 ? x : y");
+        // Dropping the left operand would change `&&` from a Bool to the right's value
+        assert_eq!(simplified("true && x"), "true && x");
+        assert_eq!(
+            simplified(r#""a" == "a" && [1, null] != [1, null]"#),
+            "false"
+        );
+        // Folding these would hide the type error evaluation reports
+        assert_eq!(simplified(r#""a" < 1"#), r#""a" < 1"#);
+        assert_eq!(simplified("(x < 1) * 0"), "(x < 1) * 0");
+    }
+
+    #[test]
+    fn test_let_simplification() {
+        assert_eq!(
+            simplified("let r = x * x + 0; r * 1 + r"),
+            "let r = x ^ 2; 2 * r"
+        );
+        assert_eq!(simplified("let r = x * 2; y + 0"), "y");
+        // The unused value would fail to evaluate for y = 0
+        assert_eq!(simplified("let r = 1 / y; 2"), "let r = 1 / y; 2");
+        // Executing the script stores an assigned value even if it is never read
+        assert_eq!(simplified("x = x * 1 + 0; y * 1"), "x = x; y");
+    }
+
+    #[test]
+    fn test_simplify_derivative() {
+        let derivative = expr!("x ^ 3 + 2 * x").derivative("x");
+        assert_eq!(derivative.simplify().to_string(), "3 * x ^ 2 + 2");
+
+        let derivative = expr!("sin(x) * y").derivative("x");
+        assert_eq!(derivative.simplify().to_string(), "cos(x) * y");
+    }
+
+    #[test]
+    fn test_simplify_preserves_evaluation() {
+        let vars = HashMap::from([("x".to_string(), 1.7), ("y".to_string(), -0.4)]);
+        for source in [
+            "x * 1 + 0 * y - (x - x)",
+            "(x + 2) * (x + 2) * 3 / (1 + 2)",
+            "x ^ 2 * x - x * x * x + y",
+            "sqrt(x * 1) + sin(0 + y) / 1",
+            "-(x - y) - -(y - x)",
+            "2 ^ x ^ 1 * 2 ^ 1",
+            "(x > 1 && 2 > 1 ? x : 0) + (y < 0 ? y * 1 : 0)",
+            "let x = x * 2 + 0; let z = x - y; x * z + z * x",
+        ] {
+            let e = Expression::parse(source).unwrap();
+            let expected = e.evaluate(&vars).unwrap();
+            let actual = e.simplify().evaluate(&vars).unwrap();
+            assert!((expected - actual).abs() < 1e-12, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_simplify_preserves_type_errors() {
+        let vars = HashMap::from([("x".to_string(), Value::Number(1.5))]);
+        // Errors are compared by kind, as they name the subexpression that failed
+        let outcome = |e: &Expression| e.evaluate_value(&vars).map_err(|e| discriminant(&e));
+        for source in [
+            r#""a" + 0"#,
+            r#""a" * 1"#,
+            "[1] * 1",
+            "null + 0",
+            r#"--"a""#,
+            "(1 < 2) + 0",
+            r#""a" / 1 - 0"#,
+            "[x] ^ 1",
+            r#""a" + "a" - "a" * 0"#,
+            "[1] + [x] + []",
+            r#"let s = "a"; s + 0 == s * 1"#,
+            r#"let s = "a"; let s = 2; s * 1 + 0"#,
+            r#"x > 1 ? 1 : "b" * 1"#,
+        ] {
+            let e = Expression::parse(source).unwrap();
+            assert_eq!(outcome(&e.simplify()), outcome(&e), "{}", source);
+        }
+        assert_eq!(simplified(r#""a" + 0"#), r#""a" + 0"#);
+        assert_eq!(simplified(r#"--"a""#), r#"--"a""#);
+        assert_eq!(simplified("(1 < 2) + 0"), "true + 0");
+    }
+
+    #[test]
+    fn test_simplify_reaches_fixed_point() {
+        for source in [
+            "x + y + x - 2 * y",
+            "2 * x * 3 * y + x * y",
+            "(x ^ 2) ^ 3 * x",
+        ] {
+            let once = Expression::parse(source).unwrap().simplify();
+            assert_eq!(once.simplify(), once);
+        }
+    }
+
+    fn arb_polynomial() -> impl Strategy<Value = Expression> {
+        let leaf = prop_oneof![
+            (0..5).prop_map(|n| Expression::Number(n as f64)),
+            prop_oneof![Just("x"), Just("y")].prop_map(expr::variable),
+        ];
+
+        leaf.prop_recursive(5, 32, 2, |inner| {
+            let pair = || (inner.clone(), inner.clone());
+            prop_oneof![
+                pair().prop_map(|(a, b)| expr::add(a, b)),
+                pair().prop_map(|(a, b)| expr::subtract(a, b)),
+                pair().prop_map(|(a, b)| expr::multiply(a, b)),
+                (inner.clone(), 0..4).prop_map(|(a, n)| expr::power(a, expr::number(n as f64))),
+                inner.clone().prop_map(expr::negate),
+            ]
+        })
+    }
+
+    // Bounds every intermediate value, so rounding error can be compared against it
+    fn magnitude(e: &Expression, vars: &HashMap<String, f64>) -> f64 {
+        match e {
+            Expression::Number(n) => n.abs(),
+            Expression::Variable(name) => vars[name].abs(),
+            Expression::Add(a, b) | Expression::Subtract(a, b) => {
+                magnitude(a, vars) + magnitude(b, vars)
+            }
+            Expression::Multiply(a, b) => magnitude(a, vars) * magnitude(b, vars),
+            Expression::Power(a, b) => magnitude(a, vars).powf(b.evaluate(vars).unwrap()),
+            Expression::Negate(a) => magnitude(a, vars),
+            _ => unreachable!(),
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_simplify_preserves_evaluation(e in arb_polynomial(), x in -3.0..3.0f64, y in -3.0..3.0f64) {
+            let vars = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+            let simplified = e.simplify();
+
+            let expected = e.evaluate(&vars).unwrap();
+            let actual = simplified.evaluate(&vars).unwrap();
+            let scale = magnitude(&e, &vars).max(magnitude(&simplified, &vars)).max(1.0);
+            prop_assert!((expected - actual).abs() <= 1e-9 * scale, "{} => {}", e, simplified);
+        }
+    }
+}
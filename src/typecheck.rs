@@ -0,0 +1,525 @@
+//! Static type inference against a declared [`Schema`].
+//!
+//! Checking a formula finds the type errors [`Expression::evaluate_value`] would report,
+//! without evaluating it, and finds them in branches evaluation would skip. The rules are
+//! the coercion rules in [`crate::value`], with two additions for the cases where the
+//! type would only be known at run time:
+//!
+//! - Both branches of `?:` must have the same type, except that an `Integer` and a
+//!   `Number` give a `Number`.
+//! - An `Integer` result may still widen to a `Number` on overflow.
+//!
+//! Division by zero is not a type error, so a well-typed formula can still fail to
+//! evaluate.
+//!
+//! [`Expression::evaluate_value`]: crate::expression::Expression::evaluate_value
+
+use crate::error::{CheckError, EvalError};
+use crate::expression::Expression;
+use crate::functions::{Arity, builtin};
+use crate::parsing::BinaryOperator;
+use crate::value::Type;
+use expression_grammar::syntax::Syntax;
+use std::collections::HashMap;
+
+/// How many arguments a function takes.
+///
+/// Functions take and return numbers, as registered with
+/// [`FunctionRegistry`](crate::functions::FunctionRegistry), so every parameter and the
+/// result are `Number`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    arity: Arity,
+}
+
+impl Signature {
+    /// A function taking exactly `params` arguments
+    pub fn new(params: usize) -> Self {
+        Signature {
+            arity: Arity::Exact(params),
+        }
+    }
+
+    /// A function taking `min_params` or more arguments
+    pub fn variadic(min_params: usize) -> Self {
+        Signature {
+            arity: Arity::AtLeast(min_params),
+        }
+    }
+
+    pub fn arity(&self) -> Arity {
+        self.arity
+    }
+}
+
+/// The declared types of a formula's variables and functions.
+///
+/// Declared functions take priority over built-ins of the same name.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    variables: HashMap<String, Type>,
+    functions: HashMap<String, Signature>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Schema::default()
+    }
+
+    pub fn with_variable(mut self, name: &str, variable_type: Type) -> Self {
+        self.variables.insert(name.to_string(), variable_type);
+        self
+    }
+
+    pub fn with_function(mut self, name: &str, signature: Signature) -> Self {
+        self.functions.insert(name.to_string(), signature);
+        self
+    }
+
+    /// Infers the type `expression` evaluates to, or reports every type error in it.
+    ///
+    /// Errors are the ones evaluation would report, in the order they occur from left to
+    /// right. A subexpression with an error is not checked against its parent, so one
+    /// mistake is reported once.
+    pub fn infer(&self, expression: &Expression) -> Result<Type, Vec<EvalError>> {
+        let mut checker = Checker::new(self);
+        match checker.infer(expression) {
+            Some(result) if checker.errors.is_empty() => Ok(result),
+            _ => Err(checker.errors.into_iter().map(|(error, _)| error).collect()),
+        }
+    }
+
+    /// Parses `source` and infers its type, locating each type error in `source`
+    pub fn check(&self, source: &str) -> Result<Type, Vec<CheckError>> {
+        let syntax =
+            expression_grammar::parse(source).map_err(|error| vec![CheckError::Parse(error)])?;
+        let mut checker = Checker::new(self);
+        match checker.infer(&Expression::from(syntax.clone())) {
+            Some(result) if checker.errors.is_empty() => Ok(result),
+            _ => Err(checker
+                .errors
+                .into_iter()
+                .map(|(error, path)| {
+                    // The expression was converted from `syntax`, so the path exists in both
+                    let node = path
+                        .iter()
+                        .fold(&syntax, |node: &Syntax, &i| node.children()[i]);
+                    CheckError::Type {
+                        error,
+                        span: node.span,
+                    }
+                })
+                .collect()),
+        }
+    }
+}
+
+fn is_numeric(t: Type) -> bool {
+    matches!(t, Type::Number | Type::Integer)
+}
+
+/// Whether a value of type `found` may be used where `expected` is
+fn accepts(expected: Type, found: Type) -> bool {
+    expected == found || (expected == Type::Number && found == Type::Integer)
+}
+
+struct Checker<'a> {
+    schema: &'a Schema,
+    /// Types of the enclosing `let` bindings, innermost last. The type is `None` if the
+    /// value had an error, so uses of the binding do not report it again
+    bindings: Vec<(String, Option<Type>)>,
+    /// Child indices from the root to the subexpression being checked
+    path: Vec<usize>,
+    errors: Vec<(EvalError, Vec<usize>)>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(schema: &'a Schema) -> Self {
+        Checker {
+            schema,
+            bindings: Vec::new(),
+            path: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Records an error about the child at `index` of the current subexpression, or about
+    /// the subexpression itself if `index` is `None`
+    fn error(&mut self, error: EvalError, index: Option<usize>) {
+        let mut path = self.path.clone();
+        path.extend(index);
+        self.errors.push((error, path));
+    }
+
+    fn child(&mut self, index: usize, child: &Expression) -> Option<Type> {
+        self.path.push(index);
+        let result = self.infer(child);
+        self.path.pop();
+        result
+    }
+
+    /// The type of `expression`, or `None` if an error was recorded for it
+    fn infer(&mut self, expression: &Expression) -> Option<Type> {
+        match expression {
+            Expression::Number(_) => Some(Type::Number),
+            Expression::Bool(_) => Some(Type::Bool),
+            Expression::String(_) => Some(Type::String),
+            Expression::Null => Some(Type::Null),
+            Expression::List(items) => {
+                let mut valid = true;
+                for (i, item) in items.iter().enumerate() {
+                    valid &= self.child(i, item).is_some();
+                }
+                valid.then_some(Type::List)
+            }
+            Expression::Variable(name) => {
+                if let Some((_, bound)) =
+                    self.bindings.iter().rev().find(|(bound, _)| bound == name)
+                {
+                    return *bound;
+                }
+                let variable_type = self.schema.variables.get(name).copied();
+                if variable_type.is_none() {
+                    let name = name.clone();
+                    self.error(EvalError::UnknownVariable { name }, None);
+                }
+                variable_type
+            }
+            Expression::Add(a, b) => self.binary(expression, BinaryOperator::Add, a, b),
+            Expression::Subtract(a, b) => self.binary(expression, BinaryOperator::Subtract, a, b),
+            Expression::Multiply(a, b) => self.binary(expression, BinaryOperator::Multiply, a, b),
+            Expression::Divide(a, b) => self.binary(expression, BinaryOperator::Divide, a, b),
+            Expression::Power(a, b) => self.binary(expression, BinaryOperator::Power, a, b),
+            Expression::Less(a, b) => self.binary(expression, BinaryOperator::Less, a, b),
+            Expression::LessEqual(a, b) => self.binary(expression, BinaryOperator::LessEqual, a, b),
+            Expression::Greater(a, b) => self.binary(expression, BinaryOperator::Greater, a, b),
+            Expression::GreaterEqual(a, b) => {
+                self.binary(expression, BinaryOperator::GreaterEqual, a, b)
+            }
+            Expression::Equal(a, b) | Expression::NotEqual(a, b) => {
+                let (a, b) = (self.child(0, a), self.child(1, b));
+                a.and(b).map(|_| Type::Bool)
+            }
+            Expression::And(a, b) | Expression::Or(a, b) => {
+                let (a, b) = (self.condition(0, a), self.condition(1, b));
+                a.and(b).map(|_| Type::Bool)
+            }
+            Expression::Not(operand) => self.condition(0, operand).map(|_| Type::Bool),
+            Expression::Negate(operand) => {
+                let found = self.child(0, operand)?;
+                if !is_numeric(found) {
+                    let expression = (**operand).clone();
+                    self.type_mismatch(Type::Number, found, expression, 0);
+                    return None;
+                }
+                Some(found)
+            }
+            Expression::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let condition = self.condition(0, condition);
+                let (then_type, otherwise_type) = (self.child(1, then), self.child(2, otherwise));
+                let (then_type, otherwise_type) = (then_type?, otherwise_type?);
+                let result = if then_type == otherwise_type {
+                    then_type
+                } else if is_numeric(then_type) && is_numeric(otherwise_type) {
+                    Type::Number
+                } else {
+                    let expression = (**otherwise).clone();
+                    self.type_mismatch(then_type, otherwise_type, expression, 2);
+                    return None;
+                };
+                condition.map(|_| result)
+            }
+            Expression::Call { name, args } => self.call(name, args),
+            Expression::Let { name, value, body } | Expression::Assign { name, value, body } => {
+                let value = self.child(0, value);
+                self.bindings.push((name.clone(), value));
+                let body = self.child(1, body);
+                self.bindings.pop();
+                value.and(body)
+            }
+            Expression::Error => {
+                self.error(EvalError::InvalidExpression, None);
+                None
+            }
+        }
+    }
+
+    fn type_mismatch(&mut self, expected: Type, found: Type, expression: Expression, index: usize) {
+        let error = EvalError::TypeMismatch {
+            expected,
+            found,
+            expression,
+        };
+        self.error(error, Some(index));
+    }
+
+    /// Checks an operand of `&&`, `||`, `!` or `?:`
+    fn condition(&mut self, index: usize, operand: &Expression) -> Option<Type> {
+        let found = self.child(index, operand)?;
+        if !matches!(found, Type::Bool | Type::Number | Type::Integer) {
+            self.type_mismatch(Type::Bool, found, operand.clone(), index);
+            return None;
+        }
+        Some(found)
+    }
+
+    fn binary(
+        &mut self,
+        expression: &Expression,
+        operator: BinaryOperator,
+        a: &Expression,
+        b: &Expression,
+    ) -> Option<Type> {
+        let (lhs, rhs) = (self.child(0, a), self.child(1, b));
+        let (lhs, rhs) = (lhs?, rhs?);
+
+        let result = match (operator, lhs, rhs) {
+            (
+                BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Multiply,
+                Type::Integer,
+                Type::Integer,
+            ) => Some(Type::Integer),
+            (BinaryOperator::Add, Type::String, Type::String) => Some(Type::String),
+            (BinaryOperator::Add, Type::List, Type::List) => Some(Type::List),
+            (
+                BinaryOperator::Less
+                | BinaryOperator::LessEqual
+                | BinaryOperator::Greater
+                | BinaryOperator::GreaterEqual,
+                _,
+                _,
+            ) => {
+                let ordered = (is_numeric(lhs) && is_numeric(rhs))
+                    || (lhs == Type::String && rhs == Type::String);
+                ordered.then_some(Type::Bool)
+            }
+            _ => (is_numeric(lhs) && is_numeric(rhs)).then_some(Type::Number),
+        };
+
+        if result.is_none() {
+            let error = EvalError::InvalidOperands {
+                operator,
+                lhs,
+                rhs,
+                expression: expression.clone(),
+            };
+            self.error(error, None);
+        }
+        result
+    }
+
+    fn call(&mut self, name: &str, args: &[Expression]) -> Option<Type> {
+        let expected = match self.schema.functions.get(name) {
+            Some(signature) => signature.arity(),
+            None => match builtin(name) {
+                Some(builtin) => builtin.arity,
+                None => {
+                    let name = name.to_string();
+                    self.error(EvalError::UnknownFunction { name }, None);
+                    return None;
+                }
+            },
+        };
+
+        if !expected.accepts(args.len()) {
+            let error = EvalError::ArityMismatch {
+                name: name.to_string(),
+                expected,
+                found: args.len(),
+            };
+            self.error(error, None);
+            return None;
+        }
+
+        let mut valid = true;
+        for (i, arg) in args.iter().enumerate() {
+            let Some(found) = self.child(i, arg) else {
+                valid = false;
+                continue;
+            };
+            if !accepts(Type::Number, found) {
+                self.type_mismatch(Type::Number, found, arg.clone(), i);
+                valid = false;
+            }
+        }
+        valid.then_some(Type::Number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::Span;
+    use crate::value::Value;
+    use expression_macro::expr;
+
+    fn schema() -> Schema {
+        Schema::new()
+            .with_variable("x", Type::Number)
+            .with_variable("n", Type::Integer)
+            .with_variable("active", Type::Bool)
+            .with_variable("name", Type::String)
+            .with_function("clamp", Signature::new(3))
+            .with_function("total", Signature::variadic(1))
+    }
+
+    #[test]
+    fn test_infer() {
+        let schema = schema();
+        for (source, expected) in [
+            ("x * 2 + sin(n)", Type::Number),
+            ("n * n - n", Type::Integer),
+            ("n / n", Type::Number),
+            ("-n", Type::Integer),
+            (r#"name + "!""#, Type::String),
+            ("[x, name] + []", Type::List),
+            ("null", Type::Null),
+            (r#"name < "m" && !active || x"#, Type::Bool),
+            ("name == x", Type::Bool),
+            ("active ? n : 0", Type::Number),
+            (r#"if(n > 1, "many", "one")"#, Type::String),
+            ("clamp(x, 0, n)", Type::Number),
+            ("total(n, x, n + n)", Type::Number),
+            ("max(n, x, 1)", Type::Number),
+            (
+                "let x = n * 2; let s = name; x > 1 ? s : name",
+                Type::String,
+            ),
+        ] {
+            assert_eq!(
+                schema.infer(&Expression::parse(source).unwrap()),
+                Ok(expected),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_infer_errors() {
+        let schema = schema();
+        assert_eq!(
+            schema.infer(&expr!("x + (active + x) * 2")),
+            Err(vec![EvalError::InvalidOperands {
+                operator: BinaryOperator::Add,
+                lhs: Type::Bool,
+                rhs: Type::Number,
+                expression: expr!("active + x"),
+            }])
+        );
+        // Unlike evaluation, every error is reported, including those in untaken branches
+        assert_eq!(
+            schema
+                .infer(&expr!("missing > 0 ? sqrt(name) : nope(x)"))
+                .unwrap_err()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "Variable 'missing' not found",
+                "Expected Number, found String",
+                "Unknown function 'nope'"
+            ]
+        );
+        assert_eq!(
+            schema.infer(&expr!("clamp(x, 1)")),
+            Err(vec![EvalError::ArityMismatch {
+                name: "clamp".to_string(),
+                expected: Arity::Exact(3),
+                found: 2
+            }])
+        );
+        assert_eq!(
+            schema.infer(&Expression::parse_recovering("x +").0),
+            Err(vec![EvalError::InvalidExpression])
+        );
+    }
+
+    #[test]
+    fn test_check_spans() {
+        let schema = schema();
+        let messages = |source: &str| {
+            schema
+                .check(source)
+                .unwrap_err()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            messages("x + (active + x) * 2"),
+            ["Cannot add Bool and Number at 4..16"]
+        );
+        assert_eq!(
+            messages(r#"(name + 1) + -name + (name + 1)"#),
+            [
+                "Cannot add String and Number at 0..10",
+                "Expected Number, found String at 14..18",
+                "Cannot add String and Number at 21..31"
+            ]
+        );
+        assert_eq!(
+            messages(r#"!name || active ? x : "none""#),
+            [
+                "Expected Bool, found String at 1..5",
+                "Expected Number, found String at 22..28"
+            ]
+        );
+        assert_eq!(
+            messages("total(x, name) + clamp()"),
+            [
+                "Expected Number, found String at 9..13",
+                "Function 'clamp' expects 3 arguments, got 0 at 17..24"
+            ]
+        );
+        assert_eq!(
+            schema.check("x +"),
+            Err(vec![CheckError::Parse(
+                crate::error::ParseError::UnexpectedEnd {
+                    span: Span::new(3, 3)
+                }
+            )])
+        );
+        assert_eq!(schema.check("[n, name] == []"), Ok(Type::Bool));
+        // The bad value is reported once, not again where the binding is used
+        assert_eq!(
+            messages("let s = name * 2\nlet t = s; -t + r"),
+            [
+                "Cannot multiply String and Number at 8..16",
+                "Variable 'r' not found at 33..34"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_well_typed_formulas_evaluate() {
+        let schema = schema();
+        let values = [
+            ("x", Value::Number(2.5)),
+            ("n", Value::Integer(4)),
+            ("active", Value::Bool(false)),
+            ("name", Value::from("ada")),
+        ];
+        for source in [
+            "n * n - n",
+            "active ? n : x",
+            r#"name + "!""#,
+            r#"!active && name > "a""#,
+            "max(n, x) / 2",
+        ] {
+            let e = Expression::parse(source).unwrap();
+            let value = e.evaluate_value(&values).unwrap();
+            assert_eq!(
+                schema.infer(&e).map(|t| accepts(t, value.type_of())),
+                Ok(true),
+                "{}",
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! Typed values produced by [`Expression::evaluate_value`].
+//!
+//! Formulas compute with [`Value`]s, converting between types only where listed here:
+//!
+//! - `Integer` widens to `Number` whenever the two meet, and for function arguments.
+//!   Number literals are always `Number`s, so integers come from the [`Context`].
+//! - `+`, `-` and `*` of two integers stay integers, widening to `Number` on overflow.
+//!   `/` and `^` always give a `Number`.
+//! - `+` also joins two strings or two lists.
+//! - `<`, `<=`, `>` and `>=` order numbers and strings, and give a `Bool`.
+//! - `==` and `!=` accept any two values. Numbers are equal by value whatever their type,
+//!   lists element by element, and values of other differing types are never equal.
+//! - Conditions of `&&`, `||`, `!` and `?:` take a `Bool`, or a number which is true when
+//!   non-zero (including NaN). `&&`, `||` and `!` give a `Bool`.
+//! - Function arguments must be numbers, and functions give a `Number`.
+//!
+//! Anything else, such as `true + 1` or `"a" < 2`, fails with a type error.
+//! [`Expression::evaluate`] converts the final result to an `f64`, reading a `Bool` as
+//! `1` or `0`.
+//!
+//! [`Expression::evaluate_value`]: crate::expression::Expression::evaluate_value
+//! [`Expression::evaluate`]: crate::expression::Expression::evaluate
+//! [`Context`]: crate::context::Context
+
+use crate::parsing::BinaryOperator;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Integer(i64),
+    Bool(bool),
+    String(String),
+    /// A missing value
+    Null,
+    List(Vec<Value>),
+}
+
+/// The type of a [`Value`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Type {
+    Number,
+    Integer,
+    Bool,
+    String,
+    Null,
+    List,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Type::Number => "Number",
+            Type::Integer => "Integer",
+            Type::Bool => "Bool",
+            Type::String => "String",
+            Type::Null => "Null",
+            Type::List => "List",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Value {
+    pub fn type_of(&self) -> Type {
+        match self {
+            Value::Number(_) => Type::Number,
+            Value::Integer(_) => Type::Integer,
+            Value::Bool(_) => Type::Bool,
+            Value::String(_) => Type::String,
+            Value::Null => Type::Null,
+            Value::List(_) => Type::List,
+        }
+    }
+
+    /// The value of a `Number`, or an `Integer` widened to `f64`
+    pub fn as_number(&self) -> Option<f64> {
+        match *self {
+            Value::Number(n) => Some(n),
+            Value::Integer(i) => Some(i as f64),
+            _ => None,
+        }
+    }
+
+    /// Whether the value counts as true in a condition, if it can be used as one
+    pub fn as_condition(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            Value::Number(n) => Some(n != 0.0),
+            Value::Integer(i) => Some(i != 0),
+            _ => None,
+        }
+    }
+
+    /// Equality as the `==` operator sees it.
+    ///
+    /// Unlike `PartialEq`, which compares structure, `Integer(1)` equals `Number(1.0)` and
+    /// `NaN` equals nothing.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::List(a), Value::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.equals(b))
+            }
+            (a, b) => match (a.as_number(), b.as_number()) {
+                (Some(a), Some(b)) => a == b,
+                _ => a == b,
+            },
+        }
+    }
+
+    /// Applies `+ - * / ^`, or `None` if the operator does not accept these types.
+    ///
+    /// Division by zero is left to the caller, giving infinity or NaN here.
+    pub(crate) fn arithmetic(operator: BinaryOperator, a: &Value, b: &Value) -> Option<Value> {
+        if let (Value::Integer(a), Value::Integer(b)) = (a, b) {
+            let exact = match operator {
+                BinaryOperator::Add => a.checked_add(*b),
+                BinaryOperator::Subtract => a.checked_sub(*b),
+                BinaryOperator::Multiply => a.checked_mul(*b),
+                _ => None,
+            };
+            if let Some(i) = exact {
+                return Some(Value::Integer(i));
+            }
+        }
+
+        match (operator, a, b) {
+            (BinaryOperator::Add, Value::String(a), Value::String(b)) => {
+                Some(Value::String(format!("{}{}", a, b)))
+            }
+            (BinaryOperator::Add, Value::List(a), Value::List(b)) => {
+                Some(Value::List(a.iter().chain(b).cloned().collect()))
+            }
+            _ => {
+                let (a, b) = (a.as_number()?, b.as_number()?);
+                Some(Value::Number(match operator {
+                    BinaryOperator::Add => a + b,
+                    BinaryOperator::Subtract => a - b,
+                    BinaryOperator::Multiply => a * b,
+                    BinaryOperator::Divide => a / b,
+                    BinaryOperator::Power => a.powf(b),
+                    _ => return None,
+                }))
+            }
+        }
+    }
+
+    /// Applies `< <= > >=`, or `None` if the operands cannot be ordered
+    pub(crate) fn compare(operator: BinaryOperator, a: &Value, b: &Value) -> Option<bool> {
+        let ordering = match (a, b) {
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            _ => a.as_number()?.partial_cmp(&b.as_number()?),
+        };
+
+        // An unordered NaN operand makes every comparison false
+        Some(ordering.is_some_and(|ordering| match operator {
+            BinaryOperator::Less => ordering.is_lt(),
+            BinaryOperator::LessEqual => ordering.is_le(),
+            BinaryOperator::Greater => ordering.is_gt(),
+            BinaryOperator::GreaterEqual => ordering.is_ge(),
+            _ => false,
+        }))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::String(text) => expression_grammar::parsing::write_string(f, text),
+            Value::Null => write!(f, "null"),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Integer(i)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::String(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::String(text)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        Value::List(items.into_iter().map(Into::into).collect())
+    }
+}
+
+/// `None` becomes [`Value::Null`]
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let add = |a: Value, b: Value| Value::arithmetic(BinaryOperator::Add, &a, &b);
+
+        assert_eq!(add(2.into(), 3.into()), Some(Value::Integer(5)));
+        assert_eq!(add(2.into(), 0.5.into()), Some(Value::Number(2.5)));
+        assert_eq!(
+            add(i64::MAX.into(), 1.into()),
+            Some(Value::Number(i64::MAX as f64 + 1.0))
+        );
+        assert_eq!(
+            Value::arithmetic(BinaryOperator::Divide, &7.into(), &2.into()),
+            Some(Value::Number(3.5))
+        );
+        assert_eq!(
+            Value::arithmetic(BinaryOperator::Power, &2.into(), &3.into()),
+            Some(Value::Number(8.0))
+        );
+    }
+
+    #[test]
+    fn test_concatenation() {
+        assert_eq!(
+            Value::arithmetic(BinaryOperator::Add, &"ab".into(), &"c".into()),
+            Some(Value::from("abc"))
+        );
+        assert_eq!(
+            Value::arithmetic(BinaryOperator::Add, &vec![1].into(), &vec![true].into()),
+            Some(Value::List(vec![Value::Integer(1), Value::Bool(true)]))
+        );
+        assert_eq!(
+            Value::arithmetic(BinaryOperator::Multiply, &"ab".into(), &2.into()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_type_errors() {
+        for (a, b) in [
+            (Value::Bool(true), Value::Number(1.0)),
+            (Value::Null, Value::Integer(1)),
+            (Value::from("1"), Value::Number(1.0)),
+            (Value::from(vec![1.0]), Value::Number(1.0)),
+        ] {
+            assert_eq!(Value::arithmetic(BinaryOperator::Add, &a, &b), None);
+            assert_eq!(Value::compare(BinaryOperator::Less, &a, &b), None);
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let less = |a: Value, b: Value| Value::compare(BinaryOperator::Less, &a, &b);
+
+        assert_eq!(less(1.into(), 1.5.into()), Some(true));
+        assert_eq!(less("apple".into(), "banana".into()), Some(true));
+        assert_eq!(less(f64::NAN.into(), 1.into()), Some(false));
+        assert_eq!(
+            Value::compare(
+                BinaryOperator::GreaterEqual,
+                &i64::MAX.into(),
+                &(i64::MAX - 1).into()
+            ),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_equals() {
+        assert!(Value::Integer(1).equals(&Value::Number(1.0)));
+        assert_ne!(Value::Integer(1), Value::Number(1.0));
+        assert!(Value::from(vec![1, 2]).equals(&Value::from(vec![1.0, 2.0])));
+        assert!(Value::Null.equals(&Value::Null));
+        assert!(!Value::from("1").equals(&Value::Integer(1)));
+        assert!(!Value::Bool(true).equals(&Value::Number(1.0)));
+        assert!(!Value::Number(f64::NAN).equals(&Value::Number(f64::NAN)));
+    }
+
+    #[test]
+    fn test_conditions() {
+        assert_eq!(Value::Bool(false).as_condition(), Some(false));
+        assert_eq!(Value::Integer(-3).as_condition(), Some(true));
+        assert_eq!(Value::Number(f64::NAN).as_condition(), Some(true));
+        assert_eq!(Value::Null.as_condition(), None);
+        assert_eq!(Value::from("").as_condition(), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let value = Value::from(vec![
+            Value::Integer(1),
+            Value::Number(2.5),
+            Value::from("a \"b\""),
+            Value::from(None::<bool>),
+        ]);
+        assert_eq!(value.to_string(), r#"[1, 2.5, "a \"b\"", null]"#);
+        assert_eq!(Type::Integer.to_string(), "Integer");
+    }
+}
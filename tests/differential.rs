@@ -0,0 +1,161 @@
+//! Checks that `expr!` and `Expression::parse` build identical trees from the same source.
+
+use expression_parser::expr;
+use expression_parser::expression::Expression;
+
+macro_rules! differential {
+    ($($source:literal),* $(,)?) => {
+        #[test]
+        fn test_macro_matches_runtime() {
+            $(
+                let runtime = Expression::parse($source).unwrap();
+                assert_eq!(expr!($source), runtime, "{:?}", $source);
+                assert_eq!(Expression::parse(&runtime.to_string()), Ok(runtime));
+            )*
+        }
+    };
+}
+
+differential! {
+    "42",
+    ".5 + 1.",
+    "x",
+    "_tmp1",
+    "order.qty * order.price",
+    "1 + 2 - 3 + 4",
+    "8 / 4 / 2 * 3",
+    "2 ^ 3 ^ 2",
+    "-2 ^ 2",
+    "(-2) ^ 2",
+    "2 ^ -x",
+    "--x",
+    "+-+x",
+    "x - -y",
+    "2 * -3 + +4",
+    "((x))",
+    "(x + y) * (x - y) / (1 + z ^ 2)",
+    "sin(x)",
+    "f()",
+    "max(1, x, y ^ 2, -z)",
+    "log(2, hypot(3, 4)) + sqrt(abs(min(x, -1)))",
+    "f(g(h(x)), (y))",
+    "x\n+\ty\r\n* 2",
+    "  1+2*3  ",
+    "x < 1 == y >= 2",
+    "a <= b != (c > d)",
+    "!x && !!y || z",
+    "a || b && c",
+    "(a || b) && c",
+    "-!x ^ 2",
+    "c ? a : b",
+    "c ? a ? 1 : 2 : d ? 3 : 4",
+    "(c ? a : b) * 2",
+    "if(x > 0, sqrt(x), if(y, 1, 2))",
+    "true || false && null",
+    r#"tier == "gold" ? [1, x, ["a\tb\\\""]] : []"#,
+    "let r = x ^ 2 + y ^ 2; sqrt(r) / r",
+    "let a = 1\nlet b = a * 2;\r\n  a + b",
+    "let x = x; let y = (let z = x; z) * 2; x ? y : -y",
+    "let(x) + let",
+    "v = v + a * dt; x = x + v * dt",
+    "let h = dt / 2\nx = x + h\nx > 1 ? x : -x",
+    "let = 1; a",
+}
+
+#[test]
+fn test_rejected_by_both() {
+    for source in [
+        "",
+        "1 +",
+        "2 3",
+        "(x",
+        "x)",
+        "f(1 2)",
+        "x @ y",
+        "1.2.3",
+        "* 2",
+        "#{x}",
+        "x == = 1",
+        "a & b",
+        "a ? b",
+        "a ? b : ",
+        "if(a, b)",
+        "!",
+        "\"abc",
+        "\"\\q\"",
+        "[1, 2",
+        "[1 2]",
+        "true(1)",
+        "let a 1; a",
+        "let a = 1 a",
+        "let a = 1;",
+        "let a = 1\n",
+        "1; 2",
+        "(x = 1)",
+        "x = 1 y = 2",
+        "let a = b = 1; a",
+        "x = ",
+        "1 = x",
+    ] {
+        let runtime = Expression::parse(source);
+        let grammar = expression_grammar::parse(source);
+        assert!(runtime.is_err(), "{:?}", source);
+        assert_eq!(runtime.err(), grammar.err(), "{:?}", source);
+    }
+}
+
+/// Checks `expr_fn!` against `Expression::evaluate` at a few points
+macro_rules! native {
+    ($($source:literal),* $(,)?) => {
+        #[test]
+        fn test_native_matches_evaluate() {
+            for (x, y) in [(0.5, 2.0), (-1.25, 3.0), (3.0, -0.75), (0.0, 1.0)] {
+                $(
+                    let native = expression_parser::expr_fn!($source, x, y)(x, y);
+                    let evaluated = expr!($source).evaluate(&[("x", x), ("y", y)]).unwrap();
+                    assert!(
+                        native == evaluated || native.is_nan() && evaluated.is_nan(),
+                        "{:?} at ({}, {}): {} != {}", $source, x, y, native, evaluated
+                    );
+                )*
+            }
+        }
+    };
+}
+
+native! {
+    "x ^ 2 + y",
+    "2 ^ 3 ^ 2 * x - -y",
+    "(x + y) * (x - y) / (1 + y ^ 2)",
+    "sin(x) + cos(y) + tan(x) + asin(x / 4) + acos(x / 4) + atan(y)",
+    "atan2(y, x) + sinh(x) + cosh(y) + tanh(x)",
+    "sqrt(abs(x)) + cbrt(y) + exp(x) + ln(abs(y)) + log2(y * y) + log10(abs(y))",
+    "log(2, abs(y)) + sign(x) + sign(y - 1) + floor(x) + ceil(y) + round(x) + trunc(y)",
+    "hypot(x, y) + min(x) + min(x, y, 0) + max(y) + max(x, y, -1)",
+    "sqrt(2) * x + log(10, 1000) + max(1, 2, 3)",
+    "x",
+    "(x < y ? 1 : 0) + (x <= y ? 2 : 0) + (x > y ? 4 : 0) + (x >= y ? 8 : 0) + (x == 0.5 ? 16 : 0)",
+    "x > 0 && y > 0 || !(x + y) ? x * y : if(y < 0, -y, 10 ^ 2)",
+    "x && y || !(x || 0) ? x : !y",
+    "y != 1",
+    "true && x > 0 || false ? 1 : x",
+    "1 < 2 ? x : y",
+    "let r = x * x + y * y; sqrt(r) / (r + 1)",
+    "let a = 2 ^ 3; let x = x + a\nlet b = a * x; b - x / a",
+    "let y = y > 0 ? y : -y; (let y = y + 1; y * x) + y",
+    "x = x * y; y = y + x\ny * 2",
+}
+
+#[test]
+fn test_native_constants_and_unused_parameters() {
+    let f = expression_parser::expr_fn!("2 ^ 10 - max(1, 4) / 2", x);
+    assert_eq!(f(123.0), 1022.0);
+    assert_eq!(expression_parser::expr_fn!("-1 / 0")(), f64::NEG_INFINITY);
+    assert!(expression_parser::expr_fn!("sqrt(-x)", x)(1.0).is_nan());
+
+    let value = 3.0;
+    assert_eq!(
+        expression_parser::expr_fn!("sign(value) * value", value)(value),
+        3.0
+    );
+}
@@ -0,0 +1,6 @@
+#[test]
+fn test_macro_expansion() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/pass/*.rs");
+    cases.compile_fail("tests/ui/fail/*.rs");
+}
@@ -0,0 +1,7 @@
+use expression_parser::expr;
+
+mod empty {}
+
+fn main() {
+    let _ = expr!("1 + x", crate = crate::empty);
+}
@@ -0,0 +1,9 @@
+use expression_parser::ExpressionContext;
+
+#[derive(ExpressionContext)]
+struct Customer {
+    #[expr(rename = "customer_name")]
+    name: String,
+}
+
+fn main() {}
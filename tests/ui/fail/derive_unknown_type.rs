@@ -0,0 +1,9 @@
+use expression_parser::ExpressionContext;
+
+#[derive(ExpressionContext)]
+struct Order {
+    qty: f64,
+    discount: Option<f64>,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use expression_parser::expr;
+
+fn main() {
+    let _ = expr!("1 + #{rate +}");
+    let _ = expr!("1 + #{rate");
+}
@@ -0,0 +1,6 @@
+use expression_parser::expr;
+
+fn main() {
+    let count = 3_i32;
+    let _ = expr!("#{count} * x");
+}
@@ -0,0 +1,5 @@
+use expression_parser::expr;
+
+fn main() {
+    let _ = expr!("2 * (x + 1");
+}
@@ -0,0 +1,5 @@
+use expression_parser::expr_fn;
+
+fn main() {
+    let _ = expr_fn!("sin(x, 2)", x);
+}
@@ -0,0 +1,7 @@
+#![deny(deprecated)]
+
+use expression_parser::expr;
+
+fn main() {
+    let _ = expr!("qty * order.price", vars = [qty, order.price, order.discount, type]);
+}
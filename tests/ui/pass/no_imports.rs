@@ -0,0 +1,6 @@
+// The expansion must not rely on anything being in scope at the call site
+fn main() {
+    let e = expression_parser::expr!("-x ^ 2 + max(x, 1) / 4");
+    let value = e.evaluate(&[("x", 3.0)]).unwrap();
+    assert_eq!(value, -8.25);
+}
@@ -0,0 +1,17 @@
+// A library that re-exports the parser under another name
+mod engine {
+    pub use expression_parser::*;
+}
+
+use engine::{ExpressionContext, expr};
+
+#[derive(ExpressionContext)]
+#[expr(crate = "crate::engine")]
+struct Order {
+    qty: f64,
+}
+
+fn main() {
+    let e: engine::expression::Expression = expr!("qty * 2", crate = crate::engine);
+    assert_eq!(e.evaluate(&Order { qty: 4.0 }), Ok(8.0));
+}
@@ -0,0 +1,28 @@
+use expression_parser::expr;
+
+// Local items with the same names as the ones the expansion uses
+#[allow(dead_code)]
+enum Expression {
+    Number(f64),
+}
+
+#[allow(dead_code)]
+struct Box;
+
+#[allow(unused_macros)]
+macro_rules! vec {
+    () => {};
+}
+
+mod std {}
+
+#[derive(expression_parser::ExpressionContext)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+fn main() {
+    let e = expr!("hypot(x, y) * 2");
+    assert_eq!(e.evaluate(&Point { x: 3.0, y: 4.0 }), Ok(10.0));
+}
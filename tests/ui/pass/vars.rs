@@ -0,0 +1,21 @@
+// Every declared variable is used, so denying the unused-variable warning is fine
+#![deny(deprecated)]
+
+use expression_parser::expr;
+
+fn main() {
+    let rate = expr!("base * 0.5", vars = [base]);
+    let e = expr!(
+        "#{rate} * order.qty + type",
+        vars = [order.qty, type,],
+        crate = ::expression_parser
+    );
+    assert_eq!(
+        e.evaluate(&[("base", 2.0), ("order.qty", 3.0), ("type", 1.0)]),
+        Ok(4.0)
+    );
+
+    // Assigned variables must be declared too, while `let` bindings are not variables
+    let step = expr!("let h = dt / 2; x = x + v * h", vars = [x, v, dt]);
+    assert_eq!(step.evaluate(&[("x", 1.0), ("v", 2.0), ("dt", 1.0)]), Ok(2.0));
+}